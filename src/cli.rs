@@ -0,0 +1,280 @@
+use crate::{
+    config::Config,
+    feed::{DEFAULT_CHANNEL, WS_URL},
+    filter_expr,
+    proxy::Proxy,
+    symbols, SearchMode, Synchronous, DB_PATH,
+};
+use std::env;
+
+const WS_URL_ENV: &str = "TRADEMAXXING_WS_URL";
+const CHANNEL_ENV: &str = "TRADEMAXXING_CHANNEL";
+const DB_ENV: &str = "TRADEMAXXING_DB";
+
+#[derive(Debug)]
+pub struct Args {
+    pub no_color: bool,
+    pub wal: bool,
+    pub synchronous: Synchronous,
+    /// Import trades from this CSV file and exit instead of starting the TUI.
+    pub import: Option<String>,
+    /// Address for the HTTP endpoints (`/metrics`, `/trades`), e.g.
+    /// `127.0.0.1:9100`.
+    pub serve: Option<String>,
+    /// Config file to use instead of the default location.
+    pub config: Option<String>,
+    pub ws_url: Option<String>,
+    pub channel: Option<String>,
+    pub db_path: Option<String>,
+    /// Append every raw websocket frame to this file.
+    pub record: Option<String>,
+    /// Play a `--record` file back instead of connecting, into an in-memory
+    /// database so the replayed trades don't join the saved ones.
+    pub replay_raw: Option<String>,
+    /// Coin to search for on startup.
+    pub symbol: Option<String>,
+    pub search_mode: Option<SearchMode>,
+    /// Trade type to show on startup, e.g. `live-trade`.
+    pub filter: Option<String>,
+    /// `BUY` or `SELL`.
+    pub action: Option<String>,
+    pub min_value: Option<f64>,
+    pub user: Option<String>,
+    /// Source of a `?` filter expression, checked while parsing.
+    pub expr: Option<String>,
+    /// IANA zone name to display times in, e.g. `UTC` or `Europe/Berlin`.
+    pub tz: Option<String>,
+    /// Delete trades older than this many days, at startup and then hourly.
+    pub retention_days: Option<u32>,
+    /// Give up after this many reconnect attempts in a row; 0 retries forever.
+    pub max_reconnects: u32,
+    /// Export trades to this directory on quit, over `auto_export.dir`.
+    pub export_on_exit: Option<String>,
+    /// Connect through this proxy, `http://` (CONNECT) or `socks5://`,
+    /// instead of the one in `ALL_PROXY`/`HTTPS_PROXY`/`HTTP_PROXY`.
+    pub proxy: Option<String>,
+    /// Discard trades below this USD value before they're saved. Unlike
+    /// `--min-value` the data is gone for good.
+    pub ingest_min_value: Option<f64>,
+    /// Discard trades in any other coin before they're saved.
+    pub ingest_coins: Option<Vec<String>>,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Args {
+            no_color: false,
+            wal: true,
+            synchronous: Synchronous::Normal,
+            import: None,
+            serve: None,
+            config: None,
+            ws_url: None,
+            channel: None,
+            db_path: None,
+            record: None,
+            replay_raw: None,
+            symbol: None,
+            search_mode: None,
+            filter: None,
+            action: None,
+            min_value: None,
+            user: None,
+            expr: None,
+            tz: None,
+            retention_days: None,
+            max_reconnects: 0,
+            export_on_exit: None,
+            proxy: None,
+            ingest_min_value: None,
+            ingest_coins: None,
+        }
+    }
+}
+
+fn flag_value(iter: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, String> {
+    iter.next().ok_or_else(|| format!("{} needs a value", flag))
+}
+
+/// Settings that can come from a flag, the environment or the config file.
+#[derive(Debug)]
+pub struct Settings {
+    pub ws_url: String,
+    /// Channel to subscribe to when no subscriptions are configured.
+    pub channel: String,
+    pub db_path: String,
+}
+
+/// A CLI flag beats the environment, which beats the config file, which
+/// beats the built-in default.
+fn resolve_setting(
+    flag: Option<String>,
+    env_value: Option<String>,
+    configured: Option<String>,
+    default: &str,
+) -> String {
+    flag.or(env_value).or(configured).unwrap_or_else(|| default.to_string())
+}
+
+pub fn resolve_settings(args: &Args, config: &Config) -> Settings {
+    Settings {
+        ws_url: resolve_setting(args.ws_url.clone(), env_setting(WS_URL_ENV), config.ws_url.clone(), WS_URL),
+        channel: resolve_setting(
+            args.channel.clone(),
+            env_setting(CHANNEL_ENV),
+            config.channel.clone(),
+            DEFAULT_CHANNEL,
+        ),
+        db_path: resolve_setting(args.db_path.clone(), env_setting(DB_ENV), config.db_path.clone(), DB_PATH),
+    }
+}
+
+/// Reads an environment variable, treating an empty value as unset.
+fn env_setting(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|v| !v.is_empty())
+}
+
+pub fn parse_args() -> Result<Args, String> {
+    let mut args = Args::default();
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--no-color" => args.no_color = true,
+            "--no-wal" => args.wal = false,
+            "--synchronous" => {
+                let value = flag_value(&mut iter, &arg)?;
+                args.synchronous = Synchronous::parse(&value).ok_or_else(|| {
+                    format!("Invalid --synchronous value: {} (expected off, normal, full or extra)", value)
+                })?;
+            }
+            "--import" => args.import = Some(flag_value(&mut iter, &arg)?),
+            "--serve" => args.serve = Some(flag_value(&mut iter, &arg)?),
+            "--config" => args.config = Some(flag_value(&mut iter, &arg)?),
+            "--ws-url" => args.ws_url = Some(flag_value(&mut iter, &arg)?),
+            "--channel" => args.channel = Some(flag_value(&mut iter, &arg)?),
+            "--db" => args.db_path = Some(flag_value(&mut iter, &arg)?),
+            "--record" => args.record = Some(flag_value(&mut iter, &arg)?),
+            "--replay-raw" => args.replay_raw = Some(flag_value(&mut iter, &arg)?),
+            "--symbol" => {
+                let value = flag_value(&mut iter, &arg)?;
+                let symbol = value.trim();
+                if symbol.is_empty() {
+                    return Err("--symbol needs a non-empty coin symbol".to_string());
+                }
+                args.symbol = Some(symbols::normalize(symbol));
+            }
+            "--search-mode" => {
+                let value = flag_value(&mut iter, &arg)?;
+                args.search_mode = Some(SearchMode::parse(&value).ok_or_else(|| {
+                    format!("Invalid --search-mode value: {} (expected exact, substring or fuzzy)", value)
+                })?);
+            }
+            "--filter" => {
+                let value = flag_value(&mut iter, &arg)?;
+                args.filter = Some(match value.to_lowercase().as_str() {
+                    "live" => "live-trade".to_string(),
+                    "all" => "all-trades".to_string(),
+                    other => other.to_string(),
+                });
+            }
+            "--action" => {
+                let value = flag_value(&mut iter, &arg)?.to_uppercase();
+                if value != "BUY" && value != "SELL" {
+                    return Err(format!("Invalid --action value: {} (expected buy or sell)", value));
+                }
+                args.action = Some(value);
+            }
+            "--min-value" => {
+                let value = flag_value(&mut iter, &arg)?;
+                args.min_value = Some(value.parse::<f64>().ok().filter(|v| v.is_finite() && *v >= 0.0).ok_or_else(
+                    || format!("Invalid --min-value value: {} (expected a USD amount)", value),
+                )?);
+            }
+            "--user" => args.user = Some(flag_value(&mut iter, &arg)?),
+            "--expr" => {
+                let value = flag_value(&mut iter, &arg)?;
+                filter_expr::parse(&value).map_err(|e| format!("Invalid --expr value: {}", e))?;
+                args.expr = Some(value);
+            }
+            "--tz" => args.tz = Some(flag_value(&mut iter, &arg)?),
+            "--retention-days" => {
+                let value = flag_value(&mut iter, &arg)?;
+                let days = value.parse::<u32>().ok().filter(|days| *days > 0).ok_or_else(|| {
+                    format!("Invalid --retention-days value: {} (expected a positive whole number)", value)
+                })?;
+                args.retention_days = Some(days);
+            }
+            "--max-reconnects" => {
+                let value = flag_value(&mut iter, &arg)?;
+                args.max_reconnects = value.parse().map_err(|_| {
+                    format!("Invalid --max-reconnects value: {} (expected a whole number, 0 for no limit)", value)
+                })?;
+            }
+            "--ingest-min-value" => {
+                let value = flag_value(&mut iter, &arg)?;
+                args.ingest_min_value = Some(value.parse::<f64>().ok().filter(|v| v.is_finite() && *v >= 0.0).ok_or_else(
+                    || format!("Invalid --ingest-min-value value: {} (expected a USD amount)", value),
+                )?);
+            }
+            "--ingest-coins" => {
+                let value = flag_value(&mut iter, &arg)?;
+                let coins: Vec<String> = value
+                    .split(',')
+                    .map(symbols::normalize)
+                    .filter(|coin| !coin.is_empty())
+                    .collect();
+                if coins.is_empty() {
+                    return Err("--ingest-coins needs a comma-separated list of coin symbols".to_string());
+                }
+                args.ingest_coins = Some(coins);
+            }
+            "--export-on-exit" => args.export_on_exit = Some(flag_value(&mut iter, &arg)?),
+            "--proxy" => {
+                let value = flag_value(&mut iter, &arg)?;
+                Proxy::parse(&value).map_err(|e| format!("Invalid --proxy value: {}", e))?;
+                args.proxy = Some(value);
+            }
+            other => return Err(format!("Unknown argument: {}", other)),
+        }
+    }
+
+    // https://no-color.org: any non-empty value disables color.
+    if env::var("NO_COLOR").is_ok_and(|v| !v.is_empty()) {
+        args.no_color = true;
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn some(s: &str) -> Option<String> {
+        Some(s.to_string())
+    }
+
+    #[test]
+    fn flag_beats_everything() {
+        assert_eq!(resolve_setting(some("flag"), some("env"), some("config"), "default"), "flag");
+    }
+
+    #[test]
+    fn env_beats_config_and_default() {
+        assert_eq!(resolve_setting(None, some("env"), some("config"), "default"), "env");
+    }
+
+    #[test]
+    fn config_beats_default() {
+        assert_eq!(resolve_setting(None, None, some("config"), "default"), "config");
+    }
+
+    #[test]
+    fn default_when_nothing_set() {
+        assert_eq!(resolve_setting(None, None, None, "default"), "default");
+    }
+
+    #[test]
+    fn flag_applies_without_env() {
+        assert_eq!(resolve_setting(some("flag"), None, None, "default"), "flag");
+    }
+}
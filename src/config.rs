@@ -0,0 +1,774 @@
+use crate::{filter_expr, keymap::KeyBinding, theme::ThemeName};
+use ratatui::style::Color;
+use serde::{
+    Deserialize, Deserializer, Serialize,
+    de::{MapAccess, Visitor},
+};
+use serde_json::value::RawValue;
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+pub const CONFIG_FILE_NAME: &str = "config.json";
+/// Subdirectory of the platform config dir (`~/.config` on Linux).
+const CONFIG_DIR_NAME: &str = "trademaxxing";
+pub const DEFAULT_VALUE_TIERS: [f64; 4] = [10.0, 100.0, 1000.0, 10000.0];
+pub const DEFAULT_AMOUNT_TIERS: [f64; 4] = [1000.0, 100000.0, 1000000.0, 100000000.0];
+pub const DEFAULT_MAX_RENDERED_ROWS: usize = 200;
+pub const DEFAULT_MOVING_AVERAGE_PERIOD: usize = 20;
+pub const DEFAULT_DISPLAY_CAPACITY: usize = 10_000;
+/// Number columns widen by one per decimal past their default, so this caps
+/// how much of the tape they can take.
+pub const MAX_COLUMN_DECIMALS: usize = 12;
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
+pub struct Config {
+    /// Column ids in display order, e.g. `["coin", "action", "user"]`.
+    /// Columns left out are hidden.
+    pub columns: Option<Vec<String>>,
+    /// Markers prefixed to the Action cell so direction doesn't rely on color.
+    pub action_glyphs: ActionGlyphs,
+    /// Raw JSON messages sent after every (re)connect. Defaults to the
+    /// global trades subscription.
+    pub subscriptions: Option<Vec<String>>,
+    /// Send something whenever the connection has been quiet this long, for
+    /// servers that drop idle clients. Off unless set; only for ws:// feeds.
+    pub keepalive: Option<Keepalive>,
+    /// Start with columns stretched to the terminal width instead of fixed.
+    pub auto_fit_columns: bool,
+    /// Esc restores the last submitted search instead of keeping typed text.
+    pub escape_reverts_search: bool,
+    /// Briefly highlight newly arrived trades.
+    pub flash_new_trades: bool,
+    /// Thin out the tape while the feed is busy. Off unless configured.
+    pub view_sampling: Option<ViewSampling>,
+    /// Pause the tape when the feed bursts, so it stays readable. Off
+    /// unless configured.
+    pub auto_pause: Option<AutoPause>,
+    /// Ascending USD floors for the row color tiers, also used as the
+    /// minimum-value filter steps.
+    pub value_tiers: [f64; 4],
+    /// Ascending token-amount floors for the row color tiers in amount mode.
+    pub amount_tiers: [f64; 4],
+    /// Whether rows are colored by USD value or by token amount at startup.
+    pub color_mode: ColorMode,
+    /// Built-in color theme: `default`, `high-contrast`, `monochrome` or
+    /// `solarized`. Cycling themes in the UI saves the choice here.
+    pub theme: ThemeName,
+    /// Scrollbar characters and colors, over what the theme uses.
+    pub scrollbar: Scrollbar,
+    /// Ring the terminal bell on big trades. Off unless configured.
+    pub bell_alert: Option<BellAlert>,
+    /// Alert when a coin trades a lot of volume in a short window. Off unless
+    /// configured.
+    pub volume_alert: Option<VolumeAlert>,
+    /// Write trades to a timestamped file on quit. Off unless configured;
+    /// `--export-on-exit` turns it on too.
+    pub auto_export: Option<AutoExport>,
+    /// Websocket endpoint; `--ws-url` and `TRADEMAXXING_WS_URL` win over this.
+    pub ws_url: Option<String>,
+    /// Channel subscribed to when `subscriptions` isn't set.
+    pub channel: Option<String>,
+    /// SQLite database file.
+    pub db_path: Option<String>,
+    /// Action name to key spec(s), e.g. `{"quit": "ctrl-c", "search": ["/", "s"]}`.
+    /// Unlisted actions keep their default keys.
+    pub keymap: BTreeMap<String, KeyBinding>,
+    /// Per numeric column id (`amount`, `total`, `price`, `implied`): `full` or `compact`.
+    pub column_formats: BTreeMap<String, NumberFormat>,
+    /// Per numeric column id: decimal places, at most `MAX_COLUMN_DECIMALS`.
+    /// Unlisted columns keep 2 for `amount` and `total`, 8 for the prices.
+    pub column_decimals: BTreeMap<String, usize>,
+    /// Digit grouping in those columns: `none`, `comma`, `period` or `space`.
+    pub number_grouping: NumberGrouping,
+    /// IANA zone to display times in; `--tz` wins. Defaults to the system zone.
+    pub timezone: Option<String>,
+    /// Show times down to the millisecond (`HH:MM:SS.mmm`).
+    pub show_millis: bool,
+    /// `plain` or `ansi` text for the screen snapshot key.
+    pub snapshot_format: SnapshotFormat,
+    /// `csv` or `json` for the balance export key.
+    pub balance_export_format: ExportFormat,
+    /// Filter the tape while typing a symbol instead of only on Enter.
+    pub live_search: bool,
+    /// How the coins view shades rows by time since each coin last traded.
+    pub activity_thresholds: ActivityThresholds,
+    /// Bound on trades waiting for the UI, and what happens past it.
+    pub trade_queue: TradeQueue,
+    /// Smallest terminal the full layout is drawn in.
+    pub min_terminal_size: MinTerminalSize,
+    /// (user, coin) balances kept in memory before zero balances are
+    /// dropped. Non-zero ones are always kept, so this is a soft cap.
+    /// Unbounded unless configured.
+    pub max_balance_entries: Option<usize>,
+    /// Most trade rows drawn per frame, for slow terminals on tall screens.
+    pub max_rendered_rows: usize,
+    /// Most trades kept in memory for the tape. Older ones stay in the
+    /// database and are paged back in when scrolling past the end.
+    pub display_capacity: usize,
+    /// What to do with trades whose amount or price came through as zero.
+    pub incomplete_trades: IncompleteTrades,
+    /// Where the tape starts: `top` (newest) or `bottom` (oldest loaded).
+    pub initial_scroll: InitialScroll,
+    /// While scrolled to the top, let new trades push the view down. When
+    /// off the view stays on the trades it shows.
+    pub follow_new_trades: bool,
+    /// Most rows a second the view scrolls to reach new trades while
+    /// following, so bursts glide in instead of jumping. Unset jumps.
+    pub auto_scroll_speed: Option<f64>,
+    /// Fade trades on the tape as they age so the newest stand out. Off
+    /// unless configured.
+    pub dim_old_trades: Option<TradeDecay>,
+    /// Trades averaged for the moving average on the price chart; 0 hides
+    /// it. `+` and `-` change it while running.
+    pub moving_average_period: usize,
+    /// Named filter bundles for the palette's `preset` command, on top of
+    /// the built-in `whale` (which one with that name replaces).
+    pub presets: BTreeMap<String, Preset>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            columns: None,
+            action_glyphs: ActionGlyphs::default(),
+            subscriptions: None,
+            keepalive: None,
+            auto_fit_columns: false,
+            escape_reverts_search: false,
+            flash_new_trades: true,
+            view_sampling: None,
+            auto_pause: None,
+            value_tiers: DEFAULT_VALUE_TIERS,
+            amount_tiers: DEFAULT_AMOUNT_TIERS,
+            color_mode: ColorMode::Value,
+            theme: ThemeName::Default,
+            scrollbar: Scrollbar::default(),
+            bell_alert: None,
+            volume_alert: None,
+            auto_export: None,
+            ws_url: None,
+            channel: None,
+            db_path: None,
+            keymap: BTreeMap::new(),
+            column_formats: BTreeMap::new(),
+            column_decimals: BTreeMap::new(),
+            number_grouping: NumberGrouping::None,
+            timezone: None,
+            show_millis: false,
+            snapshot_format: SnapshotFormat::Plain,
+            balance_export_format: ExportFormat::Csv,
+            live_search: false,
+            activity_thresholds: ActivityThresholds::default(),
+            trade_queue: TradeQueue::default(),
+            min_terminal_size: MinTerminalSize::default(),
+            max_rendered_rows: DEFAULT_MAX_RENDERED_ROWS,
+            display_capacity: DEFAULT_DISPLAY_CAPACITY,
+            incomplete_trades: IncompleteTrades::Tag,
+            max_balance_entries: None,
+            initial_scroll: InitialScroll::Top,
+            follow_new_trades: true,
+            auto_scroll_speed: None,
+            dim_old_trades: None,
+            moving_average_period: DEFAULT_MOVING_AVERAGE_PERIOD,
+            presets: BTreeMap::new(),
+        }
+    }
+}
+
+/// Seconds since a coin's last trade at which its row fades a step.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct ActivityThresholds {
+    pub active_secs: i64,
+    pub recent_secs: i64,
+    pub stale_secs: i64,
+}
+
+impl Default for ActivityThresholds {
+    fn default() -> Self {
+        ActivityThresholds {
+            active_secs: 10,
+            recent_secs: 60,
+            stale_secs: 300,
+        }
+    }
+}
+
+/// How a screen snapshot is written.
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SnapshotFormat {
+    /// Just the characters.
+    #[default]
+    Plain,
+    /// With color escapes, for `cat` in a terminal.
+    Ansi,
+}
+
+/// What the feed thread does when the UI has fallen this far behind.
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverflowPolicy {
+    /// Wait for the UI to catch up; nothing is lost, but the feed stalls.
+    #[default]
+    Block,
+    /// Discard the oldest queued trade. Discarded trades are never saved.
+    DropOldest,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Keepalive {
+    /// Seconds without anything sent before a keepalive goes out.
+    pub interval_secs: u64,
+    /// Raw JSON message to send. Unset sends a websocket ping frame.
+    pub message: Option<String>,
+}
+
+impl Default for Keepalive {
+    fn default() -> Self {
+        Keepalive {
+            interval_secs: 30,
+            message: None,
+        }
+    }
+}
+
+/// Rows keep full brightness for `step_secs`, are dimmed after that and
+/// greyed out after twice that. Value and action colors stay on dimmed
+/// rows; only untiered rows turn grey.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct TradeDecay {
+    pub step_secs: u64,
+}
+
+impl Default for TradeDecay {
+    fn default() -> Self {
+        TradeDecay { step_secs: 60 }
+    }
+}
+
+/// The queue of trades between the feed thread and the UI.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct TradeQueue {
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for TradeQueue {
+    fn default() -> Self {
+        TradeQueue {
+            capacity: 10_000,
+            overflow: OverflowPolicy::Block,
+        }
+    }
+}
+
+/// Below this size only a "terminal too small" notice is drawn.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct MinTerminalSize {
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Default for MinTerminalSize {
+    fn default() -> Self {
+        MinTerminalSize { width: 40, height: 20 }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct AutoExport {
+    /// Directory the file is written to; created if missing.
+    pub dir: String,
+    pub format: ExportFormat,
+    pub scope: ExportScope,
+}
+
+impl Default for AutoExport {
+    fn default() -> Self {
+        AutoExport {
+            dir: ".".to_string(),
+            format: ExportFormat::Csv,
+            scope: ExportScope::Session,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Same layout `--import` reads.
+    #[default]
+    Csv,
+    Json,
+}
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportScope {
+    /// Trades received since the app started.
+    #[default]
+    Session,
+    /// Every trade in the database.
+    All,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct BellAlert {
+    /// Trades worth at least this much USD ring the bell.
+    pub min_value: f64,
+    /// Minimum seconds between two bells, however many big trades arrive.
+    pub min_interval_secs: u64,
+    /// Search for the coin of a big trade as it arrives.
+    pub auto_search: bool,
+}
+
+impl Default for BellAlert {
+    fn default() -> Self {
+        BellAlert {
+            min_value: 10000.0,
+            min_interval_secs: 5,
+            auto_search: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct VolumeAlert {
+    /// Length of the rolling window, in seconds.
+    pub window_secs: u64,
+    /// USD volume within the window that counts as a spike.
+    pub min_volume: f64,
+    /// Seconds before the same coin can alert again.
+    pub cooldown_secs: u64,
+    /// Search for the spiking coin when the alert fires.
+    pub auto_search: bool,
+}
+
+impl Default for VolumeAlert {
+    fn default() -> Self {
+        VolumeAlert {
+            window_secs: 60,
+            min_volume: 50000.0,
+            cooldown_secs: 300,
+            auto_search: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct ViewSampling {
+    /// Show one trade in this many while the feed is hot.
+    pub every: u32,
+    /// Trades per second above which the feed counts as hot.
+    pub hot_trades_per_sec: f64,
+}
+
+impl Default for ViewSampling {
+    fn default() -> Self {
+        ViewSampling {
+            every: 10,
+            hot_trades_per_sec: 20.0,
+        }
+    }
+}
+
+/// While paused the view holds still; trades keep being saved and collect
+/// above it until the tape is resumed.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct AutoPause {
+    /// Trades per second arriving from the feed that count as a burst.
+    pub trades_per_sec: f64,
+    /// Resume by itself once the rate drops back below `trades_per_sec`,
+    /// instead of waiting for the pause key.
+    pub resume_when_calm: bool,
+}
+
+impl Default for AutoPause {
+    fn default() -> Self {
+        AutoPause {
+            trades_per_sec: 30.0,
+            resume_when_calm: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IncompleteTrades {
+    /// Treat them like any other trade.
+    Keep,
+    /// Save and show them, styled apart, but leave them out of balances,
+    /// prices and stats.
+    #[default]
+    Tag,
+    /// Discard them, only counting how many.
+    Drop,
+}
+
+/// Order of the trade tape.
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TapeSort {
+    /// Newest first, as they arrive.
+    #[default]
+    Newest,
+    /// Largest USD value first.
+    Total,
+}
+
+/// A named set of tape filters, applied all at once. Filters it leaves out
+/// are cleared.
+#[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Preset {
+    /// Trade type, e.g. `live-trade`.
+    pub trade_type: Option<String>,
+    /// `BUY` or `SELL`.
+    pub action: Option<String>,
+    pub min_value: Option<f64>,
+    pub user: Option<String>,
+    /// A `?` filter expression.
+    pub expr: Option<String>,
+    pub sort: TapeSort,
+    /// Overrides `flash_new_trades` while the preset is on.
+    pub flash: Option<bool>,
+}
+
+pub const WHALE_PRESET: &str = "whale";
+
+/// The built-in presets, by name.
+pub fn builtin_presets() -> BTreeMap<String, Preset> {
+    BTreeMap::from([(
+        WHALE_PRESET.to_string(),
+        Preset {
+            trade_type: Some("live-trade".to_string()),
+            min_value: Some(5000.0),
+            sort: TapeSort::Total,
+            flash: Some(true),
+            ..Preset::default()
+        },
+    )])
+}
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum InitialScroll {
+    #[default]
+    Top,
+    Bottom,
+}
+
+/// What the row color tiers are keyed off.
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    /// The trade's total USD value, against `value_tiers`.
+    #[default]
+    Value,
+    /// The number of tokens traded, against `amount_tiers`.
+    Amount,
+}
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NumberFormat {
+    /// Every digit at the column's usual precision.
+    #[default]
+    Full,
+    /// Values of 1,000 and up abbreviated with K/M/B.
+    Compact,
+}
+
+/// Thousands separator and decimal mark for the tape's number columns.
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NumberGrouping {
+    /// `1234.56`
+    #[default]
+    None,
+    /// `1,234.56`
+    Comma,
+    /// `1.234,56`
+    Period,
+    /// `1 234,56`
+    Space,
+}
+
+impl NumberGrouping {
+    /// Thousands separator and decimal mark; `None` leaves numbers as is.
+    pub fn separators(self) -> Option<(char, char)> {
+        match self {
+            NumberGrouping::None => None,
+            NumberGrouping::Comma => Some((',', '.')),
+            NumberGrouping::Period => Some(('.', ',')),
+            NumberGrouping::Space => Some((' ', ',')),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Scrollbar {
+    /// Character set for the thumb and track.
+    pub chars: ScrollbarChars,
+    /// Overrides the preset's thumb character.
+    pub thumb: Option<char>,
+    /// Overrides the preset's track character.
+    pub track: Option<char>,
+    /// Color name (`"gray"`, `"#586e75"`, ...) over the theme's.
+    pub thumb_color: Option<String>,
+    pub track_color: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScrollbarChars {
+    #[default]
+    Blocks,
+    /// For fonts and terminals that mangle block elements.
+    Ascii,
+}
+
+impl ScrollbarChars {
+    /// (thumb, track)
+    pub fn chars(self) -> (char, char) {
+        match self {
+            ScrollbarChars::Blocks => ('\u{2588}', '\u{2591}'),
+            ScrollbarChars::Ascii => ('|', ':'),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ActionGlyphs {
+    #[default]
+    Signs,
+    Arrows,
+    None,
+}
+
+impl ActionGlyphs {
+    /// (buy, sell) markers, each a single column wide.
+    pub fn markers(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            ActionGlyphs::Signs => Some(("+", "\u{2212}")),
+            ActionGlyphs::Arrows => Some(("\u{25b2}", "\u{25bc}")),
+            ActionGlyphs::None => None,
+        }
+    }
+}
+
+/// `--config` wins; otherwise a `config.json` in the working directory
+/// (where older versions looked), then the per-user config dir.
+fn resolve_config_path(flag: Option<&str>) -> Option<PathBuf> {
+    if let Some(path) = flag {
+        return Some(PathBuf::from(path));
+    }
+    let legacy = PathBuf::from(CONFIG_FILE_NAME);
+    if legacy.exists() {
+        return Some(legacy);
+    }
+    dirs::config_dir().map(|dir| dir.join(CONFIG_DIR_NAME).join(CONFIG_FILE_NAME))
+}
+
+/// Writes the defaults to `path` so there's a complete file to edit.
+fn write_default_config(path: &Path) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let contents = serde_json::to_string_pretty(&Config::default()).map_err(|e| e.to_string())?;
+    fs::write(path, contents + "\n").map_err(|e| e.to_string())
+}
+
+/// A config file's top-level entries in the order they're written, each
+/// value kept as its original text.
+struct RawSettings(Vec<(String, Box<RawValue>)>);
+
+impl<'de> Deserialize<'de> for RawSettings {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EntriesVisitor;
+
+        impl<'de> Visitor<'de> for EntriesVisitor {
+            type Value = RawSettings;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<RawSettings, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(RawSettings(entries))
+            }
+        }
+
+        deserializer.deserialize_map(EntriesVisitor)
+    }
+}
+
+/// Sets one top-level key in the config file, leaving the rest of it as the
+/// user wrote it, key order included. Creates the file from the defaults if
+/// there isn't one yet, unless it was named with `--config`.
+pub fn save_setting(flag: Option<&str>, key: &str, value: serde_json::Value) -> Result<(), String> {
+    let path = resolve_config_path(flag).ok_or("no config directory")?;
+    if !path.exists() {
+        if flag.is_some() {
+            return Err(format!("{} not found", path.display()));
+        }
+        write_default_config(&path)?;
+    }
+    let contents = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let RawSettings(mut settings) =
+        serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+    // Indented to sit one level into the file's object.
+    let pretty = serde_json::to_string_pretty(&value).map_err(|e| e.to_string())?.replace('\n', "\n  ");
+    let value = RawValue::from_string(pretty).map_err(|e| e.to_string())?;
+    match settings.iter_mut().find(|(existing, _)| existing == key) {
+        Some((_, existing)) => *existing = value,
+        None => settings.push((key.to_string(), value)),
+    }
+    let mut contents = String::from("{\n");
+    for (i, (key, value)) in settings.iter().enumerate() {
+        let separator = if i + 1 < settings.len() { "," } else { "" };
+        contents += &format!("  {}: {}{}\n", serde_json::Value::from(key.as_str()), value.get(), separator);
+    }
+    contents += "}\n";
+    fs::write(&path, contents).map_err(|e| e.to_string())
+}
+
+/// Loads the config, creating a default file on first run. An explicit
+/// `--config` path is never created, only read.
+pub fn load_or_create_config(flag: Option<&str>) -> Config {
+    let Some(path) = resolve_config_path(flag) else {
+        return Config::default();
+    };
+    if !path.exists() {
+        if flag.is_some() {
+            eprintln!("Config file {} not found. Using defaults.", path.display());
+        } else if let Err(e) = write_default_config(&path) {
+            eprintln!("Couldn't create {}: {}", path.display(), e);
+        }
+        return Config::default();
+    }
+    load_config(&path)
+}
+
+fn load_config(path: &Path) -> Config {
+    let mut config: Config = match fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+            eprintln!("Failed to parse {}: {}. Using defaults.", path.display(), e);
+            Config::default()
+        }),
+        Err(e) => {
+            eprintln!("Failed to read {}: {}. Using defaults.", path.display(), e);
+            Config::default()
+        }
+    };
+    if !config.value_tiers.windows(2).all(|pair| pair[0] < pair[1]) {
+        eprintln!("value_tiers must be strictly ascending: {:?}. Using defaults.", config.value_tiers);
+        config.value_tiers = DEFAULT_VALUE_TIERS;
+    }
+    if !config.amount_tiers.windows(2).all(|pair| pair[0] < pair[1]) {
+        eprintln!("amount_tiers must be strictly ascending: {:?}. Using defaults.", config.amount_tiers);
+        config.amount_tiers = DEFAULT_AMOUNT_TIERS;
+    }
+    if config.max_balance_entries.is_some_and(|max| max < 10) {
+        eprintln!("max_balance_entries must be at least 10. Not capping balances.");
+        config.max_balance_entries = None;
+    }
+    if config.max_rendered_rows == 0 {
+        eprintln!("max_rendered_rows must be at least 1. Using {}.", DEFAULT_MAX_RENDERED_ROWS);
+        config.max_rendered_rows = DEFAULT_MAX_RENDERED_ROWS;
+    }
+    if config.trade_queue.capacity == 0 {
+        eprintln!("trade_queue.capacity must be at least 1. Using {}.", TradeQueue::default().capacity);
+        config.trade_queue.capacity = TradeQueue::default().capacity;
+    }
+    if config.auto_scroll_speed.is_some_and(|speed| !speed.is_finite() || speed <= 0.0) {
+        eprintln!("auto_scroll_speed must be a positive number of rows per second. Scrolling instantly.");
+        config.auto_scroll_speed = None;
+    }
+    if config.auto_pause.is_some_and(|pause| !pause.trades_per_sec.is_finite() || pause.trades_per_sec <= 0.0) {
+        eprintln!("auto_pause.trades_per_sec must be a positive number. Not pausing on bursts.");
+        config.auto_pause = None;
+    }
+    if config.dim_old_trades.is_some_and(|decay| decay.step_secs == 0) {
+        eprintln!("dim_old_trades.step_secs must be at least 1. Not dimming old trades.");
+        config.dim_old_trades = None;
+    }
+    if config.keepalive.as_ref().is_some_and(|keepalive| keepalive.interval_secs == 0) {
+        eprintln!("keepalive.interval_secs must be at least 1. Not sending keepalives.");
+        config.keepalive = None;
+    }
+    if let Some(keepalive) = &mut config.keepalive
+        && keepalive.message.as_deref().is_some_and(|message| serde_json::from_str::<serde_json::Value>(message).is_err())
+    {
+        eprintln!("keepalive.message isn't valid JSON. Sending ping frames instead.");
+        keepalive.message = None;
+    }
+    if config.display_capacity == 0 {
+        eprintln!("display_capacity must be at least 1. Using {}.", DEFAULT_DISPLAY_CAPACITY);
+        config.display_capacity = DEFAULT_DISPLAY_CAPACITY;
+    }
+    config.presets.retain(|name, preset| {
+        if let Some(Err(e)) = preset.expr.as_deref().map(filter_expr::parse) {
+            eprintln!("Ignoring preset '{}': invalid expr: {}", name, e);
+            return false;
+        }
+        preset.action = preset.action.as_ref().map(|action| action.to_uppercase());
+        true
+    });
+    for color in [&mut config.scrollbar.thumb_color, &mut config.scrollbar.track_color] {
+        if let Some(name) = color
+            && name.parse::<Color>().is_err()
+        {
+            eprintln!("Unknown scrollbar color '{}'. Using the theme's.", name);
+            *color = None;
+        }
+    }
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saving_a_setting_keeps_the_file_in_order() {
+        let path = std::env::temp_dir().join(format!("trademaxxing-save-setting-{}.json", std::process::id()));
+        fs::write(&path, "{\n  \"theme\": \"default\",\n  \"columns\": [\"user\", \"coin\"],\n  \"alert_sound\": false\n}\n").unwrap();
+        let flag = path.to_str();
+        save_setting(flag, "theme", "solarized".into()).unwrap();
+        save_setting(flag, "column_formats", serde_json::json!({ "total": "compact" })).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            contents,
+            "{\n  \"theme\": \"solarized\",\n  \"columns\": [\"user\", \"coin\"],\n  \"alert_sound\": false,\n  \"column_formats\": {\n    \"total\": \"compact\"\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn saving_never_creates_a_named_config() {
+        let path = std::env::temp_dir().join(format!("trademaxxing-missing-{}.json", std::process::id()));
+        assert!(save_setting(path.to_str(), "theme", "solarized".into()).is_err());
+        assert!(!path.exists());
+    }
+}
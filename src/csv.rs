@@ -0,0 +1,182 @@
+use crate::{insert_trade_db, Trade};
+use rusqlite::Connection;
+use std::{fs, path::Path};
+
+/// Column order used for trade CSV files. `trade_id` may be left empty.
+pub const TRADE_CSV_HEADER: [&str; 9] = [
+    "timestamp",
+    "trade_type_val",
+    "action",
+    "username",
+    "amount",
+    "coin_symbol",
+    "total_value",
+    "price",
+    "trade_id",
+];
+
+pub struct ImportSummary {
+    pub imported: usize,
+    pub skipped: usize,
+}
+
+/// Splits CSV text into records of fields, honouring double-quoted fields,
+/// `""` escapes and line breaks inside quotes. Blank lines are dropped.
+fn split_csv_records(contents: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = contents.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes && chars.peek() == Some(&'\n') => {}
+            '\n' if !in_quotes => {
+                fields.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut fields));
+            }
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    records.push(fields);
+    records.retain(|record| !(record.len() == 1 && record[0].trim().is_empty()));
+    records
+}
+
+/// Quotes `field` when it contains a comma, quote or line break.
+fn quote_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Writes trades in `TRADE_CSV_HEADER` layout, so `--import` can read
+/// them back.
+pub fn export_trades_csv(path: &Path, trades: &[Trade]) -> std::io::Result<()> {
+    let mut contents = TRADE_CSV_HEADER.join(",") + "\n";
+    for trade in trades {
+        let fields = [
+            quote_csv_field(&trade.timestamp),
+            quote_csv_field(&trade.trade_type_val),
+            quote_csv_field(&trade.action),
+            quote_csv_field(&trade.username),
+            trade.amount.to_string(),
+            quote_csv_field(&trade.coin_symbol),
+            trade.total_value.to_string(),
+            trade.price.to_string(),
+            quote_csv_field(trade.id.as_deref().unwrap_or("")),
+        ];
+        contents.push_str(&fields.join(","));
+        contents.push('\n');
+    }
+    fs::write(path, contents)
+}
+
+/// Writes (user, coin, balance) rows with a header line.
+pub fn export_balances_csv(path: &Path, rows: &[(String, String, f64)]) -> std::io::Result<()> {
+    let mut contents = String::from("username,coin_symbol,balance\n");
+    for (username, coin_symbol, balance) in rows {
+        contents.push_str(&format!(
+            "{},{},{}\n",
+            quote_csv_field(username),
+            quote_csv_field(coin_symbol),
+            balance
+        ));
+    }
+    fs::write(path, contents)
+}
+
+fn parse_trade(fields: &[String], positions: &[Option<usize>]) -> Option<Trade> {
+    let field = |i: usize| positions[i].and_then(|p| fields.get(p)).map(|f| f.trim());
+    let text = |i: usize| field(i).filter(|f| !f.is_empty()).map(str::to_string);
+    let number = |i: usize| field(i)?.parse::<f64>().ok();
+
+    Some(Trade {
+        timestamp: text(0)?,
+        trade_type_val: text(1)?,
+        action: text(2)?,
+        username: text(3)?,
+        amount: number(4)?,
+        coin_symbol: text(5)?,
+        total_value: number(6)?,
+        price: number(7)?,
+        id: text(8),
+        received_at: None,
+        recorded_at: None,
+        row_id: None,
+    })
+}
+
+/// Loads trades from a CSV file written in `TRADE_CSV_HEADER` layout into
+/// the database, in a single transaction. Rows that don't parse are skipped.
+pub fn import_csv(conn: &Connection, path: &str) -> Result<ImportSummary, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}", path, e))?;
+    let mut records = split_csv_records(&contents).into_iter();
+    let header = records.next().ok_or_else(|| format!("{} is empty", path))?;
+
+    let positions: Vec<Option<usize>> = TRADE_CSV_HEADER
+        .iter()
+        .map(|name| header.iter().position(|h| h.trim() == *name))
+        .collect();
+    let missing: Vec<&str> = TRADE_CSV_HEADER
+        .iter()
+        .zip(&positions)
+        .filter(|(name, position)| position.is_none() && **name != "trade_id")
+        .map(|(name, _)| *name)
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("{} is missing columns: {}", path, missing.join(", ")));
+    }
+
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let mut summary = ImportSummary { imported: 0, skipped: 0 };
+    for record in records {
+        match parse_trade(&record, &positions) {
+            Some(trade) if insert_trade_db(&tx, &trade).is_ok() => summary.imported += 1,
+            _ => summary.skipped += 1,
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_fields_keep_commas_quotes_and_line_breaks() {
+        let records = split_csv_records("a,\"b,c\",\"say \"\"hi\"\"\"\r\n\"two\nlines\",,x\n\n");
+        assert_eq!(
+            records,
+            [vec!["a", "b,c", "say \"hi\""], vec!["two\nlines", "", "x"]]
+        );
+    }
+
+    #[test]
+    fn exported_fields_split_back_the_same() {
+        let awkward = ["plain", "with, comma", "with \"quotes\"", "line\nbreak", ""];
+        let line: Vec<String> = awkward.iter().map(|field| quote_csv_field(field)).collect();
+        assert_eq!(split_csv_records(&line.join(",")), [awkward]);
+    }
+
+    #[test]
+    fn short_and_malformed_rows_are_not_trades() {
+        let positions: Vec<Option<usize>> = (0..TRADE_CSV_HEADER.len()).map(Some).collect();
+        let row = |line: &str| split_csv_records(line).remove(0);
+        let trade = parse_trade(&row("12:00:00,live-trade,BUY,alice,5,DOGE,10,2,"), &positions).unwrap();
+        assert_eq!((trade.username.as_str(), trade.amount, trade.id), ("alice", 5.0, None));
+        assert!(parse_trade(&row("12:00:00,live-trade,BUY,alice,5"), &positions).is_none());
+        assert!(parse_trade(&row("12:00:00,live-trade,BUY,alice,five,DOGE,10,2"), &positions).is_none());
+        assert!(parse_trade(&row("12:00:00,live-trade,BUY,,5,DOGE,10,2"), &positions).is_none());
+    }
+}
@@ -0,0 +1,139 @@
+//! Searches the whole `trades` table rather than the trades in memory.
+//! Queries look like `DOGE @whale >100 <5000 since:2026-01-01 until:2026-02-01T12:00`.
+
+use crate::{symbols, trade_from_row, Trade, TRADE_SELECT_SQL};
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
+use rusqlite::{params_from_iter, types::Value, Connection, Result as RusqliteResult};
+
+/// More rows than this aren't useful in a terminal table.
+pub const RESULT_LIMIT: usize = 1000;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct DbQuery {
+    symbol: Option<String>,
+    user: Option<String>,
+    min_value: Option<f64>,
+    max_value: Option<f64>,
+    /// Unix seconds, matched against `recorded_at`.
+    since: Option<i64>,
+    until: Option<i64>,
+}
+
+/// Accepts `YYYY-MM-DD` (midnight) or `YYYY-MM-DDTHH:MM`, in local time.
+fn parse_time(value: &str) -> Result<i64, String> {
+    let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|date| date.and_time(Default::default())))
+        .map_err(|_| format!("'{}' is not a date (use YYYY-MM-DD or YYYY-MM-DDTHH:MM)", value))?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|time| time.timestamp())
+        .ok_or_else(|| format!("'{}' doesn't exist in the local timezone", value))
+}
+
+fn parse_value(value: &str) -> Result<f64, String> {
+    value.parse().map_err(|_| format!("'{}' is not a number", value))
+}
+
+impl DbQuery {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut query = DbQuery::default();
+        for term in input.split_whitespace() {
+            if let Some(user) = term.strip_prefix('@') {
+                query.user = Some(user.to_string());
+            } else if let Some(value) = term.strip_prefix('>') {
+                query.min_value = Some(parse_value(value)?);
+            } else if let Some(value) = term.strip_prefix('<') {
+                query.max_value = Some(parse_value(value)?);
+            } else if let Some(value) = term.strip_prefix("since:") {
+                query.since = Some(parse_time(value)?);
+            } else if let Some(value) = term.strip_prefix("until:") {
+                query.until = Some(parse_time(value)?);
+            } else if query.symbol.is_none() {
+                query.symbol = Some(symbols::normalize(term));
+            } else {
+                return Err(format!("unexpected '{}' (only one symbol per search)", term));
+            }
+        }
+        Ok(query)
+    }
+}
+
+/// Newest first, at most `RESULT_LIMIT` trades. Every value is bound as a
+/// parameter; only the fixed clause text is spliced into the SQL.
+pub fn search_trades_db(conn: &Connection, query: &DbQuery) -> RusqliteResult<Vec<Trade>> {
+    let mut clauses: Vec<&str> = Vec::new();
+    let mut params: Vec<Value> = Vec::new();
+    if let Some(symbol) = &query.symbol {
+        clauses.push("UPPER(coin_symbol) = ?");
+        params.push(symbol.clone().into());
+    }
+    if let Some(user) = &query.user {
+        clauses.push("username = ? COLLATE NOCASE");
+        params.push(user.clone().into());
+    }
+    if let Some(min_value) = query.min_value {
+        clauses.push("total_value > ?");
+        params.push(min_value.into());
+    }
+    if let Some(max_value) = query.max_value {
+        clauses.push("total_value < ?");
+        params.push(max_value.into());
+    }
+    if let Some(since) = query.since {
+        clauses.push("recorded_at >= ?");
+        params.push(since.into());
+    }
+    if let Some(until) = query.until {
+        clauses.push("recorded_at < ?");
+        params.push(until.into());
+    }
+
+    let where_sql = if clauses.is_empty() {
+        String::new()
+    } else {
+        format!(" WHERE {}", clauses.join(" AND "))
+    };
+    let sql = format!("{}{} ORDER BY id DESC LIMIT {}", TRADE_SELECT_SQL, where_sql, RESULT_LIMIT);
+    let mut stmt = conn.prepare(&sql)?;
+    let trades = stmt
+        .query_map(params_from_iter(params), trade_from_row)?
+        .collect::<RusqliteResult<Vec<Trade>>>()?;
+    Ok(trades)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_kind_of_term_in_any_order() {
+        let query = DbQuery::parse("  >100 @whale doge <5000.5 since:2026-01-01 until:2026-02-01T12:30 ").unwrap();
+        assert_eq!(
+            query,
+            DbQuery {
+                symbol: Some("DOGE".to_string()),
+                user: Some("whale".to_string()),
+                min_value: Some(100.0),
+                max_value: Some(5000.5),
+                since: Some(parse_time("2026-01-01").unwrap()),
+                until: Some(parse_time("2026-02-01T12:30").unwrap()),
+            }
+        );
+        assert_eq!(DbQuery::parse("").unwrap(), DbQuery::default());
+    }
+
+    #[test]
+    fn bad_terms_are_explained() {
+        for (input, error) in [
+            (">lots", "'lots' is not a number"),
+            ("<", "'' is not a number"),
+            ("since:yesterday", "'yesterday' is not a date"),
+            ("until:2026-13-01", "'2026-13-01' is not a date"),
+            ("DOGE PEPE", "unexpected 'PEPE' (only one symbol per search)"),
+        ] {
+            let message = DbQuery::parse(input).unwrap_err();
+            assert!(message.contains(error), "{:?} gave {:?}", input, message);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{
+    config::{AutoExport, ExportFormat},
+    csv, Trade,
+};
+use chrono::Local;
+use serde_json::json;
+use std::{fs, path::PathBuf};
+
+/// Writes `trades` to a timestamped file in `settings.dir` and returns its
+/// path.
+pub fn export_trades(settings: &AutoExport, trades: &[Trade]) -> Result<PathBuf, String> {
+    fs::create_dir_all(&settings.dir).map_err(|e| format!("Can't create {}: {}", settings.dir, e))?;
+    let extension = match settings.format {
+        ExportFormat::Csv => "csv",
+        ExportFormat::Json => "json",
+    };
+    let path = PathBuf::from(&settings.dir).join(format!(
+        "trades-{}.{}",
+        Local::now().format("%Y%m%d-%H%M%S"),
+        extension
+    ));
+    let written = match settings.format {
+        ExportFormat::Csv => csv::export_trades_csv(&path, trades),
+        ExportFormat::Json => {
+            let trades: Vec<serde_json::Value> = trades.iter().map(trade_json).collect();
+            let contents = serde_json::to_string_pretty(&trades).map_err(|e| e.to_string())?;
+            fs::write(&path, contents + "\n")
+        }
+    };
+    written.map_err(|e| format!("Can't write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Writes (user, coin, balance) rows to a timestamped file in the working
+/// directory, named after `coin` or `all`, and returns its path.
+pub fn export_balances(coin: Option<&str>, format: ExportFormat, rows: &[(String, String, f64)]) -> Result<PathBuf, String> {
+    let extension = match format {
+        ExportFormat::Csv => "csv",
+        ExportFormat::Json => "json",
+    };
+    let path = PathBuf::from(format!(
+        "balances-{}-{}.{}",
+        coin.map_or("all".to_string(), file_name_safe),
+        Local::now().format("%Y%m%d-%H%M%S"),
+        extension
+    ));
+    let written = match format {
+        ExportFormat::Csv => csv::export_balances_csv(&path, rows),
+        ExportFormat::Json => {
+            let rows: Vec<serde_json::Value> = rows
+                .iter()
+                .map(|(username, coin_symbol, balance)| {
+                    json!({ "username": username, "coin_symbol": coin_symbol, "balance": balance })
+                })
+                .collect();
+            let contents = serde_json::to_string_pretty(&rows).map_err(|e| e.to_string())?;
+            fs::write(&path, contents + "\n")
+        }
+    };
+    written.map_err(|e| format!("Can't write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Coin symbols come off the wire, so anything that could leave the
+/// directory or trip up a shell becomes `_`.
+fn file_name_safe(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// Field names match the CSV header.
+pub fn trade_json(trade: &Trade) -> serde_json::Value {
+    json!({
+        "timestamp": trade.timestamp,
+        "recorded_at": trade.recorded_at,
+        "trade_type_val": trade.trade_type_val,
+        "action": trade.action,
+        "username": trade.username,
+        "amount": trade.amount,
+        "coin_symbol": trade.coin_symbol,
+        "total_value": trade.total_value,
+        "price": trade.price,
+        "trade_id": trade.id,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coin_symbols_cannot_escape_the_file_name() {
+        assert_eq!(file_name_safe("DOGE"), "DOGE");
+        assert_eq!(file_name_safe("../../etc/x"), "______etc_x");
+        assert_eq!(file_name_safe("A/B C"), "A_B_C");
+    }
+}
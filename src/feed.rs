@@ -0,0 +1,438 @@
+use crate::{
+    config::Keepalive,
+    metrics::{ConnectionState, Metrics},
+    proxy::Proxy,
+    raw_stream::RawRecorder,
+    symbols,
+    trade_channel::BoundedSender,
+    Trade,
+};
+use chrono::{Local, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    collections::{hash_map::RandomState, BTreeSet},
+    fs::OpenOptions,
+    hash::{BuildHasher, Hasher},
+    io::{self, Write},
+    net::TcpStream,
+    sync::{mpsc::Sender, Arc},
+    thread,
+    time::{Duration, Instant},
+};
+use tungstenite::{client::IntoClientRequest, connect, stream::MaybeTlsStream, Message, WebSocket};
+
+pub const WS_URL: &str = "ws://ws.rugplay.com/api/";
+pub const DEFAULT_CHANNEL: &str = "trades:all";
+/// Frames that can't be decoded, or whose trades don't look like we expect,
+/// are appended here; stderr belongs to the TUI.
+pub const FEED_LOG_PATH: &str = "feed.log";
+const LOG_PREVIEW_CHARS: usize = 500;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// Each reconnect delay is spread by up to this fraction either way, so
+/// clients dropped together don't all come back at the same moment.
+const BACKOFF_JITTER: f64 = 0.2;
+
+fn default_subscriptions(channel: &str) -> Vec<String> {
+    vec![
+        serde_json::json!({ "type": "subscribe", "channel": channel }).to_string(),
+        "{\"type\":\"set_coin\",\"coinSymbol\":\"@global\"}".to_string(),
+    ]
+}
+
+/// Picks the subscription messages to send on connect, skipping (with a
+/// warning) any that aren't valid JSON. Falls back to subscribing to
+/// `channel` when none are configured.
+pub fn resolve_subscriptions(configured: Option<&[String]>, channel: &str) -> Vec<String> {
+    let Some(configured) = configured else {
+        return default_subscriptions(channel);
+    };
+    configured
+        .iter()
+        .filter(|message| match serde_json::from_str::<Value>(message) {
+            Ok(_) => true,
+            Err(e) => {
+                eprintln!("Ignoring subscription that isn't valid JSON ({}): {}", e, message);
+                false
+            }
+        })
+        .cloned()
+        .collect()
+}
+
+/// Where the feed connects to and what it asks for there.
+pub struct Endpoint {
+    pub url: String,
+    /// Tunnel through this proxy instead of connecting directly.
+    pub proxy: Option<Proxy>,
+    /// Messages sent after every (re)connect.
+    pub subscriptions: Vec<String>,
+    pub keepalive: Option<Keepalive>,
+}
+
+/// Decides which trades enter the app at all. Unlike the view filters this
+/// runs in the feed thread, before trades are saved or counted in
+/// balances, so whatever it rejects is gone for good.
+#[derive(Clone, Debug, Default)]
+pub struct IngestFilter {
+    /// Drop trades worth less than this many USD.
+    pub min_value: Option<f64>,
+    /// Only keep trades in these coins (upper case).
+    pub coins: Option<BTreeSet<String>>,
+}
+
+impl IngestFilter {
+    pub fn admits(&self, trade: &Trade) -> bool {
+        self.min_value.is_none_or(|min| trade.total_value >= min)
+            && self.coins.as_ref().is_none_or(|coins| coins.contains(&symbols::normalize(&trade.coin_symbol)))
+    }
+}
+
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+/// Opens the websocket, through the proxy when there is one. Errors say
+/// which step failed so they make sense in the status line.
+fn open_socket(endpoint: &Endpoint) -> Result<Socket, String> {
+    let Some(proxy) = &endpoint.proxy else {
+        return connect(endpoint.url.as_str())
+            .map(|(socket, _response)| socket)
+            .map_err(|e| format!("connect failed: {}", e));
+    };
+    let request = endpoint.url.as_str().into_client_request().map_err(|e| format!("bad feed URL: {}", e))?;
+    let uri = request.uri();
+    if uri.scheme_str() != Some("ws") {
+        return Err("only ws:// feed URLs can go through a proxy".to_string());
+    }
+    let host = uri.host().unwrap_or_default().trim_start_matches('[').trim_end_matches(']').to_string();
+    let port = uri.port_u16().unwrap_or(80);
+    let stream = proxy
+        .connect(&host, port)
+        .map_err(|e| format!("proxy connect to {}:{} failed: {}", proxy.host, proxy.port, e))?;
+    tungstenite::client(request, MaybeTlsStream::Plain(stream))
+        .map(|(socket, _response)| socket)
+        .map_err(|e| format!("handshake through proxy failed: {}", e))
+}
+
+/// Streams trades into `tx`, reconnecting with exponential backoff whenever
+/// the socket drops. Every delay is jittered by `BACKOFF_JITTER` and never
+/// exceeds `MAX_BACKOFF`. Returns once the receiving side has gone away.
+/// When `recorder` is set every raw frame is also written to it.
+/// With a non-zero `max_reconnects` the feed gives up after that many
+/// attempts in a row without getting connected, marks the connection as
+/// failed and reports the last error on `status_tx`. Proxy failures are
+/// reported there on every attempt, since they usually mean a setup
+/// problem rather than a flaky network. Trades `ingest` rejects are never
+/// sent. With `endpoint.keepalive` set, its message (or a ping) goes out
+/// whenever nothing else has been sent for its interval.
+pub fn run_feed(
+    tx: BoundedSender<Trade>,
+    endpoint: Endpoint,
+    metrics: Arc<Metrics>,
+    mut recorder: Option<RawRecorder>,
+    max_reconnects: u32,
+    ingest: IngestFilter,
+    status_tx: Sender<String>,
+) {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut jitter = Jitter::new();
+    let mut attempts = 0;
+    loop {
+        metrics.set_connection_state(ConnectionState::Connecting);
+        let last_error = match open_socket(&endpoint) {
+            Ok(mut socket) => {
+                backoff = INITIAL_BACKOFF;
+                attempts = 0;
+                let subscribed = endpoint
+                    .subscriptions
+                    .iter()
+                    .try_for_each(|message| socket.send(Message::Text(message.as_str().into())).map_err(|e| e.to_string()));
+
+                let keepalive = endpoint.keepalive.as_ref().map(|keepalive| {
+                    (Duration::from_secs(keepalive.interval_secs), keepalive.message.as_deref())
+                });
+                // Wake from reads at least once an interval to check on it.
+                // Startup refuses keepalives on wss://, so TLS never gets here.
+                let subscribed = subscribed.and_then(|()| match (keepalive, socket.get_ref()) {
+                    (Some((interval, _)), MaybeTlsStream::Plain(stream)) => {
+                        stream.set_read_timeout(Some(interval)).map_err(|e| e.to_string())
+                    }
+                    (Some(_), _) => Err("keepalive needs a plain ws:// connection".to_string()),
+                    (None, _) => Ok(()),
+                });
+
+                match subscribed {
+                    Ok(()) => {
+                        metrics.set_connection_state(ConnectionState::Connected);
+                        let mut last_sent = Instant::now();
+                        loop {
+                            match socket.read() {
+                                Ok(msg) => {
+                                    if let Some(recorder) = &mut recorder {
+                                        recorder.record(&msg);
+                                    }
+                                    // Pongs to our pings land here too and
+                                    // are ignored like any non-trade frame.
+                                    if handle_frame(&msg, &tx, &metrics, &ingest).is_err() {
+                                        return;
+                                    }
+                                }
+                                Err(tungstenite::Error::Io(e))
+                                    if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {}
+                                Err(e) => break format!("connection lost: {}", e),
+                            }
+                            if let Some((interval, message)) = keepalive
+                                && last_sent.elapsed() >= interval
+                            {
+                                let ping = match message {
+                                    Some(message) => Message::Text(message.into()),
+                                    None => Message::Ping(Default::default()),
+                                };
+                                if let Err(e) = socket.send(ping) {
+                                    break format!("keepalive failed: {}", e);
+                                }
+                                last_sent = Instant::now();
+                            }
+                        }
+                    }
+                    Err(e) => format!("subscribe failed: {}", e),
+                }
+            }
+            Err(e) => {
+                if endpoint.proxy.is_some() {
+                    let _ = status_tx.send(format!("Feed {}", e));
+                }
+                e
+            }
+        };
+
+        if max_reconnects > 0 && attempts >= max_reconnects {
+            metrics.set_connection_state(ConnectionState::Failed);
+            let _ = status_tx.send(format!("Feed gave up after {} reconnect attempts: {}", attempts, last_error));
+            return;
+        }
+        metrics.set_connection_state(ConnectionState::Disconnected);
+        thread::sleep(jitter.apply(backoff));
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+        attempts += 1;
+        metrics.record_reconnect();
+    }
+}
+
+/// A small LCG for reconnect jitter. It only has to differ between clients,
+/// so it's seeded from std's per-process random hasher keys.
+struct Jitter(u64);
+
+impl Jitter {
+    fn new() -> Self {
+        Jitter(RandomState::new().build_hasher().finish())
+    }
+
+    /// `delay` scaled by a random factor within `BACKOFF_JITTER` of 1,
+    /// capped at `MAX_BACKOFF`.
+    fn apply(&mut self, delay: Duration) -> Duration {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        // The top 53 bits as a float in [0, 1).
+        let unit = (self.0 >> 11) as f64 / (1u64 << 53) as f64;
+        delay.mul_f64(1.0 + BACKOFF_JITTER * (2.0 * unit - 1.0)).min(MAX_BACKOFF)
+    }
+}
+
+/// Parses one frame and forwards any trade in it that `ingest` admits.
+/// `Err` means the receiving side has gone away.
+pub fn handle_frame(msg: &Message, tx: &BoundedSender<Trade>, metrics: &Metrics, ingest: &IngestFilter) -> Result<(), ()> {
+    let trade = match parse_message(msg) {
+        Ok(Some(trade)) => trade,
+        Ok(None) => return Ok(()),
+        Err(FrameError::Undecodable) => {
+            metrics.record_dropped();
+            return Ok(());
+        }
+        Err(FrameError::SchemaMismatch) => {
+            metrics.record_schema_mismatch();
+            return Ok(());
+        }
+    };
+    metrics.record_trade(&trade.coin_symbol);
+    if !ingest.admits(&trade) {
+        metrics.record_filtered();
+        return Ok(());
+    }
+    tx.send(trade).map_err(|_| ())
+}
+
+fn log_feed_problem(reason: &str, raw: &str) {
+    // The tests feed in broken frames on purpose.
+    if cfg!(test) {
+        return;
+    }
+    let preview: String = raw.chars().take(LOG_PREVIEW_CHARS).collect();
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(FEED_LOG_PATH) {
+        let _ = writeln!(file, "{} {}: {}", Local::now().format("%Y-%m-%d %H:%M:%S"), reason, preview);
+    }
+}
+
+/// Why a frame didn't give a trade.
+#[derive(Debug, PartialEq)]
+enum FrameError {
+    /// Not JSON at all.
+    Undecodable,
+    /// A trade whose `data` lacks fields we need or has them as the wrong
+    /// type, which usually means the feed's format changed.
+    SchemaMismatch,
+}
+
+/// A trade's `data` as the feed sends it. Every field is optional so that
+/// missing ones can be named instead of failing the whole decode.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TradePayload {
+    #[serde(rename = "type")]
+    action: Option<String>,
+    username: Option<String>,
+    amount: Option<f64>,
+    coin_symbol: Option<String>,
+    total_value: Option<f64>,
+    price: Option<f64>,
+    id: Option<Value>,
+}
+
+impl TradePayload {
+    /// Feed names of the required fields that are absent.
+    fn missing_fields(&self) -> Vec<&'static str> {
+        [
+            ("type", self.action.is_none()),
+            ("username", self.username.is_none()),
+            ("amount", self.amount.is_none()),
+            ("coinSymbol", self.coin_symbol.is_none()),
+            ("totalValue", self.total_value.is_none()),
+            ("price", self.price.is_none()),
+        ]
+        .into_iter()
+        .filter_map(|(name, missing)| missing.then_some(name))
+        .collect()
+    }
+}
+
+/// `Ok(None)` for messages that just aren't trades (pings, control
+/// frames), `Err` for frames that couldn't be decoded or don't match the
+/// trade format. Either kind of `Err` is logged with the raw frame.
+fn parse_message(msg: &Message) -> Result<Option<Trade>, FrameError> {
+    let raw = || match msg {
+        Message::Binary(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+        _ => msg.to_text().unwrap_or_default().to_string(),
+    };
+    let v: Value = match msg {
+        Message::Text(text) => match serde_json::from_str(text.as_str()) {
+            Ok(v) => v,
+            Err(e) => {
+                log_feed_problem(&format!("undecodable text frame ({})", e), &raw());
+                return Err(FrameError::Undecodable);
+            }
+        },
+        Message::Binary(bytes) => match serde_json::from_slice(bytes) {
+            Ok(v) => v,
+            Err(e) => {
+                log_feed_problem(&format!("undecodable binary frame of {} bytes ({})", bytes.len(), e), &raw());
+                return Err(FrameError::Undecodable);
+            }
+        },
+        _ => return Ok(None),
+    };
+
+    let trade_type_val = v["type"].as_str().unwrap_or_default().to_string();
+    if trade_type_val == "ping" || !v["data"].is_object() {
+        return Ok(None);
+    }
+
+    let payload = match TradePayload::deserialize(&v["data"]) {
+        Ok(payload) => payload,
+        Err(e) => {
+            log_feed_problem(&format!("trade doesn't match the expected format ({})", e), &raw());
+            return Err(FrameError::SchemaMismatch);
+        }
+    };
+    let missing = payload.missing_fields();
+    let (Some(action), Some(username), Some(amount), Some(coin_symbol), Some(total_value), Some(price)) = (
+        payload.action,
+        payload.username,
+        payload.amount,
+        payload.coin_symbol,
+        payload.total_value,
+        payload.price,
+    ) else {
+        log_feed_problem(&format!("trade missing {}", missing.join(", ")), &raw());
+        return Err(FrameError::SchemaMismatch);
+    };
+    let id = match payload.id {
+        Some(Value::String(id)) => Some(id),
+        Some(Value::Number(id)) => Some(id.to_string()),
+        _ => None,
+    };
+    // Milliseconds kept so trades within the same second stay ordered.
+    let now = Utc::now();
+    let timestamp = now.with_timezone(&Local).format("%H:%M:%S%.3f").to_string();
+
+    Ok(Some(Trade {
+        timestamp,
+        trade_type_val,
+        action,
+        username,
+        amount,
+        coin_symbol,
+        total_value,
+        price,
+        id,
+        received_at: None,
+        recorded_at: Some(now.timestamp()),
+        row_id: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(json: &str) -> Message {
+        Message::Text(json.into())
+    }
+
+    #[test]
+    fn a_trade_frame_becomes_a_trade() {
+        let frame = text(
+            r#"{"type":"live-trade","data":{"type":"BUY","username":"alice","amount":5,"coinSymbol":"DOGE","totalValue":10.5,"price":2.1,"id":42}}"#,
+        );
+        let trade = parse_message(&frame).unwrap().unwrap();
+        assert_eq!(trade.trade_type_val, "live-trade");
+        assert_eq!((trade.action.as_str(), trade.username.as_str(), trade.coin_symbol.as_str()), ("BUY", "alice", "DOGE"));
+        assert_eq!((trade.amount, trade.total_value, trade.price), (5.0, 10.5, 2.1));
+        assert_eq!(trade.id.as_deref(), Some("42"));
+    }
+
+    #[test]
+    fn frames_without_a_trade_are_skipped() {
+        assert!(matches!(parse_message(&text(r#"{"type":"ping"}"#)), Ok(None)));
+        assert!(matches!(parse_message(&text(r#"{"type":"ping","data":{"username":"x"}}"#)), Ok(None)));
+        assert!(matches!(parse_message(&text(r#"{"type":"subscribed","channel":"trades:all"}"#)), Ok(None)));
+        assert!(matches!(parse_message(&text(r#"{"type":"live-trade","data":"hello"}"#)), Ok(None)));
+        assert!(matches!(parse_message(&Message::Ping(Vec::new().into())), Ok(None)));
+    }
+
+    #[test]
+    fn broken_trades_are_schema_mismatches() {
+        let missing = text(r#"{"type":"live-trade","data":{"type":"BUY","username":"alice","coinSymbol":"DOGE","price":2}}"#);
+        assert_eq!(parse_message(&missing).err(), Some(FrameError::SchemaMismatch));
+        let wrong_type = text(
+            r#"{"type":"live-trade","data":{"type":"BUY","username":"alice","amount":"5","coinSymbol":"DOGE","totalValue":10,"price":2}}"#,
+        );
+        assert_eq!(parse_message(&wrong_type).err(), Some(FrameError::SchemaMismatch));
+        assert_eq!(parse_message(&text("not json")).err(), Some(FrameError::Undecodable));
+        assert_eq!(parse_message(&Message::Binary(vec![0xff, 0x00].into())).err(), Some(FrameError::Undecodable));
+    }
+
+    #[test]
+    fn payloads_name_their_missing_fields() {
+        let payload = TradePayload::deserialize(&serde_json::json!({ "username": "alice", "price": 1.0 })).unwrap();
+        assert_eq!(payload.missing_fields(), ["type", "amount", "coinSymbol", "totalValue"]);
+    }
+}
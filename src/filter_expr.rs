@@ -0,0 +1,270 @@
+//! A small filter language for the search box, e.g.
+//! `coin=DOGE and value>1000 or user=whale`. `and` binds tighter than `or`.
+//! Values with spaces or operators in them go in quotes: `user="big whale"`.
+
+use crate::{symbols, Trade};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Coin,
+    User,
+    Action,
+    Value,
+    Amount,
+    Price,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "coin" => Some(Field::Coin),
+            "user" => Some(Field::User),
+            "action" => Some(Field::Action),
+            "value" => Some(Field::Value),
+            "amount" => Some(Field::Amount),
+            "price" => Some(Field::Price),
+            _ => None,
+        }
+    }
+
+    fn is_numeric(self) -> bool {
+        matches!(self, Field::Value | Field::Amount | Field::Price)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    Gt,
+    Lt,
+}
+
+#[derive(Debug, Clone)]
+pub enum Operand {
+    Text(String),
+    Number(f64),
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Condition(Field, Op, Operand),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn matches(&self, trade: &Trade) -> bool {
+        match self {
+            Expr::And(left, right) => left.matches(trade) && right.matches(trade),
+            Expr::Or(left, right) => left.matches(trade) || right.matches(trade),
+            Expr::Condition(field, op, Operand::Number(expected)) => {
+                let actual = match field {
+                    Field::Value => trade.total_value,
+                    Field::Amount => trade.amount,
+                    _ => trade.price,
+                };
+                match op {
+                    Op::Eq => actual == *expected,
+                    Op::Gt => actual > *expected,
+                    Op::Lt => actual < *expected,
+                }
+            }
+            Expr::Condition(field, _, Operand::Text(expected)) => {
+                match field {
+                    Field::Coin => symbols::same(&trade.coin_symbol, expected),
+                    Field::User => trade.username.eq_ignore_ascii_case(expected),
+                    _ => trade.action.eq_ignore_ascii_case(expected),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    /// A value in single or double quotes, never a keyword or field.
+    Quoted(String),
+    Op(Op),
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        if (c == '"' || c == '\'') && word.is_empty() {
+            let mut quoted = String::new();
+            loop {
+                match chars.next() {
+                    Some(next) if next == c => break,
+                    Some(next) => quoted.push(next),
+                    None => return Err(format!("missing closing {}", c)),
+                }
+            }
+            tokens.push(Token::Quoted(quoted));
+            continue;
+        }
+        let op = match c {
+            '=' => Some(Op::Eq),
+            '>' => Some(Op::Gt),
+            '<' => Some(Op::Lt),
+            _ => None,
+        };
+        if c.is_whitespace() || op.is_some() {
+            if !word.is_empty() {
+                tokens.push(Token::Word(std::mem::take(&mut word)));
+            }
+            if let Some(op) = op {
+                tokens.push(Token::Op(op));
+            }
+        } else {
+            word.push(c);
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(Token::Word(word));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        token
+    }
+
+    /// Consumes the next token if it is the keyword `keyword`.
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        match self.tokens.get(self.position) {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_and()?;
+        while self.eat_keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_condition()?;
+        while self.eat_keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_condition()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_condition(&mut self) -> Result<Expr, String> {
+        let field = match self.next() {
+            Some(Token::Word(name)) => Field::parse(name).ok_or_else(|| {
+                format!("unknown field '{}' (use coin, user, action, value, amount or price)", name)
+            })?,
+            Some(Token::Op(_)) => return Err("expected a field before the operator".to_string()),
+            Some(Token::Quoted(text)) => return Err(format!("expected a field, found \"{}\"", text)),
+            None => return Err("expected a condition".to_string()),
+        };
+        let op = match self.next() {
+            Some(Token::Op(op)) => *op,
+            _ => return Err("expected =, > or < after the field".to_string()),
+        };
+        let value = match self.next() {
+            Some(Token::Word(value) | Token::Quoted(value)) => value.clone(),
+            _ => return Err("expected a value after the operator".to_string()),
+        };
+        let operand = if field.is_numeric() {
+            Operand::Number(value.parse().map_err(|_| format!("'{}' is not a number", value))?)
+        } else if op == Op::Eq {
+            Operand::Text(value)
+        } else {
+            return Err("text fields only support =".to_string());
+        };
+        Ok(Expr::Condition(field, op, operand))
+    }
+}
+
+pub fn parse(input: &str) -> Result<Expr, String> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        position: 0,
+    };
+    let expr = parser.parse_or()?;
+    match parser.next() {
+        None => Ok(expr),
+        Some(Token::Word(word) | Token::Quoted(word)) => Err(format!("expected 'and' or 'or', found '{}'", word)),
+        Some(Token::Op(_)) => Err("unexpected operator".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(coin: &str, user: &str, total_value: f64) -> Trade {
+        Trade {
+            timestamp: "12:00:00".to_string(),
+            trade_type_val: "live-trade".to_string(),
+            action: "BUY".to_string(),
+            username: user.to_string(),
+            amount: 1.0,
+            coin_symbol: coin.to_string(),
+            total_value,
+            price: total_value,
+            id: None,
+            received_at: None,
+            recorded_at: None,
+            row_id: None,
+        }
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let expr = parse("coin=DOGE and value>1000 or user=whale").unwrap();
+        assert!(expr.matches(&trade("doge", "someone", 5000.0)));
+        assert!(!expr.matches(&trade("DOGE", "someone", 10.0)));
+        // Only the `or` side has to hold.
+        assert!(expr.matches(&trade("PEPE", "WHALE", 10.0)));
+        assert!(matches!(expr, Expr::Or(left, _) if matches!(*left, Expr::And(..))));
+    }
+
+    #[test]
+    fn quoted_values_keep_spaces_and_operators() {
+        let expr = parse("user=\"big whale\" or user='a<b'").unwrap();
+        assert!(expr.matches(&trade("DOGE", "Big Whale", 1.0)));
+        assert!(expr.matches(&trade("DOGE", "a<b", 1.0)));
+        assert!(!expr.matches(&trade("DOGE", "big", 1.0)));
+        // A quoted keyword is just a value.
+        assert!(parse("user=\"or\"").unwrap().matches(&trade("DOGE", "or", 1.0)));
+    }
+
+    #[test]
+    fn bad_expressions_are_explained() {
+        for (input, error) in [
+            ("", "expected a condition"),
+            ("size>3", "unknown field 'size'"),
+            ("value>lots", "'lots' is not a number"),
+            ("coin>DOGE", "text fields only support ="),
+            ("coin=DOGE value>3", "expected 'and' or 'or', found 'value'"),
+            ("coin=DOGE and", "expected a condition"),
+            ("user=\"big whale", "missing closing \""),
+            ("\"coin\"=DOGE", "expected a field"),
+            ("=DOGE", "expected a field before the operator"),
+            ("coin DOGE", "expected =, > or < after the field"),
+        ] {
+            let message = parse(input).unwrap_err();
+            assert!(message.contains(error), "{:?} gave {:?}", input, message);
+        }
+    }
+}
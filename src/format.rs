@@ -0,0 +1,193 @@
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Values at least this big are abbreviated even where every digit is
+/// normally shown; past this the digits are just noise.
+const ALWAYS_COMPACT_FROM: f64 = 1e12;
+
+/// Abbreviates large values with K/M/B/T suffixes, e.g. `1234.5` -> `1.23K`.
+pub fn format_compact(value: f64) -> String {
+    let abs = value.abs();
+    if abs >= 1_000_000_000_000.0 {
+        format!("{:.2}T", value / 1_000_000_000_000.0)
+    } else if abs >= 1_000_000_000.0 {
+        format!("{:.2}B", value / 1_000_000_000.0)
+    } else if abs >= 1_000_000.0 {
+        format!("{:.2}M", value / 1_000_000.0)
+    } else if abs >= 1_000.0 {
+        format!("{:.2}K", value / 1_000.0)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+/// `value` with `decimals` places, switching to scientific notation
+/// (`1.23e-10`) when that would round a non-zero value to all zeros, and to
+/// `format_compact` for huge values.
+pub fn format_adaptive(value: f64, decimals: usize) -> String {
+    let abs = value.abs();
+    if !value.is_finite() {
+        value.to_string()
+    } else if abs >= ALWAYS_COMPACT_FROM {
+        format_compact(value)
+    } else if abs != 0.0 && abs < 0.5 * 10f64.powi(-(decimals as i32)) {
+        format!("{:.2e}", value)
+    } else {
+        format!("{:.*}", decimals, value)
+    }
+}
+
+/// A coin price, to 8 decimals.
+pub fn format_price(value: f64) -> String {
+    format_adaptive(value, 8)
+}
+
+/// A USD value, balance or token amount, to 2 decimals.
+pub fn format_value(value: f64) -> String {
+    format_adaptive(value, 2)
+}
+
+/// Regroups a number formatted by the functions above: the integer digits
+/// get `thousands` every three places and the decimal point becomes
+/// `decimal`. Suffixes and exponents are kept, so `1.23K` becomes `1,23K`
+/// with a comma decimal mark.
+pub fn group_digits(formatted: &str, thousands: char, decimal: char) -> String {
+    let (sign, unsigned) = match formatted.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", formatted),
+    };
+    let digits_end = unsigned.find(|c: char| !c.is_ascii_digit()).unwrap_or(unsigned.len());
+    let (digits, rest) = unsigned.split_at(digits_end);
+
+    let mut grouped = String::from(sign);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(thousands);
+        }
+        grouped.push(digit);
+    }
+    match rest.strip_prefix('.') {
+        Some(fraction) => {
+            grouped.push(decimal);
+            grouped.push_str(fraction);
+        }
+        None => grouped.push_str(rest),
+    }
+    grouped
+}
+
+/// Shortens `s` to at most `width` terminal columns, ending in `…` when
+/// anything was cut. Wide (e.g. CJK) characters count as two columns.
+pub fn truncate_to(s: &str, width: usize) -> String {
+    if s.width() <= width {
+        return s.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+
+    let mut truncated = String::new();
+    let mut used = 0;
+    for c in s.chars() {
+        let char_width = c.width().unwrap_or(0);
+        // Leave one column for the ellipsis.
+        if used + char_width > width - 1 {
+            break;
+        }
+        truncated.push(c);
+        used += char_width;
+    }
+    truncated.push('\u{2026}');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ordinary_magnitudes_keep_fixed_decimals() {
+        assert_eq!(format_price(0.00012345), "0.00012345");
+        assert_eq!(format_price(1.5), "1.50000000");
+        assert_eq!(format_value(1234.567), "1234.57");
+        assert_eq!(format_value(0.0), "0.00");
+        assert_eq!(format_value(-42.0), "-42.00");
+    }
+
+    #[test]
+    fn tiny_values_use_scientific_notation() {
+        assert_eq!(format_price(1.23e-10), "1.23e-10");
+        assert_eq!(format_price(-4.5e-12), "-4.50e-12");
+        assert_eq!(format_value(0.004), "4.00e-3");
+        // Still visible at the usual precision, so no switch.
+        assert_eq!(format_price(1e-8), "0.00000001");
+        assert_eq!(format_value(0.005), "0.01");
+    }
+
+    #[test]
+    fn huge_values_are_abbreviated() {
+        assert_eq!(format_value(999_999_999_999.0), "999999999999.00");
+        assert_eq!(format_value(1.5e12), "1.50T");
+        assert_eq!(format_value(-2.5e15), "-2500.00T");
+        assert_eq!(format_price(3e12), "3.00T");
+    }
+
+    #[test]
+    fn non_finite_values_pass_through() {
+        assert_eq!(format_value(f64::NAN), "NaN");
+        assert_eq!(format_value(f64::INFINITY), "inf");
+    }
+
+    #[test]
+    fn compact_suffixes() {
+        assert_eq!(format_compact(999.0), "999.00");
+        assert_eq!(format_compact(1234.5), "1.23K");
+        assert_eq!(format_compact(2_500_000.0), "2.50M");
+        assert_eq!(format_compact(7e9), "7.00B");
+        assert_eq!(format_compact(7e12), "7.00T");
+    }
+
+    #[test]
+    fn grouping_inserts_thousands_separators() {
+        assert_eq!(group_digits("1234567.89", ',', '.'), "1,234,567.89");
+        assert_eq!(group_digits("1234567.89", '.', ','), "1.234.567,89");
+        assert_eq!(group_digits("-1234.50", ' ', ','), "-1 234,50");
+        assert_eq!(group_digits("999.00", ',', '.'), "999.00");
+        assert_eq!(group_digits("123456", ',', '.'), "123,456");
+    }
+
+    #[test]
+    fn grouping_keeps_suffixes_and_exponents() {
+        assert_eq!(group_digits("1.23K", '.', ','), "1,23K");
+        assert_eq!(group_digits("-4.50e-12", ',', '.'), "-4.50e-12");
+        assert_eq!(group_digits("4.00e-3", '.', ','), "4,00e-3");
+        assert_eq!(group_digits("NaN", ',', '.'), "NaN");
+        assert_eq!(group_digits("-inf", ',', '.'), "-inf");
+    }
+
+    #[test]
+    fn truncate_leaves_short_strings_alone() {
+        assert_eq!(truncate_to("DOGE", 8), "DOGE");
+        assert_eq!(truncate_to("exactly8", 8), "exactly8");
+        assert_eq!(truncate_to("", 3), "");
+    }
+
+    #[test]
+    fn truncate_adds_ellipsis() {
+        assert_eq!(truncate_to("verylongusername", 8), "verylon\u{2026}");
+        assert_eq!(truncate_to("abc", 1), "\u{2026}");
+        assert_eq!(truncate_to("abc", 0), "");
+    }
+
+    #[test]
+    fn truncate_handles_multibyte_chars() {
+        assert_eq!(truncate_to("ünïcödé_name", 6), "ünïcö\u{2026}");
+        assert_eq!(truncate_to("🚀🚀🚀moon", 5), "🚀🚀\u{2026}");
+    }
+
+    #[test]
+    fn truncate_counts_wide_chars_as_two_columns() {
+        assert_eq!(truncate_to("日本語テキスト", 7), "日本語\u{2026}");
+        // A wide char that would straddle the limit is dropped entirely.
+        assert_eq!(truncate_to("日本語テキスト", 6), "日本\u{2026}");
+    }
+}
@@ -0,0 +1,96 @@
+use crate::symbols;
+
+/// Scores `candidate` against `pattern` as a case-insensitive subsequence
+/// match: every pattern character has to appear in order. Runs of
+/// consecutive characters and matches at word starts score higher, gaps
+/// score lower. `None` when it doesn't match at all.
+pub fn subsequence_score(pattern: &str, candidate: &str) -> Option<i64> {
+    let candidate: Vec<char> = candidate.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous_match: Option<usize> = None;
+    for wanted in pattern.chars().flat_map(char::to_lowercase) {
+        let found = position + candidate[position..].iter().position(|&c| c == wanted)?;
+        score += 10;
+        if found == 0 || matches!(candidate[found - 1], '_' | '-' | ' ') {
+            score += 20;
+        }
+        match previous_match {
+            Some(previous) if previous + 1 == found => score += 15,
+            Some(previous) => score -= (found - previous - 1) as i64,
+            None => score -= found as i64,
+        }
+        previous_match = Some(found);
+        position = found + 1;
+    }
+    // Among equal matches, prefer the shorter candidate.
+    Some(score - (candidate.len() - position) as i64)
+}
+
+/// Edit distance between `a` and `b`, counting a swap of two neighbouring
+/// chars as one edit since that's the most common typo.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Scores a coin symbol against a half-remembered query. Abbreviations
+/// (`DGE` for `DOGE`) match as subsequences; typos (`DOEG`) fall back to
+/// edit distance, allowing one edit per three characters, and always rank
+/// below subsequence matches.
+pub fn symbol_score(query: &str, symbol: &str) -> Option<i64> {
+    if let Some(score) = subsequence_score(query, symbol) {
+        return Some(score);
+    }
+    let distance = edit_distance(&symbols::normalize(query), &symbols::normalize(symbol));
+    let allowed = (query.chars().count() / 3).max(1);
+    (distance <= allowed).then(|| -100 - 10 * distance as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subsequences_match_in_order_only() {
+        assert!(subsequence_score("dge", "DOGE").is_some());
+        assert!(subsequence_score("", "DOGE").is_some());
+        assert_eq!(subsequence_score("egd", "DOGE"), None);
+        assert_eq!(subsequence_score("doges", "DOGE"), None);
+    }
+
+    #[test]
+    fn runs_and_word_starts_rank_higher() {
+        let score = |pattern, candidate| subsequence_score(pattern, candidate).unwrap();
+        assert!(score("dog", "DOGE") > score("dog", "DXOXG"));
+        assert!(score("tp", "toggle_pause") > score("tp", "stop"));
+        assert!(score("quit", "quit") > score("quit", "quitter"));
+    }
+
+    #[test]
+    fn typos_match_below_every_abbreviation() {
+        assert_eq!(edit_distance("DOEG", "DOGE"), 1);
+        let typo = symbol_score("DOEG", "DOGE").unwrap();
+        let abbreviation = symbol_score("DOGEX", "DOGEXCOINWITHAVERYLONGNAME").unwrap();
+        assert!(typo < abbreviation);
+        // One edit per three characters, so two edits in four is too many.
+        assert_eq!(symbol_score("DXEG", "DOGE"), None);
+        assert_eq!(symbol_score("PEPE", "DOGE"), None);
+    }
+}
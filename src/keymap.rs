@@ -0,0 +1,566 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, collections::HashMap, fmt};
+
+/// Everything a key can be bound to in normal mode.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    Quit,
+    Help,
+    CommandPalette,
+    Search,
+    Submit,
+    SelectDown,
+    SelectUp,
+    ShowDetails,
+    FollowUser,
+    BlockUser,
+    BlockCoin,
+    Blocklist,
+    RemoveEntry,
+    SessionStats,
+    JumpToLargest,
+    EditNote,
+    Bookmarks,
+    WhaleWatch,
+    ClearSelection,
+    TogglePause,
+    ScrollUp,
+    ScrollDown,
+    PageUp,
+    PageDown,
+    ToggleType,
+    ToggleBuySell,
+    CycleMinValue,
+    ToggleSearchMode,
+    FocusNext,
+    FocusPrev,
+    TogglePin,
+    SetPriceAlert,
+    ClearPriceAlerts,
+    LengthenAverage,
+    ShortenAverage,
+    RecentCoins,
+    MovePinnedUp,
+    MovePinnedDown,
+    ToggleWatchlistOnly,
+    ToggleAutoFit,
+    SelectColumnLeft,
+    SelectColumnRight,
+    CycleColumnFormat,
+    ToggleView,
+    ToggleDirectionalOnly,
+    ToggleLegend,
+    ToggleColorMode,
+    CycleBalanceSort,
+    FilterBalances,
+    ToggleBell,
+    ToggleUtc,
+    ToggleMillis,
+    ExportBalances,
+    CopyCliArgs,
+    Snapshot,
+    CycleTheme,
+    DeleteAll,
+}
+
+impl Action {
+    /// In the order the help overlay lists them.
+    pub const ALL: [Action; 57] = [
+        Action::Quit,
+        Action::Help,
+        Action::CommandPalette,
+        Action::Search,
+        Action::Submit,
+        Action::SelectDown,
+        Action::SelectUp,
+        Action::ShowDetails,
+        Action::FollowUser,
+        Action::BlockUser,
+        Action::BlockCoin,
+        Action::Blocklist,
+        Action::RemoveEntry,
+        Action::SessionStats,
+        Action::JumpToLargest,
+        Action::EditNote,
+        Action::Bookmarks,
+        Action::WhaleWatch,
+        Action::ClearSelection,
+        Action::TogglePause,
+        Action::ScrollUp,
+        Action::ScrollDown,
+        Action::PageUp,
+        Action::PageDown,
+        Action::ToggleType,
+        Action::ToggleBuySell,
+        Action::CycleMinValue,
+        Action::ToggleSearchMode,
+        Action::FocusNext,
+        Action::FocusPrev,
+        Action::TogglePin,
+        Action::SetPriceAlert,
+        Action::ClearPriceAlerts,
+        Action::LengthenAverage,
+        Action::ShortenAverage,
+        Action::RecentCoins,
+        Action::MovePinnedUp,
+        Action::MovePinnedDown,
+        Action::ToggleWatchlistOnly,
+        Action::ToggleAutoFit,
+        Action::SelectColumnLeft,
+        Action::SelectColumnRight,
+        Action::CycleColumnFormat,
+        Action::ToggleView,
+        Action::ToggleDirectionalOnly,
+        Action::ToggleLegend,
+        Action::ToggleColorMode,
+        Action::CycleBalanceSort,
+        Action::FilterBalances,
+        Action::ToggleBell,
+        Action::ToggleUtc,
+        Action::ToggleMillis,
+        Action::ExportBalances,
+        Action::CopyCliArgs,
+        Action::Snapshot,
+        Action::CycleTheme,
+        Action::DeleteAll,
+    ];
+
+    /// The name used in the config's `keymap` section.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Help => "help",
+            Action::CommandPalette => "command_palette",
+            Action::Search => "search",
+            Action::Submit => "submit",
+            Action::SelectDown => "select_down",
+            Action::SelectUp => "select_up",
+            Action::ShowDetails => "show_details",
+            Action::FollowUser => "follow_user",
+            Action::BlockUser => "block_user",
+            Action::BlockCoin => "block_coin",
+            Action::Blocklist => "blocklist",
+            Action::RemoveEntry => "remove_entry",
+            Action::SessionStats => "session_stats",
+            Action::JumpToLargest => "jump_to_largest",
+            Action::EditNote => "edit_note",
+            Action::Bookmarks => "bookmarks",
+            Action::WhaleWatch => "whale_watch",
+            Action::ClearSelection => "clear_selection",
+            Action::TogglePause => "pause",
+            Action::ScrollUp => "scroll_up",
+            Action::ScrollDown => "scroll_down",
+            Action::PageUp => "page_up",
+            Action::PageDown => "page_down",
+            Action::ToggleType => "toggle_type",
+            Action::ToggleBuySell => "toggle_buy_sell",
+            Action::CycleMinValue => "cycle_min_value",
+            Action::ToggleSearchMode => "toggle_search_mode",
+            Action::FocusNext => "focus_next",
+            Action::FocusPrev => "focus_prev",
+            Action::TogglePin => "toggle_pin",
+            Action::SetPriceAlert => "set_price_alert",
+            Action::ClearPriceAlerts => "clear_price_alerts",
+            Action::LengthenAverage => "lengthen_average",
+            Action::ShortenAverage => "shorten_average",
+            Action::RecentCoins => "recent_coins",
+            Action::MovePinnedUp => "move_pinned_up",
+            Action::MovePinnedDown => "move_pinned_down",
+            Action::ToggleWatchlistOnly => "toggle_watchlist_only",
+            Action::ToggleAutoFit => "toggle_auto_fit",
+            Action::SelectColumnLeft => "select_column_left",
+            Action::SelectColumnRight => "select_column_right",
+            Action::CycleColumnFormat => "cycle_column_format",
+            Action::ToggleView => "toggle_view",
+            Action::ToggleDirectionalOnly => "toggle_directional_only",
+            Action::ToggleLegend => "toggle_legend",
+            Action::ToggleColorMode => "toggle_color_mode",
+            Action::CycleBalanceSort => "cycle_balance_sort",
+            Action::FilterBalances => "filter_balances",
+            Action::ToggleBell => "toggle_bell",
+            Action::ToggleUtc => "toggle_utc",
+            Action::ToggleMillis => "toggle_millis",
+            Action::ExportBalances => "export_balances",
+            Action::CopyCliArgs => "copy_cli_args",
+            Action::Snapshot => "snapshot",
+            Action::CycleTheme => "cycle_theme",
+            Action::DeleteAll => "delete_all",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::Help => "Show this help",
+            Action::CommandPalette => "Open the command palette",
+            Action::Search => "Edit the search (@user, ?expr, !db query)",
+            Action::Submit => "Apply the search / open the selected coin",
+            Action::SelectDown => "Select the next row",
+            Action::SelectUp => "Select the previous row",
+            Action::ShowDetails => "Details of the selected trade",
+            Action::FollowUser => "Follow the selected trade's user across all coins",
+            Action::BlockUser => "Hide the selected trade's user",
+            Action::BlockCoin => "Hide the selected trade's coin",
+            Action::Blocklist => "Manage hidden users and coins",
+            Action::RemoveEntry => "Remove the highlighted entry from the hidden or bookmarks list",
+            Action::SessionStats => "Session summary statistics",
+            Action::JumpToLargest => "Jump to the session's largest trade",
+            Action::EditNote => "Add or edit a note on the selected trade",
+            Action::Bookmarks => "List noted trades to review or jump back to",
+            Action::WhaleWatch => "Toggle the whale preset: big live trades, largest first",
+            Action::ClearSelection => "Clear the selection",
+            Action::TogglePause => "Pause or resume the tape",
+            Action::ScrollUp => "Scroll up",
+            Action::ScrollDown => "Scroll down",
+            Action::PageUp => "Scroll up a page",
+            Action::PageDown => "Scroll down a page",
+            Action::ToggleType => "Cycle the trade type filter",
+            Action::ToggleBuySell => "Cycle the buy/sell filter",
+            Action::CycleMinValue => "Cycle the minimum value filter",
+            Action::ToggleSearchMode => "Cycle exact, substring and fuzzy symbol match",
+            Action::FocusNext => "Focus the next matched coin",
+            Action::FocusPrev => "Focus the previous matched coin",
+            Action::TogglePin => "Pin or unpin the focused coin",
+            Action::SetPriceAlert => "Add a price alert line at the focused coin's price",
+            Action::ClearPriceAlerts => "Clear the focused coin's price alerts",
+            Action::LengthenAverage => "Average more trades in the chart's moving average",
+            Action::ShortenAverage => "Average fewer trades (down to hidden)",
+            Action::RecentCoins => "Switch to a recently searched coin",
+            Action::MovePinnedUp => "Move the focused coin up the watchlist",
+            Action::MovePinnedDown => "Move the focused coin down the watchlist",
+            Action::ToggleWatchlistOnly => "Only show watchlist coins",
+            Action::ToggleAutoFit => "Fit columns to the terminal",
+            Action::SelectColumnLeft => "Select the column to the left",
+            Action::SelectColumnRight => "Select the column to the right",
+            Action::CycleColumnFormat => "Full or compact numbers in the selected column",
+            Action::ToggleView => "Switch between tape and coins view",
+            Action::ToggleDirectionalOnly => "Hide coins whose buys and sells cancel out",
+            Action::ToggleLegend => "Show the value tier legend",
+            Action::ToggleColorMode => "Color rows by USD value or token amount",
+            Action::CycleBalanceSort => "Cycle the balances sort",
+            Action::FilterBalances => "Filter balances by username",
+            Action::ToggleBell => "Mute or unmute the bell alert",
+            Action::ToggleUtc => "Switch times between the display zone and UTC",
+            Action::ToggleMillis => "Show or hide milliseconds in times",
+            Action::ExportBalances => "Export balances (focused coin, else all) to CSV or JSON",
+            Action::CopyCliArgs => "Copy a command line that reopens this view",
+            Action::Snapshot => "Save the screen to a text file",
+            Action::CycleTheme => "Switch to the next color theme",
+            Action::DeleteAll => "Delete all trades",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q"],
+            Action::Help => &["?", "f1"],
+            Action::CommandPalette => &[":"],
+            Action::Search => &["e", "/"],
+            Action::Submit => &["enter"],
+            Action::SelectDown => &["j"],
+            Action::SelectUp => &["k"],
+            Action::ShowDetails => &["i"],
+            Action::FollowUser => &["U"],
+            Action::BlockUser => &["X"],
+            Action::BlockCoin => &["K"],
+            Action::Blocklist => &["H"],
+            Action::RemoveEntry => &["d", "delete", "backspace"],
+            Action::SessionStats => &["S"],
+            Action::JumpToLargest => &["R"],
+            Action::EditNote => &["N"],
+            Action::Bookmarks => &["M"],
+            Action::WhaleWatch => &["W"],
+            Action::ClearSelection => &["esc"],
+            Action::TogglePause => &["space"],
+            Action::ScrollUp => &["up"],
+            Action::ScrollDown => &["down"],
+            Action::PageUp => &["pageup"],
+            Action::PageDown => &["pagedown"],
+            Action::ToggleType => &["t"],
+            Action::ToggleBuySell => &["a"],
+            Action::CycleMinValue => &["v"],
+            Action::ToggleSearchMode => &["m"],
+            Action::FocusNext => &["tab"],
+            Action::FocusPrev => &["backtab"],
+            Action::TogglePin => &["p"],
+            Action::SetPriceAlert => &["A"],
+            Action::ClearPriceAlerts => &["ctrl-a"],
+            Action::LengthenAverage => &["+"],
+            Action::ShortenAverage => &["-"],
+            Action::RecentCoins => &["r"],
+            Action::MovePinnedUp => &["["],
+            Action::MovePinnedDown => &["]"],
+            Action::ToggleWatchlistOnly => &["w"],
+            Action::ToggleAutoFit => &["f"],
+            Action::SelectColumnLeft => &["<"],
+            Action::SelectColumnRight => &[">"],
+            Action::CycleColumnFormat => &["c"],
+            Action::ToggleView => &["g"],
+            Action::ToggleDirectionalOnly => &["n"],
+            Action::ToggleLegend => &["L"],
+            Action::ToggleColorMode => &["C"],
+            Action::CycleBalanceSort => &["b"],
+            Action::FilterBalances => &["u"],
+            Action::ToggleBell => &["B"],
+            Action::ToggleUtc => &["z"],
+            Action::ToggleMillis => &["Z"],
+            Action::ExportBalances => &["x"],
+            Action::CopyCliArgs => &["y"],
+            Action::Snapshot => &["P"],
+            Action::CycleTheme => &["T"],
+            Action::DeleteAll => &["D"],
+        }
+    }
+}
+
+/// A key plus the modifiers that matter for matching, e.g. `ctrl-c`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct KeySpec {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeySpec {
+    /// Terminals disagree on whether uppercase letters and back-tab carry
+    /// SHIFT, so it's dropped wherever the key itself already implies it.
+    /// `shift-a` is `A`, since that's what the terminal sends for it.
+    fn normalized(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let code = match code {
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::Char(c.to_ascii_uppercase()),
+            code => code,
+        };
+        let modifiers = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        KeySpec { code, modifiers }
+    }
+
+    /// Parses specs like `q`, `Q`, `ctrl-d`, `alt-enter`, `pagedown` or `f5`.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = spec;
+        // A lone "-" is the minus key, not a separator.
+        while let Some((prefix, key)) = rest.split_once('-').filter(|(_, key)| !key.is_empty()) {
+            modifiers |= match prefix.to_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+            rest = key;
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_lowercase().as_str() {
+                "enter" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "space" => KeyCode::Char(' '),
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "delete" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                name => KeyCode::F(name.strip_prefix('f')?.parse().ok().filter(|n| (1..=12).contains(n))?),
+            },
+        };
+        Some(KeySpec::normalized(code, modifiers))
+    }
+}
+
+impl From<&KeyEvent> for KeySpec {
+    fn from(key: &KeyEvent) -> Self {
+        KeySpec::normalized(key.code, key.modifiers)
+    }
+}
+
+impl fmt::Display for KeySpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "shift-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "f{}", n),
+            KeyCode::PageUp => write!(f, "pageup"),
+            KeyCode::PageDown => write!(f, "pagedown"),
+            KeyCode::BackTab => write!(f, "backtab"),
+            code => write!(f, "{}", format!("{:?}", code).to_lowercase()),
+        }
+    }
+}
+
+/// A config `keymap` entry: one key spec or a list of them.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum KeyBinding {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyBinding {
+    fn specs(&self) -> Vec<&str> {
+        match self {
+            KeyBinding::One(spec) => vec![spec.as_str()],
+            KeyBinding::Many(specs) => specs.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+pub struct Keymap {
+    bindings: HashMap<KeySpec, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::from_config(&BTreeMap::new()).0
+    }
+}
+
+impl Keymap {
+    /// Builds the effective keymap. Configured actions replace their default
+    /// keys entirely; unknown actions and unparsable keys are skipped. Also
+    /// returns warnings about those and about keys bound twice.
+    pub fn from_config(configured: &BTreeMap<String, KeyBinding>) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+        let mut overrides: Vec<(Action, Vec<KeySpec>)> = Vec::new();
+        for (name, binding) in configured {
+            let Some(action) = Action::from_name(name) else {
+                warnings.push(format!("Ignoring keymap entry for unknown action: {}", name));
+                continue;
+            };
+            let specs = binding
+                .specs()
+                .into_iter()
+                .filter_map(|spec| {
+                    let parsed = KeySpec::parse(spec);
+                    if parsed.is_none() {
+                        warnings.push(format!("Ignoring unrecognised key '{}' for {}", spec, name));
+                    }
+                    parsed
+                })
+                .collect();
+            overrides.push((action, specs));
+        }
+
+        let mut bindings = HashMap::new();
+        for action in Action::ALL {
+            if overrides.iter().any(|(overridden, _)| *overridden == action) {
+                continue;
+            }
+            for spec in action.default_keys() {
+                bindings.insert(KeySpec::parse(spec).expect("default keys parse"), action);
+            }
+        }
+        // Configured keys win over defaults they collide with.
+        let mut configured_keys: HashMap<KeySpec, Action> = HashMap::new();
+        for (action, specs) in overrides {
+            for spec in specs {
+                if let Some(previous) = configured_keys.insert(spec, action)
+                    && previous != action
+                {
+                    warnings.push(format!("Key {} is bound to both {} and {}", spec, previous.name(), action.name()));
+                }
+                bindings.insert(spec, action);
+            }
+        }
+        (Keymap { bindings }, warnings)
+    }
+
+    pub fn action_for(&self, key: &KeyEvent) -> Option<Action> {
+        self.bindings.get(&KeySpec::from(key)).copied()
+    }
+
+    /// Every key bound to `action`, for display.
+    pub fn keys_for(&self, action: Action) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .bindings
+            .iter()
+            .filter(|(_, bound)| **bound == action)
+            .map(|(spec, _)| spec.to_string())
+            .collect();
+        keys.sort();
+        keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeySpec {
+        KeySpec::from(&KeyEvent::new(code, modifiers))
+    }
+
+    #[test]
+    fn parses_keys_with_modifiers() {
+        assert_eq!(KeySpec::parse("q"), Some(key(KeyCode::Char('q'), KeyModifiers::NONE)));
+        assert_eq!(KeySpec::parse("ctrl-d"), Some(key(KeyCode::Char('d'), KeyModifiers::CONTROL)));
+        assert_eq!(KeySpec::parse("Alt-Enter"), Some(key(KeyCode::Enter, KeyModifiers::ALT)));
+        assert_eq!(KeySpec::parse("pagedown"), Some(key(KeyCode::PageDown, KeyModifiers::NONE)));
+        assert_eq!(KeySpec::parse("f12"), Some(key(KeyCode::F(12), KeyModifiers::NONE)));
+        for bad in ["", "f13", "hyper-a", "ctrl-", "enterr"] {
+            assert_eq!(KeySpec::parse(bad), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn shift_is_folded_into_the_key() {
+        let upper_a = key(KeyCode::Char('A'), KeyModifiers::NONE);
+        assert_eq!(KeySpec::parse("A"), Some(upper_a));
+        assert_eq!(KeySpec::parse("shift-a"), Some(upper_a));
+        // Some terminals report SHIFT with the uppercase letter, some don't.
+        assert_eq!(key(KeyCode::Char('A'), KeyModifiers::SHIFT), upper_a);
+        assert_eq!(KeySpec::parse("ctrl-shift-a"), Some(key(KeyCode::Char('A'), KeyModifiers::CONTROL | KeyModifiers::SHIFT)));
+        assert_eq!(KeySpec::parse("shift-tab"), Some(key(KeyCode::BackTab, KeyModifiers::SHIFT)));
+        assert_eq!(KeySpec::parse("backtab"), KeySpec::parse("shift-tab"));
+    }
+
+    #[test]
+    fn a_lone_dash_is_the_minus_key() {
+        assert_eq!(KeySpec::parse("-"), Some(key(KeyCode::Char('-'), KeyModifiers::NONE)));
+        assert_eq!(KeySpec::parse("ctrl--"), Some(key(KeyCode::Char('-'), KeyModifiers::CONTROL)));
+    }
+
+    #[test]
+    fn bad_config_entries_come_back_as_warnings() {
+        let configured = BTreeMap::from([
+            ("quit".to_string(), KeyBinding::One("ctrl-q".to_string())),
+            ("search".to_string(), KeyBinding::Many(vec!["ctrl-q".to_string(), "nope-x".to_string()])),
+            ("fly".to_string(), KeyBinding::One("x".to_string())),
+        ]);
+        let (keymap, warnings) = Keymap::from_config(&configured);
+        assert_eq!(
+            warnings,
+            [
+                "Ignoring keymap entry for unknown action: fly",
+                "Ignoring unrecognised key 'nope-x' for search",
+                "Key ctrl-q is bound to both quit and search",
+            ]
+        );
+        assert_eq!(keymap.keys_for(Action::Quit), Vec::<String>::new());
+    }
+}
@@ -1,29 +1,119 @@
-use chrono::Local;
+mod cli;
+mod clipboard;
+mod config;
+mod csv;
+mod db_search;
+mod export;
+mod feed;
+mod filter_expr;
+mod format;
+mod fuzzy;
+mod keymap;
+mod metrics;
+mod palette;
+mod proxy;
+mod raw_stream;
+mod retention;
+mod sampling;
+mod schema;
+mod server;
+mod snapshot;
+mod store;
+mod symbols;
+mod theme;
+mod trade_channel;
+mod volume_alert;
+
+use chrono::{Local, NaiveTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use cli::{parse_args, resolve_settings};
+use config::{builtin_presets, load_or_create_config, ActionGlyphs, AutoExport, ColorMode, ExportFormat, ExportScope, IncompleteTrades, InitialScroll, MinTerminalSize, NumberGrouping, Preset, ScrollbarChars, SnapshotFormat, TapeSort, TradeDecay, WHALE_PRESET, ActivityThresholds, AutoPause, BellAlert, NumberFormat, DEFAULT_AMOUNT_TIERS, DEFAULT_DISPLAY_CAPACITY, DEFAULT_MAX_RENDERED_ROWS, DEFAULT_MOVING_AVERAGE_PERIOD, DEFAULT_VALUE_TIERS, MAX_COLUMN_DECIMALS};
+use feed::{resolve_subscriptions, run_feed, Endpoint, IngestFilter};
+use format::{format_adaptive, format_compact, format_price, format_value, group_digits, truncate_to};
+use keymap::{Action, Keymap};
+use metrics::{ConnectionState, Metrics, RateMeter};
+use palette::Palette;
+use proxy::Proxy;
+use sampling::Sampler;
+use schema::SchemaError;
+use store::TradeStore;
+use theme::{Theme, ThemeName};
+use trade_channel::BoundedReceiver;
+use volume_alert::VolumeWatcher;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
     backend::{Backend, CrosstermBackend},
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Cell, Paragraph, Row, Table, Wrap},
+    widgets::{BarChart, Block, Borders, Cell, Clear, Paragraph, Row, Sparkline, Table, Wrap},
     Frame, Terminal,
 };
-use serde_json::Value;
 use std::{
-    collections::HashMap,
-    io::{self},
-    sync::mpsc::{self, Receiver, Sender},
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    io::{self, Write},
+    ops::Bound,
+    path::Path,
+    sync::{
+        mpsc::{self, Receiver},
+        Arc,
+    },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
-use tungstenite::{connect, Message};
 use rusqlite::{params, Connection, Result as RusqliteResult};
 
-const DB_PATH: &str = "trades.db";
+pub const DB_PATH: &str = "trades.db";
+const PRICE_HISTORY_MAX_AGE_SECS: i64 = 24 * 60 * 60;
+const PRICE_HISTORY_PRUNE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// Most trades taken off the queue between two frames, so a flood can't
+/// starve drawing and input.
+const TRADES_PER_TICK: usize = 1_000;
+/// How long one tick may spend on queued trades.
+const TRADE_TICK_BUDGET: Duration = Duration::from_millis(50);
+const PRICE_HISTORY_LOAD_LIMIT: usize = 500;
+/// Net flow within this fraction of a coin's volume counts as balanced.
+const BALANCED_FLOW_RATIO: f64 = 0.01;
+/// Trades read from the database per page when scrolling past the buffer.
+const HISTORY_PAGE_SIZE: usize = 500;
+const HISTORY_PAGES_PER_LOAD: usize = 10;
+const FLASH_DURATION: Duration = Duration::from_millis(400);
+/// Only the newest few rows flash, so bursts don't light up the whole table.
+const FLASH_MAX_ROWS: usize = 3;
+/// Pins past this are counted under the watchlist rather than shown.
+const WATCHLIST_MAX_ROWS: usize = 8;
+/// Balances this close to zero are float leftovers of buys and sells that
+/// cancel out, and count as no holding at all.
+const BALANCE_DUST: f64 = 1e-6;
+/// How many coins a fuzzy search shows at most.
+const FUZZY_MAX_MATCHES: usize = 5;
+/// Kept to nine so each recent coin has a single-digit key.
+const RECENT_COINS_MAX: usize = 9;
+/// Relative gap between implied and feed price that gets highlighted.
+const IMPLIED_PRICE_TOLERANCE: f64 = 0.01;
+/// How long an alert-driven search holds before a smaller alert may replace it.
+const ALERT_FOLLOW_HOLD: Duration = Duration::from_secs(15);
+const MOVING_AVERAGE_STEP: usize = 5;
+/// How long the footer calls out a new largest trade of the session.
+const RECORD_FLASH_DURATION: Duration = Duration::from_secs(3);
+/// Furthest a smoothly scrolling view lags the newest trade; past this it
+/// jumps to keep up.
+const CATCH_UP_MAX_LAG: usize = 50;
+/// Width of `HH:MM:SS.mmm` in the Time column.
+const TIME_MILLIS_WIDTH: u16 = 12;
+/// Narrower than this, the compare view stacks its two coins.
+const COMPARE_SIDE_BY_SIDE_MIN_WIDTH: u16 = 100;
+/// Rows each stacked compare pane needs: stats, chart and a few trades.
+const COMPARE_MIN_PANE_HEIGHT: u16 = 13;
+/// Longest note that can be attached to a trade.
+const NOTE_MAX_CHARS: usize = 200;
+/// Drawn in the tape's left border next to trades with a note.
+const NOTE_MARKER: char = '*';
+const RECENT_VOLUME_WINDOW: Duration = Duration::from_secs(5 * 60);
 
 #[derive(Clone, Debug)]
 struct Trade {
@@ -35,631 +125,5383 @@ struct Trade {
     coin_symbol: String,
     total_value: f64,
     price: f64,
+    /// Exchange-side trade id, when the feed provides one.
+    id: Option<String>,
+    /// When this session received the trade; `None` for loaded trades.
+    received_at: Option<Instant>,
+    /// Unix seconds (UTC) the trade was recorded at. `None` for trades saved
+    /// before dates were kept, which only have `timestamp`.
+    recorded_at: Option<i64>,
+    /// Row id in the `trades` table once saved.
+    row_id: Option<i64>,
 }
 
-#[derive(PartialEq)]
-enum InputMode {
-    Normal,
-    Editing,
-}
+impl Trade {
+    /// A zero amount or price means the trade can't be trusted for balances
+    /// or prices.
+    fn is_incomplete(&self) -> bool {
+        self.amount == 0.0 || self.price == 0.0
+    }
 
-struct App {
-    search_input: String,
-    active_search_symbol: Option<String>,
-    all_trades: Vec<Trade>,
-    user_balances: HashMap<String, HashMap<String, f64>>,
-    scroll_offset: usize,
-    trade_type_filter: Option<String>,
-    input_mode: InputMode,
-    cursor_position: usize,
+    /// Whether the trade counts towards balances and stats under `policy`.
+    fn counts_towards_stats(&self, policy: IncompleteTrades) -> bool {
+        policy == IncompleteTrades::Keep || !self.is_incomplete()
+    }
 }
 
-impl App {
-    fn new(initial_trades: Vec<Trade>) -> App {
-        App {
-            search_input: String::new(),
-            active_search_symbol: None,
-            all_trades: initial_trades,
-            user_balances: HashMap::new(),
-            scroll_offset: 0,
-            trade_type_filter: None,
-            input_mode: InputMode::Normal,
-            cursor_position: 0,
-        }
+/// The milliseconds in a stored `HH:MM:SS.mmm` timestamp. Trades saved
+/// before milliseconds were kept have none.
+fn timestamp_millis(timestamp: &str) -> Option<u32> {
+    if !timestamp.contains('.') {
+        return None;
     }
+    let time = NaiveTime::parse_from_str(timestamp, "%H:%M:%S%.f").ok()?;
+    Some(time.nanosecond() / 1_000_000)
+}
 
-    fn add_trade(&mut self, trade: Trade, conn: &Connection) {
-        if insert_trade_db(conn, &trade).is_err() {
-            eprintln!("Failed to save trade to DB: {:?}", trade);
-        }
+/// Running per-coin figures, updated as trades arrive.
+#[derive(Default)]
+struct CoinStats {
+    /// The symbol as the feed last spelled it; the map key is normalized.
+    display_symbol: String,
+    last_price: f64,
+    /// Price when this session first saw the coin, used for % change.
+    session_start_price: Option<f64>,
+    last_trade_time: Option<String>,
+    last_trade_at: Option<i64>,
+    /// Trade values seen within `RECENT_VOLUME_WINDOW`, oldest first.
+    recent_trades: VecDeque<(Instant, f64)>,
+    trade_count: u64,
+    buy_volume: f64,
+    sell_volume: f64,
+    /// Totals as of the last time the coin was on screen.
+    last_viewed: Option<ViewedSnapshot>,
+}
 
-        let user_coin_balances = self
-            .user_balances
-            .entry(trade.username.clone())
-            .or_insert_with(HashMap::new);
-        let balance = user_coin_balances
-            .entry(trade.coin_symbol.clone())
-            .or_insert(0.0);
+#[derive(Clone, Copy, Debug)]
+struct ViewedSnapshot {
+    trade_count: u64,
+    volume: f64,
+    price: f64,
+}
 
+impl CoinStats {
+    /// Folds a trade into the running totals. Used for loaded and live trades.
+    fn accumulate(&mut self, trade: &Trade) {
+        self.display_symbol.clone_from(&trade.coin_symbol);
+        self.last_price = trade.price;
+        self.last_trade_time = Some(trade.timestamp.clone());
+        self.last_trade_at = trade.recorded_at;
+        self.trade_count += 1;
         if trade.action == "BUY" {
-            *balance += trade.amount;
+            self.buy_volume += trade.total_value;
         } else if trade.action == "SELL" {
-            *balance -= trade.amount;
+            self.sell_volume += trade.total_value;
         }
-        
-        self.all_trades.insert(0, trade);
     }
 
-    fn recalculate_balances_from_trades(&mut self) {
-        self.user_balances.clear();
-        for trade in self.all_trades.iter().rev() {
-            let user_coin_balances = self
-                .user_balances
-                .entry(trade.username.clone())
-                .or_insert_with(HashMap::new);
-            let balance = user_coin_balances
-                .entry(trade.coin_symbol.clone())
-                .or_insert(0.0);
+    fn record(&mut self, trade: &Trade, now: Instant) {
+        // A zero first price can't anchor a percentage, so keep looking.
+        match self.session_start_price {
+            Some(start) if start != 0.0 => {}
+            _ => self.session_start_price = Some(trade.price),
+        }
+        self.accumulate(trade);
+        self.recent_trades.push_back((now, trade.total_value));
+        self.expire(now);
+    }
 
-            if trade.action == "BUY" {
-                *balance += trade.amount;
-            } else if trade.action == "SELL" {
-                *balance -= trade.amount;
+    fn net_volume(&self) -> f64 {
+        self.buy_volume - self.sell_volume
+    }
+
+    /// Whether buys and sells cancel out to within `BALANCED_FLOW_RATIO` of
+    /// the coin's volume, i.e. churn with no direction.
+    fn is_balanced(&self) -> bool {
+        let volume = self.buy_volume + self.sell_volume;
+        self.net_volume().abs() <= (volume * BALANCED_FLOW_RATIO).max(f64::EPSILON)
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some((seen, _)) = self.recent_trades.front() {
+            if now.duration_since(*seen) <= RECENT_VOLUME_WINDOW {
+                break;
             }
+            self.recent_trades.pop_front();
         }
     }
 
+    fn session_change_pct(&self) -> Option<f64> {
+        let start = self.session_start_price?;
+        if start == 0.0 {
+            return None;
+        }
+        Some((self.last_price - start) / start * 100.0)
+    }
 
-    fn get_visible_trades(&self) -> Vec<Trade> { 
-        let trades_after_type_filter: Vec<Trade> = match self.trade_type_filter.as_deref() {
-            None => {
-                self.all_trades.iter().cloned().collect()
-            }
-            Some(specific_filter_type) => {
-                self.all_trades.iter()
-                    .filter(|t| t.trade_type_val.to_lowercase() == specific_filter_type.to_lowercase())
-                    .cloned()
-                    .collect()
-            }
-        };
+    fn recent_volume(&self) -> f64 {
+        self.recent_trades.iter().fold(0.0, |sum, (_, value)| sum + value)
+    }
 
-        if let Some(symbol) = &self.active_search_symbol {
-            trades_after_type_filter
-                .into_iter() 
-                .filter(|t| t.coin_symbol.to_uppercase() == *symbol)
-                .collect()
-        } else {
-            trades_after_type_filter
+    fn mark_viewed(&mut self) {
+        self.last_viewed = Some(ViewedSnapshot {
+            trade_count: self.trade_count,
+            volume: self.buy_volume + self.sell_volume,
+            price: self.last_price,
+        });
+    }
+
+    /// What happened since the coin was last on screen, e.g.
+    /// `+3 trades, +$5.00K, +1.20%`. `-` if it was never viewed, empty when
+    /// nothing has traded since.
+    fn since_viewed(&self) -> String {
+        let Some(viewed) = self.last_viewed else {
+            return "-".to_string();
+        };
+        let new_trades = self.trade_count.saturating_sub(viewed.trade_count);
+        if new_trades == 0 {
+            return String::new();
+        }
+        let volume = self.buy_volume + self.sell_volume - viewed.volume;
+        let mut badge = format!(
+            "+{} trade{}, +${}",
+            new_trades,
+            if new_trades == 1 { "" } else { "s" },
+            format_compact(volume)
+        );
+        if viewed.price != 0.0 {
+            badge.push_str(&format!(", {:+.2}%", (self.last_price - viewed.price) / viewed.price * 100.0));
         }
+        badge
     }
-    
-    fn scroll_up(&mut self) {
-        if self.scroll_offset > 0 {
-            self.scroll_offset -= 1;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Column {
+    Time,
+    Type,
+    Action,
+    User,
+    Amount,
+    Coin,
+    Total,
+    Price,
+    /// `total_value / amount`, to compare against the feed's `price`.
+    Implied,
+    SessionChange,
+    Id,
+}
+
+impl Column {
+    const ALL: [Column; 11] = [
+        Column::Time,
+        Column::Type,
+        Column::Action,
+        Column::User,
+        Column::Amount,
+        Column::Coin,
+        Column::Total,
+        Column::Price,
+        Column::Implied,
+        Column::SessionChange,
+        Column::Id,
+    ];
+
+    const DEFAULT: [Column; 8] = [
+        Column::Time,
+        Column::Type,
+        Column::Action,
+        Column::User,
+        Column::Amount,
+        Column::Coin,
+        Column::Total,
+        Column::Price,
+    ];
+
+    fn id(self) -> &'static str {
+        match self {
+            Column::Time => "time",
+            Column::Type => "type",
+            Column::Action => "action",
+            Column::User => "user",
+            Column::Amount => "amount",
+            Column::Coin => "coin",
+            Column::Total => "total",
+            Column::Price => "price",
+            Column::Implied => "implied",
+            Column::SessionChange => "change",
+            Column::Id => "id",
         }
     }
 
-    fn scroll_down(&mut self, num_visible_items: usize) {
-        let total_items = self.get_visible_trades().len();
-        if total_items > 0 && self.scroll_offset < total_items.saturating_sub(1) {
-            if total_items > num_visible_items && self.scroll_offset < total_items - num_visible_items {
-                self.scroll_offset += 1;
-            } else if total_items <= num_visible_items && self.scroll_offset < total_items -1 {
-                 self.scroll_offset += 1;
-            } else if total_items > num_visible_items && self.scroll_offset >= total_items - num_visible_items {
-                self.scroll_offset = total_items - num_visible_items;
-            }
+    fn from_id(id: &str) -> Option<Column> {
+        Column::ALL.into_iter().find(|c| c.id() == id.to_lowercase())
+    }
+
+    /// Whether the column shows a number that `NumberFormat` applies to.
+    fn is_numeric(self) -> bool {
+        matches!(self, Column::Amount | Column::Total | Column::Price | Column::Implied)
+    }
+
+    /// Decimal places shown unless `column_decimals` says otherwise.
+    fn default_decimals(self) -> usize {
+        match self {
+            Column::Price | Column::Implied => 8,
+            _ => 2,
         }
     }
 
-    fn toggle_trade_type_filter(&mut self) {
-        match self.trade_type_filter.as_deref() {
-            Some("live-trade") => { 
-                self.trade_type_filter = Some("all-trades".to_string()); 
-            }
-            Some("all-trades") => { 
-                self.trade_type_filter = Some("live-trade".to_string());
-            }
-            None => {
-                self.trade_type_filter = Some("all-trades".to_string());
-            }
-            _ => { 
-                self.trade_type_filter = Some("live-trade".to_string());
-            }
+    fn format_number(self, value: f64, app: &App) -> String {
+        let format = app.column_formats.get(&self).copied().unwrap_or_default();
+        let formatted = if format == NumberFormat::Compact && value.abs() >= 1000.0 {
+            format_compact(value)
+        } else {
+            let decimals = app.column_decimals.get(&self).copied().unwrap_or(self.default_decimals());
+            format_adaptive(value, decimals)
+        };
+        match app.number_grouping.separators() {
+            Some((thousands, decimal)) => group_digits(&formatted, thousands, decimal),
+            None => formatted,
         }
-        self.scroll_offset = 0;
     }
 
-    fn move_cursor_left(&mut self) {
-        if self.cursor_position > 0 {
-            self.cursor_position -= 1;
+    fn header(self) -> &'static str {
+        match self {
+            Column::Time => "Time",
+            Column::Type => "Type",
+            Column::Action => "Action",
+            Column::User => "User",
+            Column::Amount => "Amount",
+            Column::Coin => "Coin",
+            Column::Total => "Total USD",
+            Column::Price => "Price USD",
+            Column::Implied => "Implied USD",
+            Column::SessionChange => "Sess %",
+            Column::Id => "Trade ID",
         }
     }
 
-    fn move_cursor_right(&mut self) {
-        if self.cursor_position < self.search_input.len() {
-            self.cursor_position += 1;
+    fn base_width(self) -> u16 {
+        match self.width() {
+            Constraint::Length(width) => width,
+            _ => 0,
         }
     }
 
-    fn enter_char(&mut self, new_char: char) {
-        self.search_input.insert(self.cursor_position, new_char);
-        self.move_cursor_right();
+    /// Auto-fit keeps every column at least its fixed width and hands the
+    /// leftover space to the free-text columns.
+    fn fit_width(self) -> Constraint {
+        match self {
+            Column::User => Constraint::Fill(3),
+            Column::Coin => Constraint::Fill(1),
+            _ => Constraint::Min(self.base_width()),
+        }
     }
 
-    fn delete_char(&mut self) {
-        if self.cursor_position > 0 && !self.search_input.is_empty() {
-            self.search_input.remove(self.cursor_position - 1);
-            self.move_cursor_left();
+    fn width(self) -> Constraint {
+        match self {
+            Column::Time => Constraint::Length(10),
+            Column::Type => Constraint::Length(10),
+            Column::Action => Constraint::Length(6),
+            Column::User => Constraint::Length(15),
+            Column::Amount => Constraint::Length(10),
+            Column::Coin => Constraint::Length(8),
+            Column::Total => Constraint::Length(12),
+            Column::Price => Constraint::Length(14),
+            Column::Implied => Constraint::Length(14),
+            Column::SessionChange => Constraint::Length(9),
+            Column::Id => Constraint::Length(12),
         }
     }
 
-    fn submit_search(&mut self) {
-        if self.search_input.is_empty() {
-            self.active_search_symbol = None;
-        } else {
-            self.active_search_symbol = Some(self.search_input.to_uppercase().clone());
+    /// `width` is what the table actually gives the column, which auto-fit
+    /// can make wider than `base_width`.
+    fn cell<'a>(self, trade: &'a Trade, app: &App, width: u16) -> Cell<'a> {
+        let use_color = app.use_color;
+        match self {
+            Column::Time => Cell::from(app.display_time(trade.recorded_at, &trade.timestamp)),
+            Column::Type => {
+                let style = fg_style(trade_type_color(&trade.trade_type_val, &app.theme), use_color);
+                Cell::from(trade.trade_type_val.as_str()).style(style)
+            }
+            Column::Action => {
+                // Without color the markers are the only cue, so fall back to signs.
+                let markers = match (app.action_glyphs.markers(), use_color) {
+                    (Some(markers), _) => Some(markers),
+                    (None, false) => ActionGlyphs::Signs.markers(),
+                    (None, true) => None,
+                };
+                let text = match markers {
+                    Some((buy, sell)) => {
+                        let marker = match trade.action.as_str() {
+                            "BUY" => buy,
+                            "SELL" => sell,
+                            _ => " ",
+                        };
+                        format!("{}{}", marker, trade.action)
+                    }
+                    None => trade.action.clone(),
+                };
+                if !use_color {
+                    return Cell::from(text);
+                }
+                let action_color = if trade.action == "BUY" {
+                    app.theme.positive
+                } else if trade.action == "SELL" {
+                    app.theme.negative
+                } else {
+                    app.theme.neutral
+                };
+                Cell::from(Span::styled(text, Style::default().fg(action_color)))
+            }
+            Column::User => Cell::from(truncate_to(&trade.username, width as usize)),
+            Column::Amount => Cell::from(self.format_number(trade.amount, app)),
+            Column::Coin => Cell::from(truncate_to(&trade.coin_symbol, width as usize)),
+            Column::Total => Cell::from(self.format_number(trade.total_value, app)),
+            Column::Price => Cell::from(self.format_number(trade.price, app)),
+            Column::Implied => {
+                let Some(implied) = implied_price(trade) else {
+                    return Cell::from("-");
+                };
+                let text = self.format_number(implied, app);
+                if price_diverges(implied, trade.price) {
+                    Cell::from(Span::styled(text, fg_style(app.theme.warning, use_color).add_modifier(Modifier::BOLD)))
+                } else {
+                    Cell::from(text)
+                }
+            }
+            Column::SessionChange => {
+                let change = app
+                    .coin_stats
+                    .get(&symbols::normalize(&trade.coin_symbol))
+                    .and_then(CoinStats::session_change_pct);
+                match change {
+                    Some(change) => Cell::from(format!("{:+.2}%", change)),
+                    None => Cell::from("-"),
+                }
+            }
+            Column::Id => Cell::from(trade.id.as_deref().unwrap_or("-")),
         }
-        self.scroll_offset = 0; 
     }
 }
 
-fn init_db(conn: &Connection) -> RusqliteResult<()> {
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS trades (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            timestamp TEXT NOT NULL,
-            trade_type_val TEXT NOT NULL,
-            action TEXT NOT NULL,
-            username TEXT NOT NULL,
-            amount REAL NOT NULL,
-            coin_symbol TEXT NOT NULL,
-            total_value REAL NOT NULL,
-            price REAL NOT NULL
-        )",
-        [],
-    )?;
-    Ok(())
+/// Price per token worked out from the trade's totals, when it has an amount.
+fn implied_price(trade: &Trade) -> Option<f64> {
+    (trade.amount != 0.0).then(|| trade.total_value / trade.amount)
 }
 
-fn insert_trade_db(conn: &Connection, trade: &Trade) -> RusqliteResult<usize> {
-    conn.execute(
-        "INSERT INTO trades (timestamp, trade_type_val, action, username, amount, coin_symbol, total_value, price)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-        params![
-            trade.timestamp,
-            trade.trade_type_val,
-            trade.action,
-            trade.username,
-            trade.amount,
-            trade.coin_symbol,
-            trade.total_value,
-            trade.price
-        ],
-    )
+/// Whether `implied` is off from the feed's `price` by more than
+/// `IMPLIED_PRICE_TOLERANCE`, relative to the price.
+fn price_diverges(implied: f64, price: f64) -> bool {
+    if price == 0.0 {
+        return implied != 0.0;
+    }
+    ((implied - price) / price).abs() > IMPLIED_PRICE_TOLERANCE
 }
 
-fn load_trades_from_db(conn: &Connection) -> RusqliteResult<Vec<Trade>> {
-    let mut stmt = conn.prepare("SELECT timestamp, trade_type_val, action, username, amount, coin_symbol, total_value, price FROM trades ORDER BY id DESC")?;
-    let trade_iter = stmt.query_map([], |row| {
-        Ok(Trade {
-            timestamp: row.get(0)?,
-            trade_type_val: row.get(1)?,
-            action: row.get(2)?,
-            username: row.get(3)?,
-            amount: row.get(4)?,
-            coin_symbol: row.get(5)?,
-            total_value: row.get(6)?,
-            price: row.get(7)?,
+/// Reads per-column number formats from the config, with a warning for each
+/// id that isn't a numeric column.
+fn resolve_column_formats(configured: &BTreeMap<String, NumberFormat>) -> (HashMap<Column, NumberFormat>, Vec<String>) {
+    let mut warnings = Vec::new();
+    let formats = configured
+        .iter()
+        .filter_map(|(id, format)| match Column::from_id(id) {
+            Some(column) if column.is_numeric() => Some((column, *format)),
+            _ => {
+                warnings.push(format!("Ignoring number format for '{}': not a numeric column (amount, total, price, implied)", id));
+                None
+            }
         })
-    })?;
-
-    let mut trades = Vec::new();
-    for trade in trade_iter {
-        trades.push(trade?);
-    }
-    Ok(trades)
+        .collect();
+    (formats, warnings)
 }
 
+/// Reads per-column decimal places from the config, with a warning for each
+/// id that isn't a numeric column and each count past `MAX_COLUMN_DECIMALS`.
+fn resolve_column_decimals(configured: &BTreeMap<String, usize>) -> (HashMap<Column, usize>, Vec<String>) {
+    let mut warnings = Vec::new();
+    let decimals = configured
+        .iter()
+        .filter_map(|(id, &decimals)| match Column::from_id(id) {
+            Some(_) if decimals > MAX_COLUMN_DECIMALS => {
+                warnings.push(format!("Ignoring {} decimals for '{}': at most {} are shown", decimals, id, MAX_COLUMN_DECIMALS));
+                None
+            }
+            Some(column) if column.is_numeric() => Some((column, decimals)),
+            _ => {
+                warnings.push(format!("Ignoring decimals for '{}': not a numeric column (amount, total, price, implied)", id));
+                None
+            }
+        })
+        .collect();
+    (decimals, warnings)
+}
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let conn = Connection::open(DB_PATH)?;
-    init_db(&conn)?;
-    let initial_trades = load_trades_from_db(&conn).unwrap_or_else(|e| {
-        eprintln!("Failed to load trades from DB: {}. Starting with empty list.", e);
-        Vec::new()
-    });
-
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-
-    let (tx, rx): (Sender<Trade>, Receiver<Trade>) = mpsc::channel();
-
-    thread::spawn(move || {
-        let (mut socket, _response) =
-            connect("ws://ws.rugplay.com/api/").expect("Can't connect to WebSocket");
+/// Turns configured column ids into columns, warning about (and skipping)
+/// ids that don't name a column. Falls back to the default columns when nothing
+/// usable is configured.
+fn resolve_columns(ids: Option<&[String]>) -> Vec<Column> {
+    let Some(ids) = ids else {
+        return Column::DEFAULT.to_vec();
+    };
+    let mut columns = Vec::new();
+    for id in ids {
+        match Column::from_id(id) {
+            Some(column) if !columns.contains(&column) => columns.push(column),
+            Some(_) => {}
+            None => eprintln!("Ignoring unknown column id in config: {}", id),
+        }
+    }
+    if columns.is_empty() {
+        Column::DEFAULT.to_vec()
+    } else {
+        columns
+    }
+}
 
-        socket
-            .send(Message::Text(
-                "{\"type\":\"subscribe\",\"channel\":\"trades:all\"}".into(),
-            ))
-            .unwrap();
-        socket
-            .send(Message::Text(
-                "{\"type\":\"set_coin\",\"coinSymbol\":\"@global\"}".into(),
-            ))
-            .unwrap();
-
-        loop {
-            match socket.read() {
-                Ok(msg) => {
-                    if msg.is_text() || msg.is_binary() {
-                        let message_str = msg.to_string();
-                        let v: Value = match serde_json::from_str(&message_str) {
-                            Ok(val) => val,
-                            Err(_) => continue,
-                        };
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SearchMode {
+    Exact,
+    Substring,
+    /// The closest few known coins, see `fuzzy::symbol_score`.
+    Fuzzy,
+}
 
-                        let trade_type_val = v["type"].as_str().unwrap_or_default().to_string();
-                        if trade_type_val == "ping" {
-                            continue;
-                        }
+impl SearchMode {
+    fn parse(value: &str) -> Option<SearchMode> {
+        match value.to_lowercase().as_str() {
+            "exact" => Some(SearchMode::Exact),
+            "substring" => Some(SearchMode::Substring),
+            "fuzzy" => Some(SearchMode::Fuzzy),
+            _ => None,
+        }
+    }
 
-                        if v["data"].is_object() {
-                            let data = &v["data"];
-                            let action = data["type"].as_str().unwrap_or_default().to_string();
-                            let username = data["username"].as_str().unwrap_or_default().to_string();
-                            let amount = data["amount"].as_f64().unwrap_or_default();
-                            let coin_symbol = data["coinSymbol"].as_str().unwrap_or_default().to_string();
-                            let total_value = data["totalValue"].as_f64().unwrap_or_default();
-                            let price = data["price"].as_f64().unwrap_or_default();
-                            let timestamp = Local::now().format("%H:%M:%S").to_string();
-
-                            let trade = Trade {
-                                timestamp,
-                                trade_type_val,
-                                action,
-                                username,
-                                amount,
-                                coin_symbol,
-                                total_value,
-                                price,
-                            };
-
-                            if tx.send(trade).is_err() {
-                                break; 
-                            }
-                        }
-                    }
-                }
-                Err(_e) => {
-                    break;
-                }
-            }
+    fn flag_value(self) -> &'static str {
+        match self {
+            SearchMode::Exact => "exact",
+            SearchMode::Substring => "substring",
+            SearchMode::Fuzzy => "fuzzy",
         }
-    });
+    }
+}
 
-    let mut app = App::new(initial_trades);
-    app.recalculate_balances_from_trades();
+/// One user's trading in one coin, for the follow-user panel.
+#[derive(Default)]
+struct UserCoinSummary {
+    coin: String,
+    trade_count: u64,
+    /// USD traded either way.
+    volume: f64,
+    balance: f64,
+    pnl: f64,
+}
 
-    run_app(&mut terminal, app, rx, &conn)?;
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ViewMode {
+    /// The live trade tape.
+    Tape,
+    /// One aggregated row per coin.
+    Coins,
+    /// Results of a `!` search over the whole database.
+    DbResults,
+    /// Two coins' tapes and stats side by side.
+    Compare,
+}
 
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+/// How far a following view lags the newest trade when scrolling smoothly.
+#[derive(Default)]
+struct CatchUp {
+    rows: usize,
+    /// `scroll_offset` as catch-up last left it. Anything else means the
+    /// user scrolled, which cancels the catch-up.
+    offset: usize,
+    /// Fractions of a row earned but not yet moved.
+    credit: f64,
+    last_step: Option<Instant>,
+}
 
-    Ok(())
+/// A paused tape; see `AutoPause`.
+struct Pause {
+    /// The feed rate that set off an auto-pause; `None` when paused by hand.
+    burst_rate: Option<f64>,
+    /// New trades that have landed above the view since.
+    held: usize,
 }
 
-fn run_app<B: Backend>(
-    terminal: &mut Terminal<B>,
-    mut app: App,
-    rx: Receiver<Trade>,
-    conn: &Connection,
-) -> io::Result<()> {
-    loop {
-        match rx.try_recv() {
-            Ok(trade) => {
-                let was_at_top = app.scroll_offset == 0;
-                
-                app.add_trade(trade, conn); 
+/// One side of the compare view.
+struct ComparedCoin {
+    symbol: String,
+    price_history: Vec<(i64, f64)>,
+}
 
-                if !was_at_top && app.input_mode == InputMode::Normal {
-                    app.scroll_offset += 1;
-                }
-            }
-            Err(mpsc::TryRecvError::Empty) => {}
-            Err(mpsc::TryRecvError::Disconnected) => {
-                break; 
-            }
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum BalanceSort {
+    BalanceDesc,
+    BalanceAsc,
+    Username,
+}
+
+impl BalanceSort {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "balance" | "value" | "desc" => Some(BalanceSort::BalanceDesc),
+            "asc" => Some(BalanceSort::BalanceAsc),
+            "user" | "name" => Some(BalanceSort::Username),
+            _ => None,
         }
+    }
 
-        terminal.draw(|f| ui(f, &mut app))?;
+    fn next(self) -> Self {
+        match self {
+            BalanceSort::BalanceDesc => BalanceSort::BalanceAsc,
+            BalanceSort::BalanceAsc => BalanceSort::Username,
+            BalanceSort::Username => BalanceSort::BalanceDesc,
+        }
+    }
 
-        if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                match app.input_mode {
-                    InputMode::Normal => match key.code {
-                        KeyCode::Char('q') => return Ok(()),
-                        KeyCode::Char('t') => app.toggle_trade_type_filter(),
-                        KeyCode::Char('e') | KeyCode::Char('/') => {
-                            app.input_mode = InputMode::Editing;
-                        }
-                        KeyCode::Enter => app.submit_search(),
-                        KeyCode::Up => app.scroll_up(),
-                        KeyCode::Down => {
-                            let visible_trade_area_height = terminal.size().map_or(0, |s| if s.height > 5 {s.height - 5} else {0}) as usize;
-                            app.scroll_down(visible_trade_area_height);
-                        }
-                        KeyCode::PageUp => {
-                            for _ in 0..10 { app.scroll_up(); }
-                        }
-                        KeyCode::PageDown => {
-                            let visible_trade_area_height = terminal.size().map_or(0, |s| if s.height > 5 {s.height - 5} else {0}) as usize;
-                            for _ in 0..10 { app.scroll_down(visible_trade_area_height); }
-                        }
-                        _ => {}
-                    },
-                    InputMode::Editing => match key.code {
-                        KeyCode::Enter => {
-                            app.submit_search();
-                            app.input_mode = InputMode::Normal;
-                        }
-                        KeyCode::Char(c) => {
-                            app.enter_char(c);
-                        }
-                        KeyCode::Backspace => {
-                            app.delete_char();
-                        }
-                        KeyCode::Left => {
-                            app.move_cursor_left();
-                        }
-                        KeyCode::Right => {
-                            app.move_cursor_right();
-                        }
-                        KeyCode::Esc => {
-                            app.input_mode = InputMode::Normal;
-                        }
-                        KeyCode::Home => {
-                            app.cursor_position = 0;
-                        }
-                        KeyCode::End => {
-                            app.cursor_position = app.search_input.len();
-                        }
-                        _ => {}
-                    },
-                }
-            }
+    fn label(self) -> &'static str {
+        match self {
+            BalanceSort::BalanceDesc => "balance \u{2193}",
+            BalanceSort::BalanceAsc => "balance \u{2191}",
+            BalanceSort::Username => "user A-Z",
         }
     }
-    Ok(())
 }
 
-fn ui(f: &mut Frame, app: &mut App) {
-    let main_chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .margin(1)
-        .constraints(
-            [
-                Constraint::Length(3), 
-                Constraint::Min(0),    
-            ]
-            .as_ref(),
-        )
-        .split(f.size());
-
-    let current_search_mode_hint = match app.input_mode {
-        InputMode::Normal => "(Press 'e' or '/' to edit, Enter to search)",
-        InputMode::Editing => "(ESC to cancel, Enter to search)",
-    };
-    
-    let search_title_base = if app.active_search_symbol.is_some() {
-        format!("Searching: {}", app.active_search_symbol.as_ref().unwrap())
-    } else {
-        "Search Symbol".to_string()
-    };
-    let search_title = format!("{} {} (q:quit, t:type)", search_title_base, current_search_mode_hint);
+#[derive(PartialEq)]
+enum InputMode {
+    Normal,
+    Editing,
+    /// Typing into the balances pane's username filter.
+    BalanceFilter,
+}
 
-    let input_block = Block::default().title(search_title).borders(Borders::ALL);
-    let input_paragraph = Paragraph::new(app.search_input.as_str())
-        .block(input_block)
-        .wrap(Wrap { trim: true });
-    f.render_widget(input_paragraph, main_chunks[0]);
+/// Totals over the trades received this run, for the session stats popup.
+/// History loaded from the database isn't counted.
+#[derive(Default)]
+struct SessionCounters {
+    trades: usize,
+    volume: f64,
+    buys: usize,
+    sells: usize,
+    /// Trades per normalized coin symbol.
+    coins: HashMap<String, usize>,
+    /// Lowercased usernames.
+    users: BTreeSet<String>,
+}
 
-    match app.input_mode {
-        InputMode::Editing => {
-            f.set_cursor(
-                main_chunks[0].x + app.cursor_position as u16 + 1,
-                main_chunks[0].y + 1,
-            )
-        }
-        InputMode::Normal => {
+impl SessionCounters {
+    fn record(&mut self, trade: &Trade) {
+        self.trades += 1;
+        self.volume += trade.total_value;
+        match trade.action.as_str() {
+            "BUY" => self.buys += 1,
+            "SELL" => self.sells += 1,
+            _ => {}
         }
+        *self.coins.entry(symbols::normalize(&trade.coin_symbol)).or_default() += 1;
+        self.users.insert(trade.username.to_lowercase());
     }
-    
-    let content_area = main_chunks[1];
+
+    /// The coin traded most this session, ties going to the first by name.
+    fn busiest_coin(&self) -> Option<(&String, usize)> {
+        self.coins
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(coin, &count)| (coin, count))
+    }
+}
+
+struct App {
+    search_input: String,
+    active_search_symbol: Option<String>,
+    /// The display buffer: the newest trades, newest first, at most
+    /// `display_capacity` of them unless older ones were paged in from the
+    /// database. The database has the full history. Balances and coin stats
+    /// are built from that history at startup and then updated per trade,
+    /// so trimming the buffer never changes them.
+    all_trades: Vec<Trade>,
+    display_capacity: usize,
+    /// Set once the buffer reaches the oldest trade in the database.
+    history_exhausted: bool,
+    /// Largest row id saved before this session started.
+    session_start_row: i64,
+    user_balances: HashMap<String, HashMap<String, f64>>,
+    /// Cap on (user, coin) pairs in `user_balances`; unbounded when `None`.
+    max_balance_entries: Option<usize>,
+    /// (user, coin) pairs in `user_balances`.
+    balance_entries: usize,
+    /// Raised above `max_balance_entries` when pruning couldn't get under
+    /// it, so it doesn't run again on every trade.
+    balance_prune_at: usize,
+    scroll_offset: usize,
+    /// Whether new trades scroll in while the view is at the top.
+    follow_new_trades: bool,
+    /// Most rows a second a following view moves to reach new trades;
+    /// `None` jumps straight to them.
+    auto_scroll_speed: Option<f64>,
+    catch_up: CatchUp,
+    trade_type_filter: Option<String>,
+    /// Every `trade_type_val` seen, loaded or live, for the type filter.
+    trade_types: BTreeSet<String>,
+    action_filter: Option<String>,
+    min_value_filter: Option<f64>,
+    user_filter: Option<String>,
+    input_mode: InputMode,
+    cursor_position: usize,
+    columns: Vec<Column>,
+    /// Whether to draw colors at all: off for `--no-color` and themes
+    /// without color.
+    use_color: bool,
+    /// `--no-color`, which wins over any theme.
+    no_color: bool,
+    theme_name: ThemeName,
+    theme: Theme,
+    /// Configured scrollbar overrides, reapplied on every theme switch.
+    scrollbar: config::Scrollbar,
+    /// `--config`, so settings changed at runtime are saved to the same file.
+    config_flag: Option<String>,
+    action_glyphs: ActionGlyphs,
+    /// (unix seconds, price) points for the searched coin, oldest first.
+    price_history: Vec<(i64, f64)>,
+    coin_stats: HashMap<String, CoinStats>,
+    pinned: Vec<String>,
+    /// Alert price levels per uppercased coin, drawn on its chart.
+    price_alerts: HashMap<String, Vec<f64>>,
+    /// Trades averaged for the price chart's moving average; 0 hides it.
+    moving_average_period: usize,
+    /// Coins searched lately, most recent first.
+    recent_coins: Vec<String>,
+    show_recent_coins: bool,
+    /// Lowercased usernames hidden from the tape.
+    blocked_users: BTreeSet<String>,
+    /// Uppercased coin symbols hidden from the tape.
+    blocked_coins: BTreeSet<String>,
+    /// Notes attached to trades, by trade row id.
+    notes: HashMap<i64, String>,
+    /// The row id of the trade being annotated and the note typed so far,
+    /// while the note prompt is open.
+    note_input: Option<(i64, String)>,
+    /// Noted trades, newest first, as of when the bookmarks list was opened.
+    bookmarks: Vec<Trade>,
+    /// Highlighted entry while the bookmarks list is open.
+    bookmarks_selected: Option<usize>,
+    /// Index into `blocklist_entries()` while the blocklist popup is open.
+    blocklist_selected: Option<usize>,
+    show_stats: bool,
+    started_at: Instant,
+    auto_fit_columns: bool,
+    last_submitted_search: String,
+    escape_reverts_search: bool,
+    watchlist_only: bool,
+    search_mode: SearchMode,
+    /// Best fuzzy matches for the search among known coins, best first.
+    fuzzy_matches: Vec<String>,
+    /// Which of the coins matched by the search the side panel shows.
+    focused_coin_index: usize,
+    flash_new_trades: bool,
+    /// `flash_new_trades` as configured, restored when a preset is cleared.
+    flash_configured: bool,
+    /// Fades tape rows by age when set.
+    dim_old_trades: Option<TradeDecay>,
+    /// Latest notice for the footer (errors, confirmations).
+    status_message: Option<String>,
+    /// Set once a DB write has failed, so the warning isn't repeated per trade.
+    db_write_failed: bool,
+    db_in_memory: bool,
+    /// Shared with the feed thread, for surfacing its connection state.
+    feed_metrics: Arc<Metrics>,
+    /// Index into the visible trades.
+    selected_trade: Option<usize>,
+    show_trade_details: bool,
+    view_mode: ViewMode,
+    /// The two coins in `ViewMode::Compare`.
+    compare: Option<[ComparedCoin; 2]>,
+    /// Index into `coins_for_view()` in the coins view.
+    selected_coin: usize,
+    /// Hide coins whose net flow is balanced in the coins view.
+    directional_only: bool,
+    /// What has been typed into the delete-all-trades prompt, while it's open.
+    delete_confirmation: Option<String>,
+    palette: Option<Palette>,
+    sampler: Option<Sampler>,
+    paused: Option<Pause>,
+    auto_pause: Option<AutoPause>,
+    feed_rate: RateMeter,
+    /// Resumed by hand mid-burst: don't auto-pause again until it calms.
+    burst_dismissed: bool,
+    /// USD floors of the value tiers, lowest first.
+    value_tiers: [f64; 4],
+    amount_tiers: [f64; 4],
+    color_mode: ColorMode,
+    show_legend: bool,
+    balance_sort: BalanceSort,
+    /// Case-insensitive username substring for the balances pane.
+    balance_filter: String,
+    bell_alert: Option<BellAlert>,
+    /// Toggled at runtime; only matters when `bell_alert` is configured.
+    bell_muted: bool,
+    last_bell: Option<Instant>,
+    volume_watcher: Option<VolumeWatcher>,
+    /// When an alert last moved the view, and the value behind it.
+    last_alert_follow: Option<(Instant, f64)>,
+    /// Source text and compiled form of the `?` filter expression.
+    filter_expr: Option<(String, filter_expr::Expr)>,
+    tape_sort: TapeSort,
+    presets: BTreeMap<String, Preset>,
+    /// Name of the last preset applied, until it's cleared.
+    active_preset: Option<String>,
+    keymap: Keymap,
+    show_help: bool,
+    column_formats: HashMap<Column, NumberFormat>,
+    column_decimals: HashMap<Column, usize>,
+    number_grouping: NumberGrouping,
+    /// Index into `columns` that column-level keys act on.
+    selected_column: usize,
+    /// The query text and matching trades of the last `!` search.
+    db_results: Option<(String, Vec<Trade>)>,
+    db_results_scroll: usize,
+    /// Zone times are shown in; `None` means the system's local zone.
+    display_tz: Option<Tz>,
+    /// Show times down to the millisecond.
+    show_millis: bool,
+    /// Save the next drawn frame to a file.
+    snapshot_requested: bool,
+    snapshot_format: SnapshotFormat,
+    balance_export_format: ExportFormat,
+    /// Quick override that shows times in UTC whatever `display_tz` says.
+    show_utc: bool,
+    live_search: bool,
+    activity_thresholds: ActivityThresholds,
+    min_terminal_size: MinTerminalSize,
+    /// Upper bound on table rows built per frame, whatever the screen height.
+    max_rendered_rows: usize,
+    incomplete_trades: IncompleteTrades,
+    /// Incomplete trades dropped this session.
+    incomplete_dropped: u64,
+    /// Trades discarded because the UI fell `trade_queue.capacity` behind.
+    queue_dropped: u64,
+    /// Biggest trade by USD value received this session.
+    largest_trade: Option<Trade>,
+    /// When `largest_trade` last beat an earlier record.
+    record_set_at: Option<Instant>,
+    session: SessionCounters,
+}
+
+impl App {
+    fn new(initial_trades: Vec<Trade>) -> App {
+        let trade_types = initial_trades.iter().map(|t| t.trade_type_val.clone()).collect();
+        App {
+            search_input: String::new(),
+            active_search_symbol: None,
+            all_trades: initial_trades,
+            display_capacity: DEFAULT_DISPLAY_CAPACITY,
+            history_exhausted: false,
+            session_start_row: 0,
+            user_balances: HashMap::new(),
+            max_balance_entries: None,
+            balance_entries: 0,
+            balance_prune_at: 0,
+            scroll_offset: 0,
+            follow_new_trades: true,
+            auto_scroll_speed: None,
+            catch_up: CatchUp::default(),
+            trade_type_filter: None,
+            trade_types,
+            action_filter: None,
+            min_value_filter: None,
+            user_filter: None,
+            input_mode: InputMode::Normal,
+            cursor_position: 0,
+            columns: Column::DEFAULT.to_vec(),
+            use_color: true,
+            no_color: false,
+            theme_name: ThemeName::Default,
+            theme: ThemeName::Default.theme(),
+            scrollbar: config::Scrollbar::default(),
+            config_flag: None,
+            action_glyphs: ActionGlyphs::default(),
+            price_history: Vec::new(),
+            coin_stats: HashMap::new(),
+            pinned: Vec::new(),
+            price_alerts: HashMap::new(),
+            moving_average_period: DEFAULT_MOVING_AVERAGE_PERIOD,
+            recent_coins: Vec::new(),
+            show_recent_coins: false,
+            blocked_users: BTreeSet::new(),
+            blocked_coins: BTreeSet::new(),
+            notes: HashMap::new(),
+            note_input: None,
+            bookmarks: Vec::new(),
+            bookmarks_selected: None,
+            blocklist_selected: None,
+            show_stats: false,
+            started_at: Instant::now(),
+            auto_fit_columns: false,
+            last_submitted_search: String::new(),
+            escape_reverts_search: false,
+            watchlist_only: false,
+            search_mode: SearchMode::Exact,
+            fuzzy_matches: Vec::new(),
+            focused_coin_index: 0,
+            flash_new_trades: true,
+            flash_configured: true,
+            dim_old_trades: None,
+            status_message: None,
+            db_write_failed: false,
+            db_in_memory: false,
+            feed_metrics: Arc::new(Metrics::new()),
+            selected_trade: None,
+            show_trade_details: false,
+            view_mode: ViewMode::Tape,
+            compare: None,
+            selected_coin: 0,
+            directional_only: false,
+            delete_confirmation: None,
+            palette: None,
+            sampler: None,
+            paused: None,
+            auto_pause: None,
+            feed_rate: RateMeter::default(),
+            burst_dismissed: false,
+            value_tiers: DEFAULT_VALUE_TIERS,
+            amount_tiers: DEFAULT_AMOUNT_TIERS,
+            color_mode: ColorMode::Value,
+            show_legend: false,
+            balance_sort: BalanceSort::BalanceDesc,
+            balance_filter: String::new(),
+            bell_alert: None,
+            bell_muted: false,
+            last_bell: None,
+            volume_watcher: None,
+            last_alert_follow: None,
+            filter_expr: None,
+            tape_sort: TapeSort::Newest,
+            presets: builtin_presets(),
+            active_preset: None,
+            keymap: Keymap::default(),
+            show_help: false,
+            column_formats: HashMap::new(),
+            column_decimals: HashMap::new(),
+            number_grouping: NumberGrouping::None,
+            selected_column: 0,
+            db_results: None,
+            db_results_scroll: 0,
+            display_tz: None,
+            show_utc: false,
+            show_millis: false,
+            snapshot_requested: false,
+            snapshot_format: SnapshotFormat::Plain,
+            balance_export_format: ExportFormat::Csv,
+            live_search: false,
+            activity_thresholds: ActivityThresholds::default(),
+            min_terminal_size: MinTerminalSize::default(),
+            max_rendered_rows: DEFAULT_MAX_RENDERED_ROWS,
+            incomplete_trades: IncompleteTrades::Tag,
+            incomplete_dropped: 0,
+            queue_dropped: 0,
+            largest_trade: None,
+            session: SessionCounters::default(),
+            record_set_at: None,
+        }
+    }
+
+    /// Records a live trade. Returns whether it made it onto the tape, which
+    /// view sampling or the incomplete-trade policy can prevent.
+    fn add_trade(&mut self, mut trade: Trade, store: &dyn TradeStore) -> bool {
+        let incomplete = trade.is_incomplete();
+        if incomplete {
+            self.feed_metrics.record_incomplete();
+            if self.incomplete_trades == IncompleteTrades::Drop {
+                self.incomplete_dropped += 1;
+                return false;
+            }
+        }
+
+        trade.received_at = Some(Instant::now());
+        if !self.trade_types.contains(&trade.trade_type_val) {
+            self.trade_types.insert(trade.trade_type_val.clone());
+        }
+        match store.insert(&trade) {
+            Ok(row_id) => trade.row_id = Some(row_id),
+            Err(e) => self.report_db_write_error(&e),
+        }
+        if trade.counts_towards_stats(self.incomplete_trades) {
+            match &self.largest_trade {
+                Some(record) if record.total_value >= trade.total_value => {}
+                previous => {
+                    if previous.is_some() {
+                        self.record_set_at = trade.received_at;
+                    }
+                    self.largest_trade = Some(trade.clone());
+                }
+            }
+            self.check_price_alerts(&trade);
+            self.session.record(&trade);
+            self.record_trade_stats(&trade, store);
+        }
+
+        let shown = self.sampler.as_mut().is_none_or(|sampler| sampler.admit(Instant::now()));
+        if shown {
+            self.all_trades.insert(0, trade);
+            self.trim_display_buffer();
+        }
+        shown
+    }
+
+    /// Drops the oldest trades past `display_capacity`, except while scrolled
+    /// down, when the page around the view is kept so paged-in history
+    /// stays put while it's being read.
+    fn trim_display_buffer(&mut self) {
+        let reading = self.scroll_offset.max(self.selected_trade.unwrap_or(0));
+        let keep = if reading == 0 {
+            self.display_capacity
+        } else {
+            self.display_capacity.max(reading + HISTORY_PAGE_SIZE)
+        };
+        if self.all_trades.len() > keep {
+            self.all_trades.truncate(keep);
+            self.history_exhausted = false;
+        }
+    }
+
+    /// Pages older trades in from the database until at least `rows` trades
+    /// pass the filters or the history runs out. Gives up after a few pages
+    /// so a filter matching almost nothing doesn't stall a keypress.
+    fn load_history(&mut self, rows: usize, store: &dyn TradeStore) {
+        for _ in 0..HISTORY_PAGES_PER_LOAD {
+            let matching = self.all_trades.iter().filter(|t| self.trade_matches_filters(t)).count();
+            if self.history_exhausted || matching >= rows {
+                return;
+            }
+            let before = self.all_trades.iter().rev().find_map(|t| t.row_id).unwrap_or(i64::MAX);
+            match store.load_page(before, HISTORY_PAGE_SIZE) {
+                Ok(older) => {
+                    self.history_exhausted = older.len() < HISTORY_PAGE_SIZE;
+                    self.all_trades.extend(older);
+                }
+                Err(e) => {
+                    self.status_message = Some(format!("Couldn't load older trades: {}", e));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Reports alert levels on the trade's coin that its price just crossed,
+    /// going either way. Call before the trade updates the coin's stats.
+    fn check_price_alerts(&mut self, trade: &Trade) {
+        let coin = symbols::normalize(&trade.coin_symbol);
+        let (Some(levels), Some(stats)) = (self.price_alerts.get(&coin), self.coin_stats.get(&coin)) else {
+            return;
+        };
+        let previous = stats.last_price;
+        let crossed: Vec<String> = levels
+            .iter()
+            .filter(|&&level| (previous < level) != (trade.price < level))
+            .map(|&level| format_price(level))
+            .collect();
+        if crossed.is_empty() {
+            return;
+        }
+        let direction = if trade.price > previous { "up" } else { "down" };
+        self.status_message = Some(format!(
+            "{} crossed {} {} (now {})",
+            coin,
+            direction,
+            crossed.join(", "),
+            format_price(trade.price)
+        ));
+        if !self.bell_muted {
+            ring_bell();
+        }
+    }
+
+    /// Adds an alert line at the focused coin's last price.
+    fn set_price_alert(&mut self, conn: &Connection) {
+        let Some(symbol) = self.focused_coin() else {
+            self.status_message = Some("Search a coin to set a price alert".to_string());
+            return;
+        };
+        let Some(price) = self.coin_stats.get(&symbol).map(|stats| stats.last_price).filter(|p| *p > 0.0) else {
+            self.status_message = Some(format!("No price for {} yet", symbol));
+            return;
+        };
+        let levels = self.price_alerts.entry(symbol.clone()).or_default();
+        if !levels.contains(&price) {
+            levels.push(price);
+            levels.sort_by(f64::total_cmp);
+        }
+        self.status_message = Some(match save_price_alerts_db(conn, &symbol, levels) {
+            Ok(()) => format!("Alert set for {} at {}", symbol, format_price(price)),
+            Err(e) => format!("Failed to save price alert: {}", describe_db_error(&e)),
+        });
+    }
+
+    fn clear_price_alerts(&mut self, conn: &Connection) {
+        let Some(symbol) = self.focused_coin() else {
+            return;
+        };
+        if self.price_alerts.remove(&symbol).is_none() {
+            return;
+        }
+        self.status_message = Some(match save_price_alerts_db(conn, &symbol, &[]) {
+            Ok(()) => format!("Cleared price alerts for {}", symbol),
+            Err(e) => format!("Failed to clear price alerts: {}", describe_db_error(&e)),
+        });
+    }
+
+    /// Feeds a trade into prices, coin stats, alerts and balances.
+    fn record_trade_stats(&mut self, trade: &Trade, store: &dyn TradeStore) {
+        let now = Local::now().timestamp();
+        if let Err(e) = store.insert_price_point(&trade.coin_symbol, now, trade.price) {
+            self.report_db_write_error(&e);
+        }
+        let coin = symbols::normalize(&trade.coin_symbol);
+        let new_coin = !self.coin_stats.contains_key(&coin);
+        self.coin_stats.entry(coin).or_default().record(trade, Instant::now());
+        if new_coin && self.search_mode == SearchMode::Fuzzy {
+            self.refresh_fuzzy_matches();
+        }
+        if let Some(watcher) = &mut self.volume_watcher {
+            watcher.record(&trade.coin_symbol, trade.total_value, Instant::now());
+        }
+
+        if self.focused_coin().as_deref() == Some(symbols::normalize(&trade.coin_symbol).as_str()) {
+            self.price_history.push((now, trade.price));
+            if self.price_history.len() > PRICE_HISTORY_LOAD_LIMIT {
+                self.price_history.remove(0);
+            }
+        }
+        for compared in self.compare.iter_mut().flatten() {
+            if compared.symbol == symbols::normalize(&trade.coin_symbol) {
+                compared.price_history.push((now, trade.price));
+                if compared.price_history.len() > PRICE_HISTORY_LOAD_LIMIT {
+                    compared.price_history.remove(0);
+                }
+            }
+        }
+
+        self.apply_to_balance(trade);
+        if self.max_balance_entries.is_some_and(|max| self.balance_entries > max.max(self.balance_prune_at)) {
+            self.prune_balances();
+        }
+    }
+
+    fn apply_to_balance(&mut self, trade: &Trade) {
+        let coin = symbols::normalize(&trade.coin_symbol);
+        let user_coin_balances = self
+            .user_balances
+            .entry(trade.username.clone())
+            .or_default();
+        let balance = user_coin_balances.entry(coin).or_insert_with(|| {
+            self.balance_entries += 1;
+            0.0
+        });
+
+        if trade.action == "BUY" {
+            *balance += trade.amount;
+        } else if trade.action == "SELL" {
+            *balance -= trade.amount;
+        }
+    }
+
+    /// Drops zero balances, which read the same as untracked ones, so a
+    /// pair that trades again still starts from the right amount. Non-zero
+    /// balances are always kept; when they alone exceed
+    /// `max_balance_entries`, the next prune waits for another 10% of it.
+    fn prune_balances(&mut self) {
+        let Some(max) = self.max_balance_entries else {
+            return;
+        };
+        for coins in self.user_balances.values_mut() {
+            coins.retain(|_, balance| balance.abs() > BALANCE_DUST);
+        }
+        self.user_balances.retain(|_, coins| !coins.is_empty());
+        self.balance_entries = self.user_balances.values().map(HashMap::len).sum();
+        self.balance_prune_at = max.max(self.balance_entries + max / 10);
+    }
+
+    /// Whether a trade worth `value` should ring the bell now. Rings are
+    /// spaced at least `min_interval_secs` apart.
+    fn should_ring_bell(&mut self, value: f64, now: Instant) -> bool {
+        let Some(alert) = self.bell_alert else {
+            return false;
+        };
+        if self.bell_muted || value < alert.min_value {
+            return false;
+        }
+        let min_interval = Duration::from_secs(alert.min_interval_secs);
+        if self.last_bell.is_some_and(|last| now.duration_since(last) < min_interval) {
+            return false;
+        }
+        self.last_bell = Some(now);
+        true
+    }
+
+    fn format_unix_time(&self, secs: i64, format: &str) -> String {
+        self.format_unix_millis(secs.saturating_mul(1000), format)
+    }
+
+    fn format_unix_millis(&self, millis: i64, format: &str) -> String {
+        let Some(time) = Utc.timestamp_millis_opt(millis).single() else {
+            return "-".to_string();
+        };
+        match self.display_tz {
+            _ if self.show_utc => time.format(format).to_string(),
+            Some(tz) => time.with_timezone(&tz).format(format).to_string(),
+            None => time.with_timezone(&Local).format(format).to_string(),
+        }
+    }
+
+    /// Short name of the zone times are currently shown in.
+    fn time_zone_label(&self) -> String {
+        match self.display_tz {
+            _ if self.show_utc => "UTC".to_string(),
+            Some(tz) => Utc::now().with_timezone(&tz).format("%Z").to_string(),
+            None => "Local".to_string(),
+        }
+    }
+
+    /// Column header, with the active zone on the Time column.
+    fn column_header(&self, column: Column) -> String {
+        match column {
+            Column::Time => format!("{} {}", column.header(), self.time_zone_label()),
+            _ => column.header().to_string(),
+        }
+    }
+
+    fn toggle_utc(&mut self) {
+        self.show_utc = !self.show_utc;
+        self.status_message = Some(format!("Showing times in {}", self.time_zone_label()));
+    }
+
+    /// Time of day in the display zone, or the stored `timestamp` for trades
+    /// recorded before dates were kept. Milliseconds come from `timestamp`,
+    /// so trades saved before they were kept show whole seconds.
+    fn display_time(&self, recorded_at: Option<i64>, timestamp: &str) -> String {
+        match (recorded_at, timestamp_millis(timestamp)) {
+            (Some(secs), Some(millis)) if self.show_millis => {
+                self.format_unix_millis(secs.saturating_mul(1000) + i64::from(millis), "%H:%M:%S%.3f")
+            }
+            (Some(secs), _) => self.format_unix_time(secs, "%H:%M:%S"),
+            (None, _) if self.show_millis => timestamp.to_string(),
+            (None, _) => timestamp.split('.').next().unwrap_or_default().to_string(),
+        }
+    }
+
+    fn toggle_pause(&mut self) {
+        if self.paused.is_some() {
+            self.resume();
+        } else {
+            self.paused = Some(Pause { burst_rate: None, held: 0 });
+        }
+    }
+
+    /// Unpauses and goes back to the newest trade.
+    fn resume(&mut self) {
+        self.paused = None;
+        self.burst_dismissed = true;
+        self.reset_view();
+    }
+
+    /// Samples the feed rate and pauses on a burst past `auto_pause`, or
+    /// resumes once it's calm again when configured to.
+    fn check_burst(&mut self, now: Instant) {
+        let rate = self.feed_rate.sample(now, self.feed_metrics.trades_received());
+        let Some(auto_pause) = self.auto_pause else {
+            return;
+        };
+        if rate <= auto_pause.trades_per_sec {
+            if auto_pause.resume_when_calm && self.paused.as_ref().is_some_and(|pause| pause.burst_rate.is_some()) {
+                self.resume();
+            }
+            self.burst_dismissed = false;
+        } else if self.paused.is_none() && !self.burst_dismissed {
+            self.paused = Some(Pause { burst_rate: Some(rate), held: 0 });
+        }
+    }
+
+    /// Rows a following view has yet to scroll to reach the newest trade.
+    fn catch_up_rows(&self) -> usize {
+        if self.scroll_offset == self.catch_up.offset { self.catch_up.rows } else { 0 }
+    }
+
+    /// Scrolls a lagging view towards the newest trade at no more than
+    /// `auto_scroll_speed`, faster only when it's `CATCH_UP_MAX_LAG` behind.
+    fn step_catch_up(&mut self, now: Instant) {
+        let rows = self.catch_up_rows();
+        let elapsed = self.catch_up.last_step.map_or(Duration::ZERO, |last| now.duration_since(last));
+        self.catch_up.last_step = Some(now);
+        let Some(speed) = self.auto_scroll_speed.filter(|_| rows > 0) else {
+            self.catch_up.rows = 0;
+            self.catch_up.credit = 0.0;
+            return;
+        };
+        self.catch_up.credit += elapsed.as_secs_f64() * speed;
+        let step = (self.catch_up.credit as usize).max(rows.saturating_sub(CATCH_UP_MAX_LAG)).min(rows);
+        self.catch_up.credit = (self.catch_up.credit - step as f64).max(0.0);
+        self.scroll_offset = self.scroll_offset.saturating_sub(step);
+        self.catch_up.rows = rows - step;
+        self.catch_up.offset = self.scroll_offset;
+    }
+
+    /// Lengthens (`1`) or shortens (`-1`) the chart's moving average by
+    /// `MOVING_AVERAGE_STEP` trades. Shortening to 0 hides it.
+    fn step_moving_average(&mut self, direction: isize) {
+        let period = self.moving_average_period as isize + direction * MOVING_AVERAGE_STEP as isize;
+        self.moving_average_period = period.clamp(0, PRICE_HISTORY_LOAD_LIMIT as isize) as usize;
+        self.status_message = Some(match self.moving_average_period {
+            0 => "Moving average hidden".to_string(),
+            period => format!("Moving average over {} trades", period),
+        });
+    }
+
+    fn toggle_millis(&mut self) {
+        self.show_millis = !self.show_millis;
+        self.status_message = Some(if self.show_millis { "Showing milliseconds" } else { "Hiding milliseconds" }.to_string());
+    }
+
+    /// A column's width under the current layout settings. Number columns
+    /// grow by one for each configured decimal past their default.
+    fn column_width(&self, column: Column) -> Constraint {
+        let extra = self.column_decimals.get(&column).map_or(0, |&decimals| decimals.saturating_sub(column.default_decimals())) as u16;
+        match column {
+            Column::Time if self.show_millis && self.auto_fit_columns => Constraint::Min(TIME_MILLIS_WIDTH),
+            Column::Time if self.show_millis => Constraint::Length(TIME_MILLIS_WIDTH),
+            _ if self.auto_fit_columns => match column.fit_width() {
+                Constraint::Min(width) => Constraint::Min(width + extra),
+                fill => fill,
+            },
+            _ => Constraint::Length(column.base_width() + extra),
+        }
+    }
+
+    /// The width each of `columns` gets in a bordered table filling `area`,
+    /// laid out the way `Table` does it.
+    fn rendered_column_widths(&self, columns: &[Column], area: Rect) -> Vec<u16> {
+        let inner = Block::default().borders(Borders::ALL).inner(area);
+        Layout::horizontal(columns.iter().map(|&c| self.column_width(c)))
+            .spacing(1)
+            .split(inner)
+            .iter()
+            .map(|rect| rect.width)
+            .collect()
+    }
+
+    fn move_selected_column(&mut self, delta: isize) {
+        let last = self.columns.len().saturating_sub(1) as isize;
+        self.selected_column = (self.selected_column as isize + delta).clamp(0, last) as usize;
+    }
+
+    fn cycle_column_format(&mut self) {
+        let Some(&column) = self.columns.get(self.selected_column) else {
+            return;
+        };
+        if !column.is_numeric() {
+            self.status_message = Some(format!("{} has no number format; select Amount, Total, Price or Implied", column.header()));
+            return;
+        }
+        let format = self.column_formats.entry(column).or_default();
+        *format = match format {
+            NumberFormat::Full => NumberFormat::Compact,
+            NumberFormat::Compact => NumberFormat::Full,
+        };
+        let label = format!("{}: {}", column.header(), if *format == NumberFormat::Compact { "compact" } else { "full" });
+        let saved: serde_json::Map<String, serde_json::Value> = self
+            .column_formats
+            .iter()
+            .map(|(column, format)| (column.id().to_string(), serde_json::json!(format)))
+            .collect();
+        self.status_message = match config::save_setting(self.config_flag.as_deref(), "column_formats", saved.into()) {
+            Ok(()) => Some(label),
+            Err(e) => Some(format!("{} (not saved: {})", label, e)),
+        };
+    }
+
+    /// Reports coins whose volume just spiked, following the biggest one if
+    /// the alert asks for that. Returns whether anything fired.
+    fn check_volume_spikes(&mut self, conn: &Connection) -> bool {
+        let Some(watcher) = &mut self.volume_watcher else {
+            return false;
+        };
+        let spikes = watcher.check(Instant::now());
+        let Some((coin, volume)) = spikes.first() else {
+            return false;
+        };
+        let settings = watcher.settings();
+        let others = match spikes.len() {
+            1 => String::new(),
+            n => format!(" (+{} more)", n - 1),
+        };
+        self.status_message = Some(format!(
+            "Volume spike: {} traded ${} in {}s{}",
+            coin,
+            format_compact(*volume),
+            settings.window_secs,
+            others
+        ));
+        if settings.auto_search {
+            let (coin, volume) = (coin.clone(), *volume);
+            self.follow_alert(&coin, volume, conn);
+        }
+        true
+    }
+
+    /// Command line flags that reopen the app with the current search and
+    /// filters.
+    fn cli_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(symbol) = &self.active_search_symbol {
+            args.extend(["--symbol".to_string(), symbol.clone()]);
+            if self.search_mode != SearchMode::Exact {
+                args.extend(["--search-mode".to_string(), self.search_mode.flag_value().to_string()]);
+            }
+        }
+        if let Some(filter) = &self.trade_type_filter {
+            args.extend(["--filter".to_string(), filter.clone()]);
+        }
+        if let Some(action) = &self.action_filter {
+            args.extend(["--action".to_string(), action.to_lowercase()]);
+        }
+        if let Some(min_value) = self.min_value_filter {
+            args.extend(["--min-value".to_string(), min_value.to_string()]);
+        }
+        if let Some(user) = &self.user_filter {
+            args.extend(["--user".to_string(), user.clone()]);
+        }
+        if let Some((source, _)) = &self.filter_expr {
+            args.extend(["--expr".to_string(), source.clone()]);
+        }
+        args
+    }
+
+    fn copy_cli_args(&mut self) {
+        let command = std::iter::once("trademaxxing".to_string())
+            .chain(self.cli_args().iter().map(|arg| shell_quote(arg)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.status_message = Some(match clipboard::copy(&command) {
+            Ok(()) => format!("Copied: {}", command),
+            Err(e) => format!("Couldn't copy ({}): {}", e, command),
+        });
+    }
+
+    /// Runs a palette command that takes an argument.
+    fn run_palette_command(&mut self, command: palette::Command, argument: &str, conn: &Connection) {
+        match command {
+            palette::Command::Search => {
+                self.search_input = argument.to_string();
+                self.move_cursor_end();
+                self.submit(conn);
+            }
+            palette::Command::Filter => {
+                self.action_filter = match argument.to_lowercase().as_str() {
+                    "buy" | "buys" => Some("BUY".to_string()),
+                    "sell" | "sells" => Some("SELL".to_string()),
+                    "all" | "" => None,
+                    other => {
+                        self.status_message = Some(format!("Unknown filter: {} (expected buy, sell or all)", other));
+                        return;
+                    }
+                };
+                self.reset_view();
+            }
+            palette::Command::Min => {
+                if argument.is_empty() {
+                    self.min_value_filter = None;
+                } else if let Ok(value) = argument.trim_start_matches('$').parse::<f64>() {
+                    self.min_value_filter = Some(value);
+                } else {
+                    self.status_message = Some(format!("Not a USD value: {}", argument));
+                    return;
+                }
+                self.reset_view();
+            }
+            palette::Command::Sort => match BalanceSort::parse(argument) {
+                Some(sort) => self.balance_sort = sort,
+                None => {
+                    self.status_message = Some(format!("Unknown sort: {} (expected balance, asc or user)", argument))
+                }
+            },
+            palette::Command::Preset => match argument {
+                "off" | "none" => self.clear_preset(),
+                name => self.apply_preset(name),
+            },
+            palette::Command::Compare => self.start_compare(argument, conn),
+            palette::Command::Run(_) => {}
+        }
+    }
+
+    fn toggle_bell(&mut self) {
+        if self.bell_alert.is_none() {
+            self.status_message = Some("No bell_alert configured".to_string());
+            return;
+        }
+        self.bell_muted = !self.bell_muted;
+        self.status_message = Some(if self.bell_muted { "Bell muted" } else { "Bell on" }.to_string());
+    }
+
+    /// The key to show for `action` in hints, so they follow the keymap.
+    /// The shortest wins, so `remove_entry` reads `d` rather than
+    /// `backspace`.
+    fn key_hint(&self, action: Action) -> String {
+        self.keymap.keys_for(action).into_iter().min_by_key(String::len).unwrap_or_else(|| "(unbound)".to_string())
+    }
+
+    /// Status for actions that need a selected trade.
+    fn select_trade_first(&self) -> String {
+        format!("Select a trade first ({}/{})", self.key_hint(Action::SelectDown), self.key_hint(Action::SelectUp))
+    }
+
+    /// Adds a trade off the feed, keeping the view and selection where the
+    /// user left them and firing any alerts it triggers.
+    fn receive_trade(&mut self, trade: Trade, conn: &Connection) {
+        let following = self.scroll_offset == self.catch_up_rows() && self.follow_new_trades && self.paused.is_none();
+        let trade_value = trade.total_value;
+        let coin_symbol = symbols::normalize(&trade.coin_symbol);
+        // A dropped or tagged incomplete trade's value can be garbage.
+        let alertable = trade.counts_towards_stats(self.incomplete_trades);
+
+        // Sorted by size there's no telling where it lands, so don't
+        // try to keep the view still.
+        let shown = self.add_trade(trade, conn) && self.tape_sort == TapeSort::Newest;
+        if alertable && self.should_ring_bell(trade_value, Instant::now()) {
+            ring_bell();
+        }
+
+        if shown && !following && self.input_mode == InputMode::Normal {
+            self.scroll_offset += 1;
+        }
+        if shown && let Some(pause) = &mut self.paused {
+            pause.held += 1;
+        } else if shown && following && self.auto_scroll_speed.is_some() {
+            // Hold still for now; `step_catch_up` brings it into view.
+            self.catch_up.rows = self.catch_up_rows() + 1;
+            self.scroll_offset += 1;
+            self.catch_up.offset = self.scroll_offset;
+        }
+        // Keep the same trade selected as the list shifts down.
+        if shown
+            && let Some(selected) = self.selected_trade
+            && self.trade_matches_filters(&self.all_trades[0])
+        {
+            self.selected_trade = Some(selected + 1);
+        }
+        if alertable && self.bell_alert.is_some_and(|alert| alert.auto_search && trade_value >= alert.min_value) {
+            self.follow_alert(&coin_symbol, trade_value, conn);
+        }
+    }
+
+    /// Surfaces the first failed write in the footer; later failures are
+    /// almost always the same problem, so they stay quiet.
+    fn report_db_write_error(&mut self, error: &str) {
+        if !self.db_write_failed {
+            self.db_write_failed = true;
+            self.status_message = Some(format!("Trades are no longer being saved: {}", error));
+        }
+    }
+
+    fn refresh_price_history(&mut self, conn: &Connection) {
+        self.price_history = match self.focused_coin() {
+            Some(symbol) => load_price_history_db(conn, &symbol).unwrap_or_default(),
+            None => Vec::new(),
+        };
+    }
+
+    fn symbol_matches(&self, coin_symbol: &str) -> bool {
+        let Some(symbol) = &self.active_search_symbol else {
+            return true;
+        };
+        let coin_symbol = symbols::normalize(coin_symbol);
+        match self.search_mode {
+            SearchMode::Exact => coin_symbol == *symbol,
+            SearchMode::Substring => coin_symbol.contains(symbol.as_str()),
+            SearchMode::Fuzzy => self.fuzzy_matches.contains(&coin_symbol),
+        }
+    }
+
+    /// Ranks the coins seen so far against the search. Only runs when the
+    /// search, the mode or the set of coins changes.
+    fn refresh_fuzzy_matches(&mut self) {
+        self.fuzzy_matches.clear();
+        let Some(symbol) = &self.active_search_symbol else {
+            return;
+        };
+        if self.search_mode != SearchMode::Fuzzy {
+            return;
+        }
+        let mut scored: Vec<(i64, &String)> = self
+            .coin_stats
+            .keys()
+            .filter_map(|coin| fuzzy::symbol_score(symbol, coin).map(|score| (score, coin)))
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        self.fuzzy_matches = scored.into_iter().take(FUZZY_MAX_MATCHES).map(|(_, coin)| coin.clone()).collect();
+    }
+
+    /// Known coins matching the search, alphabetically (best first when
+    /// fuzzy).
+    fn matched_coins(&self) -> Vec<String> {
+        if self.active_search_symbol.is_none() {
+            return Vec::new();
+        }
+        if self.search_mode == SearchMode::Fuzzy {
+            return self.fuzzy_matches.clone();
+        }
+        let mut coins: Vec<String> = self
+            .coin_stats
+            .keys()
+            .filter(|coin| self.symbol_matches(coin))
+            .cloned()
+            .collect();
+        coins.sort();
+        coins
+    }
+
+    /// Trades arriving while a coin is on screen don't count as missed.
+    fn mark_focused_coin_viewed(&mut self) {
+        if self.view_mode != ViewMode::Tape {
+            return;
+        }
+        if let Some(stats) = self.focused_coin().and_then(|symbol| self.coin_stats.get_mut(&symbol)) {
+            stats.mark_viewed();
+        }
+    }
+
+    /// The coin the side panel (balances, chart, pinning) is about.
+    fn focused_coin(&self) -> Option<String> {
+        let symbol = self.active_search_symbol.as_ref()?;
+        let matched = self.matched_coins();
+        if matched.is_empty() {
+            return Some(symbol.clone());
+        }
+        Some(matched[self.focused_coin_index % matched.len()].clone())
+    }
+
+    fn cycle_focused_coin(&mut self, forward: bool, conn: &Connection) {
+        let matched_count = self.matched_coins().len();
+        if matched_count < 2 {
+            return;
+        }
+        let index = self.focused_coin_index % matched_count;
+        self.focused_coin_index = if forward {
+            (index + 1) % matched_count
+        } else {
+            (index + matched_count - 1) % matched_count
+        };
+        self.refresh_price_history(conn);
+    }
+
+    fn toggle_search_mode(&mut self, conn: &Connection) {
+        self.search_mode = match self.search_mode {
+            SearchMode::Exact => SearchMode::Substring,
+            SearchMode::Substring => SearchMode::Fuzzy,
+            SearchMode::Fuzzy => SearchMode::Exact,
+        };
+        self.refresh_fuzzy_matches();
+        self.focused_coin_index = 0;
+        self.reset_view();
+        self.refresh_price_history(conn);
+    }
+
+    /// Seeds prices from the loaded trades so the session starts at the last
+    /// known price; recent volume only counts live trades.
+    fn seed_coin_stats(&mut self) {
+        let policy = self.incomplete_trades;
+        for trade in self.all_trades.iter().rev().filter(|t| t.counts_towards_stats(policy)) {
+            self.coin_stats
+                .entry(symbols::normalize(&trade.coin_symbol))
+                .or_default()
+                .accumulate(trade);
+        }
+        for stats in self.coin_stats.values_mut() {
+            stats.session_start_price = Some(stats.last_price);
+        }
+    }
+
+    /// Pins the focused coin, or unpins it if it's already pinned.
+    fn toggle_pin(&mut self, conn: &Connection) {
+        let Some(symbol) = self.focused_coin() else {
+            return;
+        };
+        if let Some(index) = self.pinned.iter().position(|p| *p == symbol) {
+            self.pinned.remove(index);
+        } else {
+            self.pinned.push(symbol);
+        }
+        if let Err(e) = save_pinned_db(conn, &self.pinned) {
+            self.status_message = Some(format!("Failed to save pinned coins: {}", describe_db_error(&e)));
+        }
+    }
+
+    /// Hides the selected trade's user (`coin == false`) or coin from the tape.
+    fn block_selected(&mut self, coin: bool, conn: &Connection) {
+        let Some(trade) = self.selected_trade() else {
+            self.status_message = Some(self.select_trade_first());
+            return;
+        };
+        let (set, value) = if coin {
+            (&mut self.blocked_coins, symbols::normalize(&trade.coin_symbol))
+        } else {
+            (&mut self.blocked_users, trade.username.to_lowercase())
+        };
+        set.insert(value.clone());
+        // The rows under the selection just changed.
+        self.selected_trade = None;
+        self.show_trade_details = false;
+        self.status_message = Some(match save_blocklist_db(conn, &self.blocked_users, &self.blocked_coins) {
+            Ok(()) => format!("Hid {} ({} to manage)", value, self.key_hint(Action::Blocklist)),
+            Err(e) => format!("Hid {} but failed to save: {}", value, describe_db_error(&e)),
+        });
+    }
+
+    /// Opens the note prompt for the selected trade, filled with its note.
+    fn start_note(&mut self) {
+        let Some(trade) = self.selected_trade() else {
+            self.status_message = Some(self.select_trade_first());
+            return;
+        };
+        let Some(row_id) = trade.row_id else {
+            self.status_message = Some("This trade wasn't saved, so it can't take a note".to_string());
+            return;
+        };
+        let note = self.notes.get(&row_id).cloned().unwrap_or_default();
+        self.note_input = Some((row_id, note));
+    }
+
+    /// Saves the note being typed; an empty one removes the trade's note.
+    fn save_note(&mut self, conn: &Connection) {
+        let Some((row_id, note)) = self.note_input.take() else {
+            return;
+        };
+        let note = note.trim().to_string();
+        if let Err(e) = save_note_db(conn, row_id, &note) {
+            self.status_message = Some(format!("Failed to save note: {}", describe_db_error(&e)));
+            return;
+        }
+        self.status_message = Some(if note.is_empty() { "Note removed" } else { "Note saved" }.to_string());
+        if note.is_empty() {
+            self.notes.remove(&row_id);
+        } else {
+            self.notes.insert(row_id, note);
+        }
+    }
+
+    fn open_bookmarks(&mut self, conn: &Connection) {
+        match load_bookmarked_trades_db(conn) {
+            Ok(trades) => {
+                self.bookmarks = trades;
+                self.bookmarks_selected = Some(0);
+            }
+            Err(e) => self.status_message = Some(format!("Couldn't load bookmarks: {}", describe_db_error(&e))),
+        }
+    }
+
+    /// Removes the highlighted bookmark's note.
+    fn remove_selected_bookmark(&mut self, conn: &Connection) {
+        let Some(index) = self.bookmarks_selected.filter(|&i| i < self.bookmarks.len()) else {
+            return;
+        };
+        let Some(row_id) = self.bookmarks[index].row_id else {
+            return;
+        };
+        if let Err(e) = save_note_db(conn, row_id, "") {
+            self.status_message = Some(format!("Failed to remove bookmark: {}", describe_db_error(&e)));
+            return;
+        }
+        self.notes.remove(&row_id);
+        self.bookmarks.remove(index);
+        self.bookmarks_selected = Some(index.min(self.bookmarks.len().saturating_sub(1)));
+    }
+
+    /// Closes the bookmarks list and selects the highlighted trade on the
+    /// tape, paging older history in if it has been trimmed away.
+    fn jump_to_selected_bookmark(&mut self, num_visible_items: usize, conn: &Connection) {
+        let Some(trade) = self.bookmarks_selected.take().and_then(|i| self.bookmarks.get(i)) else {
+            return;
+        };
+        let (Some(row_id), coin) = (trade.row_id, trade.coin_symbol.clone()) else {
+            return;
+        };
+        for _ in 0..HISTORY_PAGES_PER_LOAD {
+            let oldest = self.all_trades.iter().rev().find_map(|t| t.row_id).unwrap_or(i64::MAX);
+            if self.history_exhausted || oldest <= row_id {
+                break;
+            }
+            match conn.load_page(oldest, HISTORY_PAGE_SIZE) {
+                Ok(older) => {
+                    self.history_exhausted = older.len() < HISTORY_PAGE_SIZE;
+                    self.all_trades.extend(older);
+                }
+                Err(e) => {
+                    self.status_message = Some(format!("Couldn't load older trades: {}", e));
+                    return;
+                }
+            }
+        }
+        match self.get_visible_trades().iter().position(|t| t.row_id == Some(row_id)) {
+            Some(index) => self.show_trade_at(index, num_visible_items),
+            None => {
+                self.status_message = Some(format!("Bookmarked {} trade isn't in the tape; check the filters", coin));
+            }
+        }
+    }
+
+    /// Blocked users then blocked coins, as (is_coin, value).
+    fn blocklist_entries(&self) -> Vec<(bool, String)> {
+        let users = self.blocked_users.iter().map(|user| (false, user.clone()));
+        let coins = self.blocked_coins.iter().map(|coin| (true, coin.clone()));
+        users.chain(coins).collect()
+    }
+
+    fn unblock_selected(&mut self, conn: &Connection) {
+        let entries = self.blocklist_entries();
+        let Some(index) = self.blocklist_selected else {
+            return;
+        };
+        let Some((coin, value)) = entries.get(index) else {
+            return;
+        };
+        if *coin {
+            self.blocked_coins.remove(value);
+        } else {
+            self.blocked_users.remove(value);
+        }
+        self.blocklist_selected = Some(index.min(entries.len().saturating_sub(2)));
+        if let Err(e) = save_blocklist_db(conn, &self.blocked_users, &self.blocked_coins) {
+            self.status_message = Some(format!("Failed to save blocklist: {}", describe_db_error(&e)));
+        }
+    }
+
+    /// Moves the focused coin up (negative) or down (positive) the watchlist.
+    fn move_pinned(&mut self, delta: isize, conn: &Connection) {
+        let Some(symbol) = self.focused_coin() else {
+            return;
+        };
+        let Some(index) = self.pinned.iter().position(|p| *p == symbol) else {
+            return;
+        };
+        let target = index as isize + delta;
+        if target < 0 || target as usize >= self.pinned.len() {
+            return;
+        }
+        self.pinned.swap(index, target as usize);
+        if let Err(e) = save_pinned_db(conn, &self.pinned) {
+            self.status_message = Some(format!("Failed to save pinned coins: {}", describe_db_error(&e)));
+        }
+    }
+
+    fn watchlist_height(&self) -> u16 {
+        if self.pinned.is_empty() {
+            0
+        } else {
+            // Borders plus header.
+            self.pinned.len().min(WATCHLIST_MAX_ROWS) as u16 + 3
+        }
+    }
+
+    /// Backs the DB up next to the original, then empties the trades table
+    /// and everything in memory derived from it.
+    fn delete_all_trades(&mut self, conn: &Connection) {
+        // Next to the database, like the schema migration's backups.
+        let name = format!("trades-backup-{}.db", Local::now().format("%Y%m%d-%H%M%S"));
+        let backup_path = match conn.path().filter(|path| !path.is_empty()) {
+            Some(db_path) => Path::new(db_path).with_file_name(name).to_string_lossy().into_owned(),
+            None => name,
+        };
+        if let Err(e) = backup_db(conn, &backup_path) {
+            self.status_message = Some(format!("Backup failed, nothing deleted: {}", describe_db_error(&e)));
+            return;
+        }
+        match delete_all_trades_db(conn) {
+            Ok(deleted) => {
+                self.all_trades.clear();
+                self.user_balances.clear();
+                self.balance_entries = 0;
+                self.coin_stats.clear();
+                self.notes.clear();
+                self.selected_trade = None;
+                self.show_trade_details = false;
+                self.reset_view();
+                self.status_message = Some(format!("Deleted {} trades (backup: {})", deleted, backup_path));
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Failed to delete trades: {}", describe_db_error(&e)));
+            }
+        }
+    }
+
+    /// Non-zero balances of `symbol` for users matching `balance_filter`,
+    /// in `balance_sort` order.
+    fn coin_balances(&self, symbol: &str) -> Vec<(String, f64)> {
+        let user_needle = self.balance_filter.to_lowercase();
+        let mut balances: Vec<(String, f64)> = self
+            .user_balances
+            .iter()
+            .filter(|(username, _)| username.to_lowercase().contains(&user_needle))
+            .filter_map(|(username, coin_map)| {
+                coin_map.get(symbol).map(|balance| (username.clone(), *balance))
+            })
+            .filter(|(_, balance)| balance.abs() > BALANCE_DUST)
+            .collect();
+        match self.balance_sort {
+            BalanceSort::BalanceDesc => balances.sort_by(|a, b| b.1.total_cmp(&a.1)),
+            BalanceSort::BalanceAsc => balances.sort_by(|a, b| a.1.total_cmp(&b.1)),
+            BalanceSort::Username => balances.sort_by_key(|(username, _)| username.to_lowercase()),
+        }
+        balances
+    }
+
+    /// Writes the focused coin's balances, or every coin's when nothing is
+    /// focused, to a timestamped CSV or JSON file.
+    fn export_balances(&mut self) {
+        let focused = self.focused_coin();
+        let mut rows: Vec<(String, String, f64)> = match &focused {
+            Some(symbol) => self
+                .coin_balances(symbol)
+                .into_iter()
+                .map(|(username, balance)| (username, symbol.clone(), balance))
+                .collect(),
+            None => self
+                .user_balances
+                .iter()
+                .flat_map(|(username, coins)| {
+                    coins
+                        .iter()
+                        .filter(|(_, balance)| balance.abs() > BALANCE_DUST)
+                        .map(|(coin, balance)| (username.clone(), coin.clone(), *balance))
+                })
+                .collect(),
+        };
+        if focused.is_none() {
+            rows.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+        }
+
+        self.status_message = Some(match export::export_balances(focused.as_deref(), self.balance_export_format, &rows) {
+            Ok(path) => format!("Exported {} balances to {}", rows.len(), path.display()),
+            Err(e) => format!("Failed to export balances: {}", e),
+        });
+    }
+
+    fn recalculate_balances_from_trades(&mut self) {
+        self.user_balances.clear();
+        self.balance_entries = 0;
+        let policy = self.incomplete_trades;
+        let trades = std::mem::take(&mut self.all_trades);
+        for trade in trades.iter().rev().filter(|t| t.counts_towards_stats(policy)) {
+            self.apply_to_balance(trade);
+        }
+        self.all_trades = trades;
+        if self.max_balance_entries.is_some_and(|max| self.balance_entries > max.max(self.balance_prune_at)) {
+            self.prune_balances();
+        }
+    }
+
+
+    fn trade_matches_filters(&self, t: &Trade) -> bool {
+        let type_matches = match self.trade_type_filter.as_deref() {
+            Some(specific_filter_type) => t.trade_type_val.eq_ignore_ascii_case(specific_filter_type),
+            None => true,
+        };
+        let action_matches = match &self.action_filter {
+            Some(action) => t.action == *action,
+            None => true,
+        };
+        let value_matches = match self.min_value_filter {
+            Some(min_value) => t.total_value >= min_value,
+            None => true,
+        };
+        let user_matches = match &self.user_filter {
+            Some(user) => t.username.eq_ignore_ascii_case(user),
+            None => true,
+        };
+        type_matches
+            && !self.blocked_users.contains(&t.username.to_lowercase())
+            && !self.blocked_coins.contains(&symbols::normalize(&t.coin_symbol))
+            && self.symbol_matches(&t.coin_symbol)
+            && action_matches
+            && value_matches
+            && user_matches
+            && (!self.watchlist_only || self.pinned.contains(&symbols::normalize(&t.coin_symbol)))
+            && self.filter_expr.as_ref().is_none_or(|(_, expr)| expr.matches(t))
+    }
+
+    fn get_visible_trades(&self) -> Vec<Trade> {
+        let mut trades: Vec<Trade> = self
+            .all_trades
+            .iter()
+            .filter(|t| self.trade_matches_filters(t))
+            .cloned()
+            .collect();
+        if self.tape_sort == TapeSort::Total {
+            // Stable, so equal totals stay newest first.
+            trades.sort_by(|a, b| b.total_value.total_cmp(&a.total_value));
+        }
+        trades
+    }
+
+    /// Replaces the tape filters with the named preset's.
+    fn apply_preset(&mut self, name: &str) {
+        let Some(preset) = self.presets.get(name).cloned() else {
+            let names: Vec<&str> = self.presets.keys().map(String::as_str).collect();
+            self.status_message = Some(format!("Unknown preset: {} (have {})", name, names.join(", ")));
+            return;
+        };
+        self.trade_type_filter = preset.trade_type;
+        self.action_filter = preset.action;
+        self.min_value_filter = preset.min_value;
+        self.user_filter = preset.user;
+        self.filter_expr = preset
+            .expr
+            .and_then(|source| filter_expr::parse(&source).ok().map(|expr| (source, expr)));
+        self.tape_sort = preset.sort;
+        self.flash_new_trades = preset.flash.unwrap_or(self.flash_configured);
+        self.active_preset = Some(name.to_string());
+        self.status_message = Some(format!("Preset: {}", name));
+        self.reset_view();
+    }
+
+    /// Clears every tape filter and goes back to newest first.
+    fn clear_preset(&mut self) {
+        self.trade_type_filter = None;
+        self.action_filter = None;
+        self.min_value_filter = None;
+        self.user_filter = None;
+        self.filter_expr = None;
+        self.tape_sort = TapeSort::Newest;
+        self.flash_new_trades = self.flash_configured;
+        self.active_preset = None;
+        self.status_message = Some("Preset cleared".to_string());
+        self.reset_view();
+    }
+
+    fn toggle_whale_watch(&mut self) {
+        if self.active_preset.as_deref() == Some(WHALE_PRESET) {
+            self.clear_preset();
+        } else {
+            self.apply_preset(WHALE_PRESET);
+        }
+    }
+
+    /// Back to the newest trade with nothing selected, after the filters change.
+    fn reset_view(&mut self) {
+        self.scroll_offset = 0;
+        self.selected_trade = None;
+        self.show_trade_details = false;
+    }
+
+    /// Selects the session's largest trade in the tape, scrolled to the
+    /// middle of the view, and opens its details.
+    fn jump_to_largest_trade(&mut self, num_visible_items: usize) {
+        let Some(record) = &self.largest_trade else {
+            self.status_message = Some("No trades received this session yet".to_string());
+            return;
+        };
+        let Some(index) = self.get_visible_trades().iter().position(|t| t.received_at == record.received_at) else {
+            let buffered = self.all_trades.iter().any(|t| t.received_at == record.received_at);
+            self.status_message = Some(format!(
+                "Largest trade (${} {}) {}",
+                format_compact(record.total_value),
+                record.coin_symbol,
+                if buffered { "is hidden by the filters" } else { "is no longer in the tape's buffer" }
+            ));
+            return;
+        };
+        self.show_trade_at(index, num_visible_items);
+    }
+
+    /// Selects visible trade `index` in the middle of the tape, with details.
+    fn show_trade_at(&mut self, index: usize, num_visible_items: usize) {
+        self.view_mode = ViewMode::Tape;
+        self.selected_trade = Some(index);
+        self.scroll_offset = index.saturating_sub(num_visible_items / 2);
+        self.show_trade_details = true;
+    }
+
+    /// Moves the selection by `delta` rows, starting at the top of the view,
+    /// and scrolls so the selected row stays on screen.
+    fn move_selection(&mut self, delta: isize, num_visible_items: usize) {
+        let total_items = self.get_visible_trades().len();
+        if total_items == 0 {
+            self.selected_trade = None;
+            return;
+        }
+        let selected = match self.selected_trade {
+            Some(selected) => (selected as isize + delta).clamp(0, total_items as isize - 1) as usize,
+            None => self.scroll_offset.min(total_items - 1),
+        };
+        self.selected_trade = Some(selected);
+        if selected < self.scroll_offset {
+            self.scroll_offset = selected;
+        } else if num_visible_items > 0 && selected >= self.scroll_offset + num_visible_items {
+            self.scroll_offset = selected + 1 - num_visible_items;
+        }
+    }
+
+    /// Every coin, busiest first.
+    fn coins_by_activity(&self) -> Vec<(&String, &CoinStats)> {
+        let mut coins: Vec<(&String, &CoinStats)> = self.coin_stats.iter().collect();
+        coins.sort_by(|a, b| b.1.trade_count.cmp(&a.1.trade_count).then_with(|| a.0.cmp(b.0)));
+        coins
+    }
+
+    /// The coins view's rows: `coins_by_activity`, minus balanced coins
+    /// while `directional_only` is on.
+    fn coins_for_view(&self) -> Vec<(&String, &CoinStats)> {
+        let mut coins = self.coins_by_activity();
+        if self.directional_only {
+            coins.retain(|(_, stats)| !stats.is_balanced());
+        }
+        coins
+    }
+
+    fn toggle_view_mode(&mut self) {
+        self.view_mode = match self.view_mode {
+            ViewMode::Tape => ViewMode::Coins,
+            ViewMode::Coins | ViewMode::DbResults | ViewMode::Compare => ViewMode::Tape,
+        };
+        self.compare = None;
+        self.show_trade_details = false;
+    }
+
+    /// Shows the two symbols in `argument` side by side, or goes back to
+    /// the tape for `off`.
+    fn start_compare(&mut self, argument: &str, conn: &Connection) {
+        let coins: Vec<String> = argument.split_whitespace().map(symbols::normalize).collect();
+        match coins.as_slice() {
+            [off] if off == "OFF" => {
+                self.compare = None;
+                self.view_mode = ViewMode::Tape;
+            }
+            [first, second] if first != second => {
+                self.compare = Some([first.clone(), second.clone()].map(|symbol| ComparedCoin {
+                    price_history: load_price_history_db(conn, &symbol).unwrap_or_default(),
+                    symbol,
+                }));
+                self.view_mode = ViewMode::Compare;
+                self.show_trade_details = false;
+            }
+            _ => self.status_message = Some("Compare needs two different symbols, e.g. compare DOGE PEPE".to_string()),
+        }
+    }
+
+    fn move_coin_selection(&mut self, delta: isize) {
+        let coin_count = self.coins_for_view().len();
+        if coin_count == 0 {
+            return;
+        }
+        self.selected_coin = (self.selected_coin as isize + delta).clamp(0, coin_count as isize - 1) as usize;
+    }
+
+    /// Opens the tape for the coin selected in the coins view.
+    fn drill_into_selected_coin(&mut self, conn: &Connection) {
+        let Some(symbol) = self
+            .coins_for_view()
+            .get(self.selected_coin)
+            .map(|(symbol, _)| (*symbol).clone())
+        else {
+            return;
+        };
+        self.show_coin(symbol, conn);
+        self.remember_recent_coin(conn);
+    }
+
+    /// Moves the focused coin to the front of `recent_coins`.
+    fn remember_recent_coin(&mut self, conn: &Connection) {
+        let Some(symbol) = self.focused_coin() else {
+            return;
+        };
+        self.recent_coins.retain(|coin| *coin != symbol);
+        self.recent_coins.insert(0, symbol);
+        self.recent_coins.truncate(RECENT_COINS_MAX);
+        if let Err(e) = save_recent_coins_db(conn, &self.recent_coins) {
+            self.status_message = Some(format!("Failed to save recent coins: {}", describe_db_error(&e)));
+        }
+    }
+
+    /// Switches to the `number`th (1-based) recent coin.
+    fn switch_to_recent_coin(&mut self, number: usize, conn: &Connection) {
+        let Some(symbol) = number.checked_sub(1).and_then(|index| self.recent_coins.get(index)).cloned() else {
+            return;
+        };
+        self.show_coin(symbol, conn);
+        self.remember_recent_coin(conn);
+    }
+
+    /// Searches for exactly `symbol` and shows it next to the tape.
+    fn show_coin(&mut self, symbol: String, conn: &Connection) {
+        self.search_input = symbol;
+        self.search_mode = SearchMode::Exact;
+        self.move_cursor_end();
+        self.submit_search();
+        self.refresh_price_history(conn);
+        self.view_mode = ViewMode::Tape;
+    }
+
+    /// Jumps to the coin behind an alert worth `value`. Once it has jumped it
+    /// stays put for `ALERT_FOLLOW_HOLD` unless a bigger alert comes in, so
+    /// several coins alerting at once don't make the view flip back and forth.
+    /// Never interrupts a search that is being typed.
+    fn follow_alert(&mut self, symbol: &str, value: f64, conn: &Connection) {
+        if self.input_mode != InputMode::Normal {
+            return;
+        }
+        let now = Instant::now();
+        if let Some((at, followed_value)) = self.last_alert_follow
+            && now.duration_since(at) < ALERT_FOLLOW_HOLD
+            && value <= followed_value
+        {
+            return;
+        }
+        self.last_alert_follow = Some((now, value));
+        if self.active_search_symbol.as_deref() != Some(symbol) {
+            self.show_coin(symbol.to_string(), conn);
+        }
+    }
+
+    /// Shows every trade by the selected trade's user, across all coins.
+    fn follow_selected_user(&mut self, conn: &Connection) {
+        let Some(trade) = self.selected_trade() else {
+            self.status_message = Some(self.select_trade_first());
+            return;
+        };
+        self.search_input = format!("@{}", trade.username);
+        self.move_cursor_end();
+        self.active_search_symbol = None;
+        self.submit_search();
+        self.refresh_price_history(conn);
+        self.view_mode = ViewMode::Tape;
+    }
+
+    /// Per-coin activity of `username`, most traded first. P&L is rough:
+    /// USD in from sells, minus USD out on buys, plus what the remaining
+    /// balance is worth at the coin's last price.
+    fn user_coin_summaries(&self, username: &str) -> Vec<UserCoinSummary> {
+        let policy = self.incomplete_trades;
+        let mut by_coin: HashMap<String, UserCoinSummary> = HashMap::new();
+        for trade in self
+            .all_trades
+            .iter()
+            .filter(|t| t.username.eq_ignore_ascii_case(username) && t.counts_towards_stats(policy))
+        {
+            let summary = by_coin.entry(trade.coin_symbol.clone()).or_insert_with(|| UserCoinSummary {
+                coin: trade.coin_symbol.clone(),
+                ..UserCoinSummary::default()
+            });
+            summary.trade_count += 1;
+            summary.volume += trade.total_value;
+            if trade.action == "BUY" {
+                summary.balance += trade.amount;
+                summary.pnl -= trade.total_value;
+            } else if trade.action == "SELL" {
+                summary.balance -= trade.amount;
+                summary.pnl += trade.total_value;
+            }
+        }
+        let mut summaries: Vec<UserCoinSummary> = by_coin.into_values().collect();
+        for summary in &mut summaries {
+            let last_price = self.coin_stats.get(&symbols::normalize(&summary.coin)).map_or(0.0, |stats| stats.last_price);
+            summary.pnl += summary.balance * last_price;
+        }
+        summaries.sort_by(|a, b| b.volume.total_cmp(&a.volume).then_with(|| a.coin.cmp(&b.coin)));
+        summaries
+    }
+
+    fn selected_trade(&self) -> Option<Trade> {
+        self.get_visible_trades().get(self.selected_trade?).cloned()
+    }
+
+    fn scroll_up(&mut self) {
+        if self.scroll_offset > 0 {
+            self.scroll_offset -= 1;
+        }
+    }
+
+    fn scroll_down(&mut self, num_visible_items: usize) {
+        let total_items = self.get_visible_trades().len();
+        if total_items > 0 && self.scroll_offset < total_items.saturating_sub(1) {
+            if (total_items > num_visible_items && self.scroll_offset < total_items - num_visible_items)
+                || (total_items <= num_visible_items && self.scroll_offset < total_items - 1)
+            {
+                self.scroll_offset += 1;
+            } else if total_items > num_visible_items && self.scroll_offset >= total_items - num_visible_items {
+                self.scroll_offset = total_items - num_visible_items;
+            }
+        }
+    }
+
+    /// Rough number of trade rows that fit on a terminal of the given height.
+    fn trade_rows_for_height(&self, height: u16) -> usize {
+        height.saturating_sub(6 + self.watchlist_height() + self.show_legend as u16) as usize
+    }
+
+    /// The tier floors `mode` colors against.
+    fn tiers(&self, mode: ColorMode) -> &[f64; 4] {
+        match mode {
+            ColorMode::Value => &self.value_tiers,
+            ColorMode::Amount => &self.amount_tiers,
+        }
+    }
+
+    /// Index of the highest tier `trade` reaches under `mode`.
+    fn tier(&self, trade: &Trade, mode: ColorMode) -> Option<usize> {
+        let measure = match mode {
+            ColorMode::Value => trade.total_value,
+            ColorMode::Amount => trade.amount.abs(),
+        };
+        self.tiers(mode).iter().rposition(|floor| measure >= *floor)
+    }
+
+    fn set_theme(&mut self, name: ThemeName) {
+        self.theme_name = name;
+        self.theme = name.theme();
+        let scrollbar = &mut self.theme.scrollbar;
+        if self.scrollbar.chars != ScrollbarChars::Blocks {
+            (scrollbar.thumb, scrollbar.track) = self.scrollbar.chars.chars();
+        }
+        scrollbar.thumb = self.scrollbar.thumb.unwrap_or(scrollbar.thumb);
+        scrollbar.track = self.scrollbar.track.unwrap_or(scrollbar.track);
+        // Validated when the config was loaded.
+        if let Some(color) = self.scrollbar.thumb_color.as_deref().and_then(|name| name.parse().ok()) {
+            scrollbar.thumb_color = color;
+        }
+        if let Some(color) = self.scrollbar.track_color.as_deref().and_then(|name| name.parse().ok()) {
+            scrollbar.track_color = color;
+        }
+        self.use_color = !self.no_color && self.theme.colored;
+    }
+
+    /// Switches to the next built-in theme and saves it as the default.
+    fn cycle_theme(&mut self) {
+        self.set_theme(self.theme_name.next());
+        let label = self.theme_name.label();
+        self.status_message = match config::save_setting(self.config_flag.as_deref(), "theme", label.into()) {
+            Ok(()) => Some(format!("Theme: {}", label)),
+            Err(e) => Some(format!("Theme: {} (not saved: {})", label, e)),
+        };
+    }
+
+    fn toggle_color_mode(&mut self) {
+        self.color_mode = match self.color_mode {
+            ColorMode::Value => ColorMode::Amount,
+            ColorMode::Amount => ColorMode::Value,
+        };
+    }
+
+    /// Steps the type filter through every type seen so far, in name
+    /// order, then back to all types.
+    fn toggle_trade_type_filter(&mut self) {
+        let next = match self.trade_type_filter.as_deref() {
+            None => self.trade_types.first(),
+            Some(current) => self.trade_types.range::<str, _>((Bound::Excluded(current), Bound::Unbounded)).next(),
+        };
+        self.trade_type_filter = next.cloned();
+        self.reset_view();
+    }
+
+    fn toggle_action_filter(&mut self) {
+        self.action_filter = match self.action_filter.as_deref() {
+            None => Some("BUY".to_string()),
+            Some("BUY") => Some("SELL".to_string()),
+            _ => None,
+        };
+        self.reset_view();
+    }
+
+    fn cycle_min_value_filter(&mut self) {
+        self.min_value_filter = match self.min_value_filter {
+            None => Some(self.value_tiers[0]),
+            Some(current) => self.value_tiers.iter().copied().find(|step| *step > current),
+        };
+        self.reset_view();
+    }
+
+    fn move_cursor_left(&mut self) {
+        if self.cursor_position > 0 {
+            self.cursor_position -= 1;
+        }
+    }
+
+    fn move_cursor_right(&mut self) {
+        if self.cursor_position < self.search_input.chars().count() {
+            self.cursor_position += 1;
+        }
+    }
+
+    fn move_cursor_start(&mut self) {
+        self.cursor_position = 0;
+    }
+
+    fn move_cursor_end(&mut self) {
+        self.cursor_position = self.search_input.chars().count();
+    }
+
+    // cursor_position counts chars, String wants a byte offset.
+    fn byte_index(&self, char_position: usize) -> usize {
+        self.search_input
+            .char_indices()
+            .map(|(i, _)| i)
+            .nth(char_position)
+            .unwrap_or(self.search_input.len())
+    }
+
+    fn prev_word_boundary(&self) -> usize {
+        let chars: Vec<char> = self.search_input.chars().collect();
+        let mut position = self.cursor_position.min(chars.len());
+        while position > 0 && chars[position - 1].is_whitespace() {
+            position -= 1;
+        }
+        while position > 0 && !chars[position - 1].is_whitespace() {
+            position -= 1;
+        }
+        position
+    }
+
+    fn next_word_boundary(&self) -> usize {
+        let chars: Vec<char> = self.search_input.chars().collect();
+        let mut position = self.cursor_position.min(chars.len());
+        while position < chars.len() && chars[position].is_whitespace() {
+            position += 1;
+        }
+        while position < chars.len() && !chars[position].is_whitespace() {
+            position += 1;
+        }
+        position
+    }
+
+    fn move_cursor_word_left(&mut self) {
+        self.cursor_position = self.prev_word_boundary();
+    }
+
+    fn move_cursor_word_right(&mut self) {
+        self.cursor_position = self.next_word_boundary();
+    }
+
+    fn enter_char(&mut self, new_char: char) {
+        let index = self.byte_index(self.cursor_position);
+        self.search_input.insert(index, new_char);
+        self.move_cursor_right();
+    }
+
+    fn delete_char(&mut self) {
+        if self.cursor_position > 0 && !self.search_input.is_empty() {
+            let index = self.byte_index(self.cursor_position - 1);
+            self.search_input.remove(index);
+            self.move_cursor_left();
+        }
+    }
+
+    fn delete_word(&mut self) {
+        let word_start = self.prev_word_boundary();
+        let start = self.byte_index(word_start);
+        let end = self.byte_index(self.cursor_position);
+        self.search_input.replace_range(start..end, "");
+        self.cursor_position = word_start;
+    }
+
+    fn cancel_editing(&mut self) {
+        if self.live_search && self.last_submitted_search.starts_with('!') {
+            // A database search has no tape filter to put back, so the one
+            // typed live goes along with the query.
+            self.search_input.clear();
+            self.submit_search();
+        } else if self.live_search {
+            // Put back the filter that was locked in before typing started.
+            let typed = std::mem::replace(&mut self.search_input, self.last_submitted_search.clone());
+            self.submit_search();
+            if !self.escape_reverts_search {
+                self.search_input = typed;
+            }
+        }
+        if self.escape_reverts_search {
+            self.search_input = self.last_submitted_search.clone();
+            self.move_cursor_end();
+        }
+        self.input_mode = InputMode::Normal;
+    }
+
+    /// Plain symbol searches; the prefixed kinds wait for Enter.
+    fn is_live_searchable(&self) -> bool {
+        !self.search_input.starts_with(['@', '?', '!'])
+    }
+
+    /// With live search on, filters by the typed symbol on every keystroke
+    /// without locking it in; Enter still does that.
+    fn apply_live_search(&mut self, conn: &Connection) {
+        if !self.live_search || !self.is_live_searchable() {
+            return;
+        }
+        self.active_search_symbol = if self.search_input.is_empty() {
+            None
+        } else {
+            Some(symbols::normalize(&self.search_input))
+        };
+        self.refresh_fuzzy_matches();
+        self.focused_coin_index = 0;
+        self.reset_view();
+        self.refresh_price_history(conn);
+    }
+
+    /// Applies the search box: `!` queries search the database, anything
+    /// else filters the tape.
+    fn submit(&mut self, conn: &Connection) {
+        if let Some(query) = self.search_input.strip_prefix('!') {
+            let query = query.trim().to_string();
+            self.run_db_search(&query, conn);
+        } else {
+            self.submit_search();
+            self.refresh_price_history(conn);
+            if !self.search_input.starts_with(['?', '@']) {
+                self.remember_recent_coin(conn);
+            }
+        }
+    }
+
+    /// A query that doesn't parse leaves the search box unapplied.
+    fn run_db_search(&mut self, query: &str, store: &dyn TradeStore) {
+        let parsed = match db_search::DbQuery::parse(query) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                self.status_message = Some(format!("DB search: {}", e));
+                return;
+            }
+        };
+        self.last_submitted_search = self.search_input.clone();
+        match store.query(&parsed) {
+            Ok(trades) => {
+                self.db_results = Some((query.to_string(), trades));
+                self.db_results_scroll = 0;
+                self.view_mode = ViewMode::DbResults;
+            }
+            Err(e) => self.status_message = Some(format!("DB search failed: {}", e)),
+        }
+    }
+
+    fn scroll_db_results(&mut self, delta: isize, visible_rows: usize) {
+        let total = self.db_results.as_ref().map_or(0, |(_, trades)| trades.len());
+        let max_scroll = total.saturating_sub(visible_rows) as isize;
+        self.db_results_scroll = (self.db_results_scroll as isize + delta).clamp(0, max_scroll.max(0)) as usize;
+    }
+
+    fn submit_search(&mut self) {
+        if let Some(source) = self.search_input.strip_prefix('?') {
+            let source = source.trim();
+            if source.is_empty() {
+                self.filter_expr = None;
+            } else {
+                // A broken expression leaves the current filter in place.
+                match filter_expr::parse(source) {
+                    Ok(expr) => self.filter_expr = Some((source.to_string(), expr)),
+                    Err(e) => {
+                        self.status_message = Some(format!("Filter error: {}", e));
+                        return;
+                    }
+                }
+            }
+        } else if let Some(user) = self.search_input.strip_prefix('@') {
+            self.user_filter = if user.is_empty() { None } else { Some(user.to_string()) };
+        } else if self.search_input.is_empty() {
+            self.active_search_symbol = None;
+        } else {
+            self.active_search_symbol = Some(symbols::normalize(&self.search_input));
+        }
+        self.last_submitted_search = self.search_input.clone();
+        self.refresh_fuzzy_matches();
+        self.focused_coin_index = 0;
+        self.reset_view();
+    }
+}
+
+/// Quotes `arg` for a POSIX shell when it has anything but safe characters.
+fn shell_quote(arg: &str) -> String {
+    let safe = !arg.is_empty()
+        && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_.,:/@+=".contains(c));
+    if safe {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// A human explanation for the SQLite failures users can actually fix.
+fn describe_db_error(error: &rusqlite::Error) -> String {
+    use rusqlite::ErrorCode;
+    match error.sqlite_error_code() {
+        Some(ErrorCode::DatabaseBusy) | Some(ErrorCode::DatabaseLocked) => {
+            "the database is locked (is another instance running?)".to_string()
+        }
+        Some(ErrorCode::ReadOnly) => "the database file is read-only".to_string(),
+        Some(ErrorCode::CannotOpen) => "the database file can't be opened (check the path and permissions)".to_string(),
+        Some(ErrorCode::DiskFull) => "the disk is full".to_string(),
+        Some(ErrorCode::PermissionDenied) => "permission denied".to_string(),
+        _ => error.to_string(),
+    }
+}
+
+fn open_db(path: &str, wal: bool, synchronous: Synchronous) -> Result<(Connection, Option<String>), SchemaError> {
+    let conn = Connection::open(path)?;
+    // The retention pruner writes from its own connection.
+    conn.busy_timeout(Duration::from_secs(5))?;
+    let backup = init_db(&conn, wal, synchronous)?;
+    Ok((conn, backup))
+}
+
+fn confirm(prompt: &str) -> bool {
+    print!("{}", prompt);
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).is_ok() && answer.trim().eq_ignore_ascii_case("y")
+}
+
+/// SQLite's `synchronous` setting, trading durability for write speed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Synchronous {
+    /// No fsyncs at all. Fastest, but an OS crash or power loss can corrupt
+    /// the database.
+    Off,
+    /// In WAL mode the database stays consistent, but the most recent
+    /// commits can be lost on power loss. The usual choice with WAL.
+    Normal,
+    /// Syncs on every commit, so committed trades survive power loss.
+    Full,
+    /// Like `Full`, and also syncs the directory after journal changes.
+    Extra,
+}
+
+impl Synchronous {
+    fn parse(value: &str) -> Option<Synchronous> {
+        match value.to_lowercase().as_str() {
+            "off" => Some(Synchronous::Off),
+            "normal" => Some(Synchronous::Normal),
+            "full" => Some(Synchronous::Full),
+            "extra" => Some(Synchronous::Extra),
+            _ => None,
+        }
+    }
+
+    fn pragma_value(self) -> &'static str {
+        match self {
+            Synchronous::Off => "OFF",
+            Synchronous::Normal => "NORMAL",
+            Synchronous::Full => "FULL",
+            Synchronous::Extra => "EXTRA",
+        }
+    }
+}
+
+/// WAL lets readers keep working while trades are being written and avoids
+/// an fsync per insert; it leaves `-wal`/`-shm` files next to the database.
+/// Returns the path of the backup taken before migrating, if any.
+fn init_db(conn: &Connection, wal: bool, synchronous: Synchronous) -> Result<Option<String>, SchemaError> {
+    if wal {
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+    }
+    conn.pragma_update(None, "synchronous", synchronous.pragma_value())?;
+    schema::migrate(conn)
+}
+
+fn load_price_history_db(conn: &Connection, coin_symbol: &str) -> RusqliteResult<Vec<(i64, f64)>> {
+    let mut stmt = conn.prepare(
+        "SELECT timestamp, price FROM price_history WHERE coin_symbol = ?1 ORDER BY id DESC LIMIT ?2",
+    )?;
+    let mut history = stmt
+        .query_map(params![coin_symbol, PRICE_HISTORY_LOAD_LIMIT as i64], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<RusqliteResult<Vec<(i64, f64)>>>()?;
+    history.reverse();
+    Ok(history)
+}
+
+fn load_pinned_db(conn: &Connection) -> RusqliteResult<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT coin_symbol FROM pinned_coins ORDER BY position")?;
+    let pinned = stmt.query_map([], |row| row.get(0))?.collect::<RusqliteResult<Vec<String>>>()?;
+    Ok(pinned)
+}
+
+fn save_pinned_db(conn: &Connection, pinned: &[String]) -> RusqliteResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM pinned_coins", [])?;
+    for (position, symbol) in pinned.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO pinned_coins (coin_symbol, position) VALUES (?1, ?2)",
+            params![symbol, position as i64],
+        )?;
+    }
+    tx.commit()
+}
+
+fn load_price_alerts_db(conn: &Connection) -> RusqliteResult<HashMap<String, Vec<f64>>> {
+    let mut stmt = conn.prepare("SELECT coin_symbol, price FROM price_alerts ORDER BY price")?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?)))?
+        .collect::<RusqliteResult<Vec<(String, f64)>>>()?;
+    let mut alerts: HashMap<String, Vec<f64>> = HashMap::new();
+    for (coin, price) in rows {
+        alerts.entry(coin).or_default().push(price);
+    }
+    Ok(alerts)
+}
+
+/// Replaces the saved alert levels for `coin_symbol`.
+fn save_price_alerts_db(conn: &Connection, coin_symbol: &str, levels: &[f64]) -> RusqliteResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM price_alerts WHERE coin_symbol = ?1", params![coin_symbol])?;
+    for level in levels {
+        tx.execute("INSERT INTO price_alerts (coin_symbol, price) VALUES (?1, ?2)", params![coin_symbol, level])?;
+    }
+    tx.commit()
+}
+
+fn load_blocklist_db(conn: &Connection) -> RusqliteResult<(BTreeSet<String>, BTreeSet<String>)> {
+    let mut stmt = conn.prepare("SELECT kind, value FROM blocklist")?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<RusqliteResult<Vec<(String, String)>>>()?;
+    let (mut users, mut coins) = (BTreeSet::new(), BTreeSet::new());
+    for (kind, value) in rows {
+        match kind.as_str() {
+            "user" => users.insert(value),
+            "coin" => coins.insert(value),
+            _ => continue,
+        };
+    }
+    Ok((users, coins))
+}
+
+fn save_blocklist_db(conn: &Connection, users: &BTreeSet<String>, coins: &BTreeSet<String>) -> RusqliteResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM blocklist", [])?;
+    let entries = users.iter().map(|user| ("user", user)).chain(coins.iter().map(|coin| ("coin", coin)));
+    for (kind, value) in entries {
+        tx.execute("INSERT INTO blocklist (kind, value) VALUES (?1, ?2)", params![kind, value])?;
+    }
+    tx.commit()
+}
+
+fn load_notes_db(conn: &Connection) -> RusqliteResult<HashMap<i64, String>> {
+    let mut stmt = conn.prepare("SELECT trade_row_id, note FROM trade_notes")?;
+    let notes = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<RusqliteResult<HashMap<i64, String>>>()?;
+    Ok(notes)
+}
+
+/// Trades that have a note, newest first.
+fn load_bookmarked_trades_db(conn: &Connection) -> RusqliteResult<Vec<Trade>> {
+    let mut stmt = conn.prepare(&format!(
+        "{} WHERE id IN (SELECT trade_row_id FROM trade_notes) ORDER BY id DESC",
+        TRADE_SELECT_SQL
+    ))?;
+    let trades = stmt.query_map([], trade_from_row)?;
+    trades.collect()
+}
+
+/// Sets the note on trade `row_id`, or removes it when `note` is empty.
+fn save_note_db(conn: &Connection, row_id: i64, note: &str) -> RusqliteResult<()> {
+    if note.is_empty() {
+        conn.execute("DELETE FROM trade_notes WHERE trade_row_id = ?1", params![row_id])?;
+    } else {
+        conn.execute(
+            "INSERT OR REPLACE INTO trade_notes (trade_row_id, note) VALUES (?1, ?2)",
+            params![row_id, note],
+        )?;
+    }
+    Ok(())
+}
+
+fn load_recent_coins_db(conn: &Connection) -> RusqliteResult<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT coin_symbol FROM recent_coins ORDER BY position")?;
+    let recent = stmt.query_map([], |row| row.get(0))?.collect::<RusqliteResult<Vec<String>>>()?;
+    Ok(recent)
+}
+
+fn save_recent_coins_db(conn: &Connection, recent: &[String]) -> RusqliteResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM recent_coins", [])?;
+    for (position, symbol) in recent.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO recent_coins (coin_symbol, position) VALUES (?1, ?2)",
+            params![symbol, position as i64],
+        )?;
+    }
+    tx.commit()
+}
+
+fn delete_all_trades_db(conn: &Connection) -> RusqliteResult<usize> {
+    conn.execute("DELETE FROM trade_notes", [])?;
+    conn.execute("DELETE FROM trades", [])
+}
+
+/// Writes a compacted copy of the whole database to `path`.
+fn backup_db(conn: &Connection, path: &str) -> RusqliteResult<()> {
+    conn.execute("VACUUM INTO ?1", params![path]).map(|_| ())
+}
+
+fn prune_price_history_db(conn: &Connection, max_age_secs: i64) -> RusqliteResult<usize> {
+    let cutoff = Local::now().timestamp() - max_age_secs;
+    conn.execute("DELETE FROM price_history WHERE timestamp < ?1", params![cutoff])
+}
+
+fn insert_trade_db(conn: &Connection, trade: &Trade) -> RusqliteResult<usize> {
+    conn.execute(
+        "INSERT INTO trades (timestamp, trade_type_val, action, username, amount, coin_symbol, total_value, price, trade_id, recorded_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            trade.timestamp,
+            trade.trade_type_val,
+            trade.action,
+            trade.username,
+            trade.amount,
+            trade.coin_symbol,
+            trade.total_value,
+            trade.price,
+            trade.id,
+            trade.recorded_at.unwrap_or_else(|| Utc::now().timestamp())
+        ],
+    )
+}
+
+/// Selects the columns `trade_from_row` expects, in order.
+const TRADE_SELECT_SQL: &str =
+    "SELECT timestamp, trade_type_val, action, username, amount, coin_symbol, total_value, price, trade_id, recorded_at, id FROM trades";
+
+fn trade_from_row(row: &rusqlite::Row) -> RusqliteResult<Trade> {
+    Ok(Trade {
+        timestamp: row.get(0)?,
+        trade_type_val: row.get(1)?,
+        action: row.get(2)?,
+        username: row.get(3)?,
+        amount: row.get(4)?,
+        coin_symbol: row.get(5)?,
+        total_value: row.get(6)?,
+        price: row.get(7)?,
+        id: row.get(8)?,
+        received_at: None,
+        recorded_at: row.get(9)?,
+        row_id: row.get(10)?,
+    })
+}
+
+/// Trades saved after row `after`, newest first.
+fn load_trades_after_db(conn: &Connection, after: i64) -> RusqliteResult<Vec<Trade>> {
+    let mut stmt = conn.prepare(&format!("{} WHERE id > ?1 ORDER BY id DESC", TRADE_SELECT_SQL))?;
+    let trades = stmt.query_map(params![after], trade_from_row)?;
+    trades.collect()
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    if std::env::args().skip(1).any(|arg| arg == "--version" || arg == "-V") {
+        println!(
+            "{} {} ({})",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            env!("TRADEMAXXING_GIT_COMMIT")
+        );
+        return Ok(());
+    }
+
+    let args = parse_args().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    let config = load_or_create_config(args.config.as_deref());
+    let settings = resolve_settings(&args, &config);
+    let display_tz = args.tz.as_deref().or(config.timezone.as_deref()).map(|name| {
+        name.parse::<Tz>().unwrap_or_else(|_| {
+            eprintln!("Unknown timezone '{}'. Use an IANA name such as UTC, Europe/Berlin or America/New_York.", name);
+            std::process::exit(2);
+        })
+    });
+    let auto_export = match (&args.export_on_exit, &config.auto_export) {
+        (Some(dir), configured) => Some(AutoExport {
+            dir: dir.clone(),
+            ..configured.clone().unwrap_or_default()
+        }),
+        (None, configured) => configured.clone(),
+    };
+    let columns = resolve_columns(config.columns.as_deref());
+    let subscriptions = resolve_subscriptions(config.subscriptions.as_deref(), &settings.channel);
+
+    let opened = if args.replay_raw.is_some() {
+        // Replayed trades would otherwise be saved again on every replay.
+        let conn = Connection::open_in_memory()?;
+        init_db(&conn, false, args.synchronous)?;
+        Ok((conn, true, None))
+    } else {
+        open_db(&settings.db_path, args.wal, args.synchronous).map(|(conn, backup)| (conn, false, backup))
+    };
+    let (conn, db_in_memory, schema_backup) = match opened {
+        Ok(opened) => opened,
+        Err(e) => {
+            eprintln!("Can't use {}: {}.", settings.db_path, e);
+            // Falling back would hide that the file needs a newer build.
+            if matches!(e, SchemaError::TooNew { .. })
+                || args.import.is_some()
+                || !confirm("Continue with a temporary in-memory database? Nothing will be saved. [y/N] ")
+            {
+                std::process::exit(1);
+            }
+            let conn = Connection::open_in_memory()?;
+            init_db(&conn, false, args.synchronous)?;
+            (conn, true, None)
+        }
+    };
+
+    if let Some(path) = &args.import {
+        let summary = csv::import_csv(&conn, path)?;
+        println!("Imported {} trades from {} ({} malformed rows skipped)", summary.imported, path, summary.skipped);
+        return Ok(());
+    }
+
+    if let Err(e) = prune_price_history_db(&conn, PRICE_HISTORY_MAX_AGE_SECS) {
+        eprintln!("Failed to prune price history: {}", e);
+    }
+    let retention_secs = args.retention_days.map(|days| i64::from(days) * 24 * 60 * 60);
+    let mut retention_notice = None;
+    if let Some(max_age_secs) = retention_secs {
+        match retention::prune_trades_db(&conn, max_age_secs) {
+            Ok(0) => {}
+            Ok(deleted) => retention_notice = Some(format!("Pruned {} trades past retention", deleted)),
+            Err(e) => eprintln!("Failed to prune old trades: {}", describe_db_error(&e)),
+        }
+    }
+    let initial_trades = conn.load_recent(None).unwrap_or_else(|e| {
+        eprintln!("Failed to load trades from DB: {}. Starting with empty list.", e);
+        Vec::new()
+    });
+
+    let metrics = Arc::new(Metrics::new());
+    if let Some(addr) = &args.serve {
+        let db_path = (!db_in_memory).then(|| settings.db_path.clone());
+        server::serve(addr, Arc::clone(&metrics), db_path)
+            .map_err(|e| format!("Can't serve on {}: {}", addr, e))?;
+    }
+
+    let recorder = match &args.record {
+        Some(path) => Some(
+            raw_stream::RawRecorder::open(path).map_err(|e| format!("Can't record to {}: {}", path, e))?,
+        ),
+        None => None,
+    };
+    let mut startup_warnings = Vec::new();
+    let proxy = match &args.proxy {
+        Some(url) => Some(Proxy::parse(url).map_err(|e| format!("Invalid --proxy: {}", e))?),
+        // A proxy only the environment asks for isn't worth refusing to start over.
+        None => Proxy::from_env(&settings.ws_url).and_then(|parsed| {
+            parsed
+                .map_err(|e| startup_warnings.push(format!("Ignoring the proxy from the environment ({}), connecting directly", e)))
+                .ok()
+        }),
+    };
+    let replay_lines = match &args.replay_raw {
+        Some(path) => {
+            Some(raw_stream::load_recording(path).map_err(|e| format!("Can't replay {}: {}", path, e))?)
+        }
+        None => None,
+    };
+
+    // Keepalives wake on read timeouts, which only plain sockets get.
+    if config.keepalive.is_some() && replay_lines.is_none() && settings.ws_url.to_ascii_lowercase().starts_with("wss://") {
+        return Err(format!("keepalive needs a ws:// feed URL, not {}", settings.ws_url).into());
+    }
+
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    let (tx, rx) = trade_channel::bounded(config.trade_queue.capacity, config.trade_queue.overflow);
+    let (status_tx, status_rx) = mpsc::channel();
+
+    let ingest = IngestFilter {
+        min_value: args.ingest_min_value,
+        coins: args.ingest_coins.clone().map(|coins| coins.into_iter().collect()),
+    };
+    let feed_metrics = Arc::clone(&metrics);
+    match replay_lines {
+        Some(lines) => {
+            thread::spawn(move || raw_stream::replay(lines, tx, feed_metrics, ingest));
+        }
+        None => {
+            let endpoint = Endpoint {
+                url: settings.ws_url.clone(),
+                proxy,
+                subscriptions,
+                keepalive: config.keepalive.clone(),
+            };
+            let max_reconnects = args.max_reconnects;
+            let feed_status_tx = status_tx.clone();
+            thread::spawn(move || {
+                run_feed(tx, endpoint, feed_metrics, recorder, max_reconnects, ingest, feed_status_tx)
+            });
+        }
+    }
+
+    if let Some(max_age_secs) = retention_secs
+        && !db_in_memory
+    {
+        retention::spawn_pruner(settings.db_path.clone(), max_age_secs, status_tx);
+    }
+
+    let mut app = App::new(initial_trades);
+    app.columns = columns;
+    app.no_color = args.no_color;
+    app.config_flag = args.config.clone();
+    app.scrollbar = config.scrollbar.clone();
+    app.set_theme(config.theme);
+    app.action_glyphs = config.action_glyphs;
+    app.auto_fit_columns = config.auto_fit_columns;
+    app.escape_reverts_search = config.escape_reverts_search;
+    app.flash_new_trades = config.flash_new_trades;
+    app.flash_configured = config.flash_new_trades;
+    app.dim_old_trades = config.dim_old_trades;
+    app.presets.extend(config.presets);
+    app.sampler = config.view_sampling.map(Sampler::new);
+    app.auto_pause = config.auto_pause;
+    app.value_tiers = config.value_tiers;
+    app.amount_tiers = config.amount_tiers;
+    app.color_mode = config.color_mode;
+    app.bell_alert = config.bell_alert;
+    app.volume_watcher = config.volume_alert.map(VolumeWatcher::new);
+    let (keymap, keymap_warnings) = Keymap::from_config(&config.keymap);
+    app.keymap = keymap;
+    startup_warnings.extend(keymap_warnings);
+    let (column_formats, format_warnings) = resolve_column_formats(&config.column_formats);
+    app.column_formats = column_formats;
+    startup_warnings.extend(format_warnings);
+    let (column_decimals, decimal_warnings) = resolve_column_decimals(&config.column_decimals);
+    app.column_decimals = column_decimals;
+    startup_warnings.extend(decimal_warnings);
+    app.number_grouping = config.number_grouping;
+    app.display_tz = display_tz;
+    app.show_millis = config.show_millis;
+    app.snapshot_format = config.snapshot_format;
+    app.balance_export_format = config.balance_export_format;
+    app.live_search = config.live_search;
+    app.activity_thresholds = config.activity_thresholds;
+    app.min_terminal_size = config.min_terminal_size;
+    app.max_rendered_rows = config.max_rendered_rows;
+    app.incomplete_trades = config.incomplete_trades;
+    app.db_in_memory = db_in_memory;
+    app.feed_metrics = Arc::clone(&metrics);
+    let schema_notice = schema_backup
+        .map(|path| format!("Upgraded the database to schema v{} (backup: {})", schema::LATEST_VERSION, path));
+    let notices: Vec<String> = schema_notice.into_iter().chain(retention_notice).chain(startup_warnings).collect();
+    app.status_message = (!notices.is_empty()).then(|| notices.join("; "));
+    app.pinned = load_pinned_db(&conn).unwrap_or_default();
+    app.recent_coins = load_recent_coins_db(&conn).unwrap_or_default();
+    (app.blocked_users, app.blocked_coins) = load_blocklist_db(&conn).unwrap_or_default();
+    app.price_alerts = load_price_alerts_db(&conn).unwrap_or_default();
+    app.notes = load_notes_db(&conn).unwrap_or_default();
+    app.max_balance_entries = config.max_balance_entries;
+    // Balances and stats need the whole history, so trim only afterwards.
+    app.recalculate_balances_from_trades();
+    app.seed_coin_stats();
+    app.session_start_row = app.all_trades.first().and_then(|t| t.row_id).unwrap_or(0);
+    app.display_capacity = config.display_capacity;
+    app.trim_display_buffer();
+    app.trade_type_filter = args.filter;
+    app.action_filter = args.action;
+    app.min_value_filter = args.min_value;
+    app.user_filter = args.user;
+    app.filter_expr = args
+        .expr
+        .and_then(|source| filter_expr::parse(&source).ok().map(|expr| (source, expr)));
+    if let Some(search_mode) = args.search_mode {
+        app.search_mode = search_mode;
+    }
+    if let Some(symbol) = args.symbol {
+        app.search_input = symbol;
+        app.move_cursor_end();
+        app.submit_search();
+        app.refresh_price_history(&conn);
+    }
+    app.follow_new_trades = config.follow_new_trades;
+    app.moving_average_period = config.moving_average_period;
+    app.auto_scroll_speed = config.auto_scroll_speed;
+    if config.initial_scroll == InitialScroll::Bottom {
+        // Drawing pulls this back to the last full page.
+        app.scroll_offset = app.get_visible_trades().len().saturating_sub(1);
+    }
+
+    // Returns once the feed channel is dropped, so nothing new arrives
+    // while exporting.
+    let app = run_app(&mut terminal, app, rx, status_rx, &conn)?;
+    let export_result = auto_export.map(|settings| {
+        let trades = match settings.scope {
+            ExportScope::Session => {
+                load_trades_after_db(&conn, app.session_start_row).map_err(|e| describe_db_error(&e))?
+            }
+            ExportScope::All => conn.load_recent(None)?,
+        };
+        export::export_trades(&settings, &trades).map(|path| (path, trades.len()))
+    });
+
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )?;
+    terminal.show_cursor()?;
+    match export_result {
+        Some(Ok((path, count))) => println!("Exported {} trades to {}", count, path.display()),
+        Some(Err(e)) => eprintln!("Auto-export failed: {}", e),
+        None => {}
+    }
+
+    Ok(())
+}
+
+/// Carries out a keymap action. `fitting_trade_rows` is how many trade rows
+/// fit on screen. Returns `true` when the app should quit.
+fn perform_action(app: &mut App, action: Action, fitting_trade_rows: usize, conn: &Connection) -> bool {
+    let visible_trade_area_height = fitting_trade_rows.min(app.max_rendered_rows);
+    let coins_view = app.view_mode == ViewMode::Coins;
+    let db_view = app.view_mode == ViewMode::DbResults;
+    let compare_view = app.view_mode == ViewMode::Compare;
+    let db_rows = fitting_trade_rows + app.watchlist_height() as usize;
+    match action {
+        Action::Quit => return true,
+        Action::Help => app.show_help = true,
+        Action::CommandPalette => app.palette = Some(Palette::default()),
+        Action::ToggleView => app.toggle_view_mode(),
+        Action::ToggleDirectionalOnly => {
+            app.directional_only = !app.directional_only;
+            app.selected_coin = 0;
+        }
+        Action::SelectDown if coins_view => app.move_coin_selection(1),
+        Action::SelectUp if coins_view => app.move_coin_selection(-1),
+        Action::Submit if coins_view => app.drill_into_selected_coin(conn),
+        Action::SelectDown | Action::ScrollDown if db_view => app.scroll_db_results(1, db_rows),
+        Action::SelectUp | Action::ScrollUp if db_view => app.scroll_db_results(-1, db_rows),
+        Action::PageDown if db_view => app.scroll_db_results(10, db_rows),
+        Action::PageUp if db_view => app.scroll_db_results(-10, db_rows),
+        Action::ClearSelection if db_view => app.view_mode = ViewMode::Tape,
+        Action::ClearSelection if compare_view => app.toggle_view_mode(),
+        Action::SelectDown | Action::ScrollDown | Action::PageDown => {
+            // Page history in ahead of the view so it can scroll past the buffer.
+            let top = app.scroll_offset.max(app.selected_trade.unwrap_or(0));
+            app.load_history(top + visible_trade_area_height + 10, conn);
+            match action {
+                Action::SelectDown => app.move_selection(1, visible_trade_area_height),
+                Action::ScrollDown => app.scroll_down(visible_trade_area_height),
+                _ => {
+                    for _ in 0..10 { app.scroll_down(visible_trade_area_height); }
+                }
+            }
+        }
+        Action::SelectUp => app.move_selection(-1, visible_trade_area_height),
+        Action::ShowDetails => app.show_trade_details = app.selected_trade.is_some(),
+        Action::FollowUser => app.follow_selected_user(conn),
+        Action::BlockUser => app.block_selected(false, conn),
+        Action::BlockCoin => app.block_selected(true, conn),
+        Action::Blocklist => app.blocklist_selected = Some(0),
+        Action::SessionStats => app.show_stats = true,
+        Action::JumpToLargest => app.jump_to_largest_trade(visible_trade_area_height),
+        Action::EditNote => app.start_note(),
+        Action::Bookmarks => app.open_bookmarks(conn),
+        Action::WhaleWatch => app.toggle_whale_watch(),
+        Action::DeleteAll => app.delete_confirmation = Some(String::new()),
+        // Only does anything inside a list popup.
+        Action::RemoveEntry => {}
+        Action::ToggleLegend => app.show_legend = !app.show_legend,
+        Action::ToggleColorMode => app.toggle_color_mode(),
+        Action::CycleBalanceSort => app.balance_sort = app.balance_sort.next(),
+        Action::FilterBalances => app.input_mode = InputMode::BalanceFilter,
+        Action::ToggleBell => app.toggle_bell(),
+        Action::ToggleUtc => app.toggle_utc(),
+        Action::ToggleMillis => app.toggle_millis(),
+        Action::ExportBalances => app.export_balances(),
+        Action::CopyCliArgs => app.copy_cli_args(),
+        Action::Snapshot => app.snapshot_requested = true,
+        Action::CycleTheme => app.cycle_theme(),
+        Action::ClearSelection => app.selected_trade = None,
+        Action::TogglePause => app.toggle_pause(),
+        Action::ToggleType => app.toggle_trade_type_filter(),
+        Action::ToggleBuySell => app.toggle_action_filter(),
+        Action::CycleMinValue => app.cycle_min_value_filter(),
+        Action::TogglePin => app.toggle_pin(conn),
+        Action::SetPriceAlert => app.set_price_alert(conn),
+        Action::LengthenAverage => app.step_moving_average(1),
+        Action::ShortenAverage => app.step_moving_average(-1),
+        Action::ClearPriceAlerts => app.clear_price_alerts(conn),
+        Action::RecentCoins => app.show_recent_coins = true,
+        Action::MovePinnedUp => app.move_pinned(-1, conn),
+        Action::MovePinnedDown => app.move_pinned(1, conn),
+        Action::ToggleAutoFit => app.auto_fit_columns = !app.auto_fit_columns,
+        Action::SelectColumnLeft => app.move_selected_column(-1),
+        Action::SelectColumnRight => app.move_selected_column(1),
+        Action::CycleColumnFormat => app.cycle_column_format(),
+        Action::ToggleSearchMode => app.toggle_search_mode(conn),
+        Action::FocusNext => app.cycle_focused_coin(true, conn),
+        Action::FocusPrev => app.cycle_focused_coin(false, conn),
+        Action::ToggleWatchlistOnly => {
+            app.watchlist_only = !app.watchlist_only;
+            app.reset_view();
+        }
+        Action::Search => app.input_mode = InputMode::Editing,
+        Action::Submit => app.submit(conn),
+        Action::ScrollUp => app.scroll_up(),
+        Action::PageUp => {
+            for _ in 0..10 { app.scroll_up(); }
+        }
+    }
+    false
+}
+
+fn ring_bell() {
+    // BEL doesn't move the cursor, so it's safe mid-frame.
+    let mut stdout = io::stdout();
+    let _ = stdout.write_all(b"\x07").and_then(|_| stdout.flush());
+}
+
+/// Takes queued trades until the queue is empty or the tick's budget is
+/// spent. Returns whether trades may still be waiting, or `None` once the
+/// feed has hung up and everything it sent has been taken.
+fn drain_trades(app: &mut App, rx: &BoundedReceiver<Trade>, conn: &Connection) -> Option<bool> {
+    let started = Instant::now();
+    for _ in 0..TRADES_PER_TICK {
+        match rx.try_recv() {
+            Ok(trade) => app.receive_trade(trade, conn),
+            Err(mpsc::TryRecvError::Empty) => return Some(false),
+            Err(mpsc::TryRecvError::Disconnected) => return None,
+        }
+        if started.elapsed() >= TRADE_TICK_BUDGET {
+            break;
+        }
+    }
+    Some(true)
+}
+
+fn run_app<B: Backend>(
+    terminal: &mut Terminal<B>,
+    mut app: App,
+    rx: BoundedReceiver<Trade>,
+    status_rx: Receiver<String>,
+    conn: &Connection,
+) -> io::Result<App> {
+    let mut last_price_prune = Instant::now();
+    loop {
+        app.mark_focused_coin_viewed();
+        app.queue_dropped = rx.dropped();
+        if let Ok(message) = status_rx.try_recv() {
+            app.status_message = Some(message);
+        }
+        if last_price_prune.elapsed() >= PRICE_HISTORY_PRUNE_INTERVAL {
+            let _ = prune_price_history_db(conn, PRICE_HISTORY_MAX_AGE_SECS);
+            last_price_prune = Instant::now();
+        }
+
+        let Some(backlog) = drain_trades(&mut app, &rx, conn) else {
+            break;
+        };
+        if app.check_volume_spikes(conn) && app.bell_alert.is_some() && !app.bell_muted {
+            ring_bell();
+        }
+        app.check_burst(Instant::now());
+        app.step_catch_up(Instant::now());
+
+        let frame = terminal.draw(|f| ui(f, &mut app))?;
+        if app.snapshot_requested {
+            app.snapshot_requested = false;
+            app.status_message = Some(match snapshot::save(frame.buffer, app.snapshot_format) {
+                Ok(path) => format!("Saved screen to {}", path),
+                Err(e) => format!("Failed to save screen: {}", e),
+            });
+        }
+
+        // Queued trades get the next tick straight away once input is seen to.
+        let poll_timeout = if backlog { Duration::ZERO } else { Duration::from_millis(100) };
+        if event::poll(poll_timeout)?
+            && let Event::Key(key) = event::read()?
+        {
+            if let Some(typed) = &mut app.delete_confirmation {
+                match key.code {
+                    KeyCode::Enter => {
+                        if typed.eq_ignore_ascii_case("yes") {
+                            app.delete_all_trades(conn);
+                        } else {
+                            app.status_message = Some("Delete cancelled".to_string());
+                        }
+                        app.delete_confirmation = None;
+                    }
+                    KeyCode::Esc => app.delete_confirmation = None,
+                    KeyCode::Backspace => {
+                        typed.pop();
+                    }
+                    KeyCode::Char(c) => typed.push(c),
+                    _ => {}
+                }
+                continue;
+            }
+
+            if let Some((_, note)) = &mut app.note_input {
+                match key.code {
+                    KeyCode::Enter => app.save_note(conn),
+                    KeyCode::Esc => app.note_input = None,
+                    KeyCode::Backspace => {
+                        note.pop();
+                    }
+                    KeyCode::Char(c) if note.chars().count() < NOTE_MAX_CHARS => note.push(c),
+                    _ => {}
+                }
+                continue;
+            }
+
+            if let Some(palette) = &mut app.palette {
+                match key.code {
+                    KeyCode::Enter => {
+                        let chosen = palette.chosen();
+                        app.palette = None;
+                        let fitting_trade_rows = terminal.size().map_or(0, |s| app.trade_rows_for_height(s.height));
+                        match chosen.map(|(entry, argument)| (entry.command, argument)) {
+                            Some((palette::Command::Run(action), _)) => {
+                                if perform_action(&mut app, action, fitting_trade_rows, conn) {
+                                    return Ok(app);
+                                }
+                            }
+                            Some((command, argument)) => app.run_palette_command(command, &argument, conn),
+                            None => app.status_message = Some("No command matches".to_string()),
+                        }
+                    }
+                    KeyCode::Esc => app.palette = None,
+                    KeyCode::Up => palette.move_selection(-1),
+                    KeyCode::Down => palette.move_selection(1),
+                    KeyCode::Tab => palette.complete(),
+                    KeyCode::Backspace => palette.pop(),
+                    KeyCode::Char(c) => palette.push(c),
+                    _ => {}
+                }
+                continue;
+            }
+            if app.show_help {
+                app.show_help = false;
+                continue;
+            }
+            if app.show_stats {
+                app.show_stats = false;
+                continue;
+            }
+            if app.show_recent_coins {
+                app.show_recent_coins = false;
+                if let KeyCode::Char(c) = key.code
+                    && let Some(number) = c.to_digit(10)
+                {
+                    app.switch_to_recent_coin(number as usize, conn);
+                }
+                continue;
+            }
+            if app.blocklist_selected.is_some() {
+                let last = app.blocklist_entries().len().saturating_sub(1);
+                match (key.code, app.keymap.action_for(&key)) {
+                    (KeyCode::Down, _) | (_, Some(Action::SelectDown)) => {
+                        app.blocklist_selected = app.blocklist_selected.map(|i| (i + 1).min(last));
+                    }
+                    (KeyCode::Up, _) | (_, Some(Action::SelectUp)) => {
+                        app.blocklist_selected = app.blocklist_selected.map(|i| i.saturating_sub(1));
+                    }
+                    (_, Some(Action::RemoveEntry)) => app.unblock_selected(conn),
+                    _ => app.blocklist_selected = None,
+                }
+                continue;
+            }
+            if app.bookmarks_selected.is_some() {
+                let last = app.bookmarks.len().saturating_sub(1);
+                match (key.code, app.keymap.action_for(&key)) {
+                    (KeyCode::Down, _) | (_, Some(Action::SelectDown)) => {
+                        app.bookmarks_selected = app.bookmarks_selected.map(|i| (i + 1).min(last));
+                    }
+                    (KeyCode::Up, _) | (_, Some(Action::SelectUp)) => {
+                        app.bookmarks_selected = app.bookmarks_selected.map(|i| i.saturating_sub(1));
+                    }
+                    (KeyCode::Enter, _) | (_, Some(Action::Submit)) => {
+                        let fitting_trade_rows = terminal.size().map_or(0, |s| app.trade_rows_for_height(s.height));
+                        app.jump_to_selected_bookmark(fitting_trade_rows, conn);
+                    }
+                    (_, Some(Action::RemoveEntry)) => app.remove_selected_bookmark(conn),
+                    _ => app.bookmarks_selected = None,
+                }
+                continue;
+            }
+            if app.show_trade_details
+                && (key.code == KeyCode::Esc || app.keymap.action_for(&key) == Some(Action::ShowDetails))
+            {
+                app.show_trade_details = false;
+                continue;
+            }
+
+            match app.input_mode {
+                InputMode::Normal => {
+                    let Some(action) = app.keymap.action_for(&key) else {
+                        continue;
+                    };
+                    let fitting_trade_rows = terminal.size().map_or(0, |s| app.trade_rows_for_height(s.height));
+                    if perform_action(&mut app, action, fitting_trade_rows, conn) {
+                        return Ok(app);
+                    }
+                }
+                InputMode::Editing => match key.code {
+                    KeyCode::Char('w') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.delete_word();
+                        app.apply_live_search(conn);
+                    }
+                    KeyCode::Char('a') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.move_cursor_start();
+                    }
+                    KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.move_cursor_end();
+                    }
+                    KeyCode::Left if key.modifiers.contains(KeyModifiers::ALT) => {
+                        app.move_cursor_word_left();
+                    }
+                    KeyCode::Right if key.modifiers.contains(KeyModifiers::ALT) => {
+                        app.move_cursor_word_right();
+                    }
+                    KeyCode::Enter => {
+                        app.submit(conn);
+                        app.input_mode = InputMode::Normal;
+                    }
+                    KeyCode::Char(c) => {
+                        app.enter_char(c);
+                        app.apply_live_search(conn);
+                    }
+                    KeyCode::Backspace => {
+                        app.delete_char();
+                        app.apply_live_search(conn);
+                    }
+                    KeyCode::Left => {
+                        app.move_cursor_left();
+                    }
+                    KeyCode::Right => {
+                        app.move_cursor_right();
+                    }
+                    KeyCode::Esc => {
+                        app.cancel_editing();
+                        if app.live_search {
+                            app.refresh_price_history(conn);
+                        }
+                    }
+                    KeyCode::Home => {
+                        app.move_cursor_start();
+                    }
+                    KeyCode::End => {
+                        app.move_cursor_end();
+                    }
+                    _ => {}
+                },
+                InputMode::BalanceFilter => match key.code {
+                    KeyCode::Enter => app.input_mode = InputMode::Normal,
+                    KeyCode::Esc => {
+                        app.balance_filter.clear();
+                        app.input_mode = InputMode::Normal;
+                    }
+                    KeyCode::Backspace => {
+                        app.balance_filter.pop();
+                    }
+                    KeyCode::Char(c) => app.balance_filter.push(c),
+                    _ => {}
+                },
+            }
+        }
+    }
+    Ok(app)
+}
+
+fn ui(f: &mut Frame, app: &mut App) {
+    let size = f.size();
+    let min = app.min_terminal_size;
+    if size.width < min.width || size.height < min.height {
+        draw_too_small(f, app, size);
+        return;
+    }
+
+    let main_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints(
+            [
+                Constraint::Length(3), 
+                Constraint::Min(0),    
+                Constraint::Length(1 + app.show_legend as u16),
+            ]
+            .as_ref(),
+        )
+        .split(f.size());
+
+    let current_search_mode_hint = match app.input_mode {
+        InputMode::Normal => format!(
+            "(Press '{}' to edit, Enter to search)",
+            app.keymap.keys_for(Action::Search).join("' or '")
+        ),
+        InputMode::Editing => "(ESC to cancel, Enter to search)".to_string(),
+        InputMode::BalanceFilter => "(Filtering balances: Enter to keep, ESC to clear)".to_string(),
+    };
+    
+    let search_title_base = if let Some(symbol) = &app.active_search_symbol {
+        format!("Searching: {}", symbol)
+    } else {
+        "Search Symbol".to_string()
+    };
+    let help_keys = app.keymap.keys_for(Action::Help);
+    let search_title = match help_keys.first() {
+        Some(key) => format!("{} {} ({} for keys)", search_title_base, current_search_mode_hint, key),
+        None => format!("{} {}", search_title_base, current_search_mode_hint),
+    };
+
+    let (search_state, search_state_color) = if app.search_input == app.last_submitted_search {
+        ("applied", app.theme.positive)
+    } else if app.live_search && app.input_mode == InputMode::Editing && app.is_live_searchable() {
+        ("live", app.theme.info)
+    } else {
+        ("unsaved", app.theme.accent)
+    };
+    let input_block = Block::default().borders(Borders::ALL);
+    let input_block = if app.search_input.is_empty() && app.last_submitted_search.is_empty() {
+        input_block.title(search_title)
+    } else {
+        input_block
+            .title(format!("{} [{}]", search_title, search_state))
+            .border_style(fg_style(search_state_color, app.use_color))
+    };
+    let input_paragraph = Paragraph::new(app.search_input.as_str())
+        .block(input_block)
+        .wrap(Wrap { trim: true });
+    f.render_widget(input_paragraph, main_chunks[0]);
+
+    match app.input_mode {
+        InputMode::Editing => {
+            f.set_cursor(
+                main_chunks[0].x + app.cursor_position as u16 + 1,
+                main_chunks[0].y + 1,
+            )
+        }
+        InputMode::Normal | InputMode::BalanceFilter => {
+        }
+    }
+    
+    let mut footer_lines = vec![filter_summary_line(app)];
+    if app.show_legend {
+        footer_lines.push(tier_legend_line(app));
+    }
+    f.render_widget(Paragraph::new(footer_lines), main_chunks[2]);
+
+    let content_area = if app.pinned.is_empty() {
+        main_chunks[1]
+    } else {
+        let pinned_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(app.watchlist_height()), Constraint::Min(0)].as_ref())
+            .split(main_chunks[1]);
+        draw_watchlist(f, app, pinned_chunks[0]);
+        pinned_chunks[1]
+    };
+    if app.view_mode == ViewMode::Coins {
+        draw_coins_overview(f, app, content_area);
+        return;
+    }
+    if app.view_mode == ViewMode::DbResults {
+        draw_db_results(f, app, main_chunks[1]);
+        return;
+    }
+    if app.view_mode == ViewMode::Compare {
+        draw_compare(f, app, content_area);
+        return;
+    }
+
     let visible_trades = app.get_visible_trades(); 
     
     let mut trades_display_block_title = if let Some(symbol) = &app.active_search_symbol {
-        format!("Trades for {}", symbol)
+        match app.search_mode {
+            SearchMode::Exact => format!("Trades for {}", symbol),
+            SearchMode::Substring => format!("Trades matching *{}*", symbol),
+            SearchMode::Fuzzy => format!("Trades for coins like ~{}", symbol),
+        }
     } else {
         "Trades".to_string() 
     };
 
     let type_filter_display_name = match app.trade_type_filter.as_deref() {
-        Some("all-trades") => "all-trades".to_string(),
         Some(filter_type) => filter_type.to_string(),
         None => "All".to_string(),
     };
     trades_display_block_title = format!("{} (Type: {})", trades_display_block_title, type_filter_display_name);
+    if app.watchlist_only {
+        trades_display_block_title = format!("{} [watchlist only]", trades_display_block_title);
+    }
+    if let Some(preset) = &app.active_preset {
+        trades_display_block_title = format!("{} [preset: {}]", trades_display_block_title, preset);
+    }
+    if app.tape_sort == TapeSort::Total {
+        trades_display_block_title = format!("{} [largest first]", trades_display_block_title);
+    }
+    let visible_total = visible_trades.iter().fold(0.0, |sum, trade| sum + trade.total_value);
+    trades_display_block_title = format!(
+        "{} - {} trades, ${}",
+        trades_display_block_title,
+        visible_trades.len(),
+        format_compact(visible_total)
+    );
+
+
+    if let Some(symbol) = app.focused_coin() {
+        let side_by_side_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(65), Constraint::Percentage(35)].as_ref())
+            .split(content_area);
+
+        draw_trades_table(f, app, &visible_trades, side_by_side_chunks[0], &trades_display_block_title);
+
+        let side_panel_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(6), Constraint::Length(8), Constraint::Min(0)].as_ref())
+            .split(side_by_side_chunks[1]);
+        draw_price_sparkline(f, app, &symbol, &app.price_history, side_panel_chunks[0]);
+        let focused_trades: Vec<Trade> = visible_trades
+            .iter()
+            .filter(|t| symbols::normalize(&t.coin_symbol) == symbol)
+            .cloned()
+            .collect();
+        draw_trade_size_histogram(f, app, &focused_trades, side_panel_chunks[1]);
+
+        let matched_coins = app.matched_coins();
+        let focus_position = match matched_coins.iter().position(|c| *c == symbol) {
+            Some(index) if matched_coins.len() > 1 => {
+                format!(" ({}/{}, {} to cycle)", index + 1, matched_coins.len(), app.key_hint(Action::FocusNext))
+            }
+            _ => String::new(),
+        };
+        let user_coin_balances = app.coin_balances(&symbol);
+        let user_filter_hint = if app.input_mode == InputMode::BalanceFilter {
+            format!(" user~{}_ ({} matches)", app.balance_filter, user_coin_balances.len())
+        } else if !app.balance_filter.is_empty() {
+            format!(" user~{} ({} matches)", app.balance_filter, user_coin_balances.len())
+        } else {
+            String::new()
+        };
+        let balances_block = Block::default()
+            .title(format!(
+                "Balances for {}{}{} [{}, {} to sort, {} to filter]",
+                symbol,
+                focus_position,
+                user_filter_hint,
+                app.balance_sort.label(),
+                app.key_hint(Action::CycleBalanceSort),
+                app.key_hint(Action::FilterBalances)
+            ))
+            .borders(Borders::ALL);
+
+        let header_cells = ["User", "Balance"]
+            .iter()
+            .map(|h| Cell::from(*h).style(fg_style(app.theme.accent, app.use_color).add_modifier(Modifier::BOLD)));
+        let header = Row::new(header_cells).height(1).bottom_margin(1);
+
+        let rows = user_coin_balances.iter().map(|(username, balance)| {
+            Row::new(vec![
+                Cell::from(username.as_str()),
+                Cell::from(format_value(*balance)),
+            ])
+        });
+
+        let balance_table = Table::new(
+                rows,
+                [Constraint::Percentage(60), Constraint::Percentage(40)]
+            )
+            .header(header)
+            .block(balances_block)
+            .widths([Constraint::Percentage(70), Constraint::Percentage(30)]);
+
+        f.render_widget(balance_table, side_panel_chunks[2]);
+
+    } else if let Some(user) = app.user_filter.clone() {
+        let side_by_side_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(65), Constraint::Percentage(35)].as_ref())
+            .split(content_area);
+        draw_trades_table(f, app, &visible_trades, side_by_side_chunks[0], &trades_display_block_title);
+        draw_user_panel(f, app, &user, side_by_side_chunks[1]);
+    } else {
+        draw_trades_table(f, app, &visible_trades, content_area, &trades_display_block_title);
+    }
+
+    if app.show_trade_details {
+        draw_trade_details(f, app);
+    }
+    if let Some((_, note)) = &app.note_input {
+        draw_note_prompt(f, note, app);
+    }
+    if let Some(typed) = &app.delete_confirmation {
+        draw_delete_confirmation(f, typed, app);
+    }
+    if app.show_help {
+        draw_help(f, app);
+    }
+    if app.show_recent_coins {
+        draw_recent_coins(f, app);
+    }
+    if let Some(selected) = app.blocklist_selected {
+        draw_blocklist(f, app, selected);
+    }
+    if let Some(selected) = app.bookmarks_selected {
+        draw_bookmarks(f, app, selected);
+    }
+    if app.show_stats {
+        draw_session_stats(f, app);
+    }
+    if let Some(palette) = &app.palette {
+        draw_palette(f, palette, app);
+    }
+}
+
+fn draw_recent_coins(f: &mut Frame, app: &App) {
+    let key_style = fg_style(app.theme.accent, app.use_color).add_modifier(Modifier::BOLD);
+    let mut lines: Vec<Line> = app
+        .recent_coins
+        .iter()
+        .enumerate()
+        .map(|(index, coin)| Line::from(vec![Span::styled(format!("{} ", index + 1), key_style), Span::raw(coin.clone())]))
+        .collect();
+    if lines.is_empty() {
+        lines.push(Line::from(Span::styled("No coins searched yet", fg_style(app.theme.muted, app.use_color))));
+    }
+    let area = centered_rect(40, lines.len() as u16 + 2, f.size());
+    let block = Block::default()
+        .title("Recent coins (1-9, any other key closes)")
+        .borders(Borders::ALL);
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn draw_blocklist(f: &mut Frame, app: &App, selected: usize) {
+    let label_style = fg_style(app.theme.muted, app.use_color);
+    let mut lines: Vec<Line> = app
+        .blocklist_entries()
+        .into_iter()
+        .enumerate()
+        .map(|(index, (coin, value))| {
+            let style = if index == selected {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            };
+            let kind = if coin { "coin " } else { "user " };
+            Line::from(vec![Span::styled(kind, label_style), Span::styled(value, style)])
+        })
+        .collect();
+    if lines.is_empty() {
+        let hint = format!(
+            "Nothing hidden: {} hides a user, {} a coin",
+            app.key_hint(Action::BlockUser),
+            app.key_hint(Action::BlockCoin)
+        );
+        lines.push(Line::from(Span::styled(hint, label_style)));
+    }
+    let area = centered_rect(50, (lines.len() as u16).min(20) + 2, f.size());
+    // Keep the selected entry in view on long lists.
+    let scroll = (selected + 1).saturating_sub(area.height.saturating_sub(2) as usize) as u16;
+    let title = format!(
+        "Hidden ({}/{}, {} to unhide, any other key closes)",
+        app.key_hint(Action::SelectDown),
+        app.key_hint(Action::SelectUp),
+        app.key_hint(Action::RemoveEntry)
+    );
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL);
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(block).scroll((scroll, 0)), area);
+}
+
+fn draw_bookmarks(f: &mut Frame, app: &App, selected: usize) {
+    let label_style = fg_style(app.theme.muted, app.use_color);
+    let mut lines: Vec<Line> = app
+        .bookmarks
+        .iter()
+        .enumerate()
+        .map(|(index, trade)| {
+            let style = if index == selected {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            };
+            let summary = format!(
+                "{} {} {} ${} {} ",
+                app.display_time(trade.recorded_at, &trade.timestamp),
+                trade.action,
+                trade.coin_symbol,
+                format_value(trade.total_value),
+                trade.username
+            );
+            let note = trade.row_id.and_then(|row_id| app.notes.get(&row_id)).cloned().unwrap_or_default();
+            Line::from(vec![Span::styled(summary, label_style), Span::styled(note, style)])
+        })
+        .collect();
+    if lines.is_empty() {
+        let hint = format!("No bookmarks: {} notes the selected trade", app.key_hint(Action::EditNote));
+        lines.push(Line::from(Span::styled(hint, label_style)));
+    }
+    let area = centered_rect(70, (lines.len() as u16).min(20) + 2, f.size());
+    // Keep the selected entry in view on long lists.
+    let scroll = (selected + 1).saturating_sub(area.height.saturating_sub(2) as usize) as u16;
+    let title = format!(
+        "Bookmarks ({}/{}, Enter to jump, {} to remove, any other key closes)",
+        app.key_hint(Action::SelectDown),
+        app.key_hint(Action::SelectUp),
+        app.key_hint(Action::RemoveEntry)
+    );
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL);
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(block).scroll((scroll, 0)), area);
+}
+
+const PALETTE_MAX_MATCHES: usize = 10;
+
+fn draw_palette(f: &mut Frame, palette: &Palette, app: &App) {
+    let matches = palette.matches();
+    // Keep the highlighted entry in view when it's past the first page.
+    let first = (palette.selected + 1).saturating_sub(PALETTE_MAX_MATCHES);
+    let mut lines = vec![Line::from(vec![
+        Span::styled(":", fg_style(app.theme.accent, app.use_color).add_modifier(Modifier::BOLD)),
+        Span::raw(palette.input.clone()),
+    ])];
+    for (index, entry) in matches.iter().enumerate().skip(first).take(PALETTE_MAX_MATCHES) {
+        let style = if index == palette.selected {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("{:<22}", entry.name), style.add_modifier(Modifier::BOLD)),
+            Span::styled(format!("{} ", entry.args), fg_style(app.theme.muted, app.use_color)),
+            Span::raw(entry.description),
+        ]));
+    }
+    if matches.is_empty() {
+        lines.push(Line::from(Span::styled("No matching command", fg_style(app.theme.muted, app.use_color))));
+    }
+
+    let area = centered_rect(70, PALETTE_MAX_MATCHES as u16 + 3, f.size());
+    let block = Block::default()
+        .title("Command (Enter to run, Tab to complete, Esc to close)")
+        .borders(Borders::ALL);
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// The effective keymap, so remapped keys show up as configured.
+fn draw_help(f: &mut Frame, app: &App) {
+    let key_style = fg_style(app.theme.accent, app.use_color).add_modifier(Modifier::BOLD);
+    let lines: Vec<Line> = Action::ALL
+        .into_iter()
+        .map(|action| {
+            let keys = app.keymap.keys_for(action);
+            let keys = if keys.is_empty() { "(unbound)".to_string() } else { keys.join(", ") };
+            Line::from(vec![
+                Span::styled(format!("{:<14} ", keys), key_style),
+                Span::raw(action.description()),
+            ])
+        })
+        .collect();
+
+    let area = centered_rect(70, lines.len() as u16 + 2, f.size());
+    let block = Block::default()
+        .title("Keys (any key to close)")
+        .borders(Borders::ALL);
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn draw_delete_confirmation(f: &mut Frame, typed: &str, app: &App) {
+    let lines = vec![
+        Line::from(Span::styled(
+            "Delete ALL trades from the database?",
+            fg_style(app.theme.negative, app.use_color).add_modifier(Modifier::BOLD),
+        )),
+        Line::from("A backup copy of the database is written first."),
+        Line::from("Type \"yes\" and press Enter to confirm, Esc to cancel."),
+        Line::from(""),
+        Line::from(vec![Span::raw("> "), Span::raw(typed.to_string())]),
+    ];
+    let area = centered_rect(60, lines.len() as u16 + 2, f.size());
+    let block = Block::default().title("Delete all trades").borders(Borders::ALL);
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(block).wrap(Wrap { trim: false }), area);
+}
+
+fn draw_note_prompt(f: &mut Frame, note: &str, app: &App) {
+    let lines = vec![
+        Line::from(Span::styled(
+            format!("Enter to save, Esc to cancel. Clear it to remove the note ({} chars max).", NOTE_MAX_CHARS),
+            fg_style(app.theme.muted, app.use_color),
+        )),
+        Line::from(""),
+        Line::from(vec![Span::raw("> "), Span::raw(note.to_string()), Span::raw("_")]),
+    ];
+    let area = centered_rect(60, wrapped_popup_height(&lines, 60, f.size()), f.size());
+    let block = Block::default().title("Trade note").borders(Borders::ALL);
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(block).wrap(Wrap { trim: false }), area);
+}
+
+fn draw_db_results(f: &mut Frame, app: &App, area: Rect) {
+    let Some((query, trades)) = &app.db_results else {
+        return;
+    };
+    let header_cells = app
+        .columns
+        .iter()
+        .map(|&c| Cell::from(app.column_header(c)).style(fg_style(app.theme.accent, app.use_color).add_modifier(Modifier::BOLD)));
+    let header = Row::new(header_cells).height(1).bottom_margin(1);
+    let visible_row_count = area.height.saturating_sub(4) as usize;
+    let cell_widths = app.rendered_column_widths(&app.columns, area);
+    let rows = trades
+        .iter()
+        .skip(app.db_results_scroll)
+        .take(visible_row_count)
+        .map(|trade| {
+            Row::new(app.columns.iter().zip(&cell_widths).map(|(c, &width)| c.cell(trade, app, width)))
+                .style(tier_row_style(app, trade, app.color_mode))
+        });
+    let widths: Vec<Constraint> = app.columns.iter().map(|&c| app.column_width(c)).collect();
+    let limit_note = if trades.len() >= db_search::RESULT_LIMIT {
+        format!(", newest {} shown", db_search::RESULT_LIMIT)
+    } else {
+        String::new()
+    };
+    let title = format!(
+        "DB search: {} ({} trades{}) - Esc to go back",
+        if query.is_empty() { "everything" } else { query },
+        trades.len(),
+        limit_note
+    );
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(Block::default().title(title).borders(Borders::ALL));
+    f.render_widget(table, area);
+}
+
+/// Green for coins trading right now, fading to dark gray as they go quiet.
+/// Coins with no dated trade yet count as stale.
+fn activity_style(app: &App, last_trade_at: Option<i64>, now: i64) -> Style {
+    let thresholds = app.activity_thresholds;
+    let color = match last_trade_at.map(|at| now - at) {
+        Some(age) if age <= thresholds.active_secs => app.theme.activity[0],
+        Some(age) if age <= thresholds.recent_secs => app.theme.activity[1],
+        Some(age) if age <= thresholds.stale_secs => app.theme.activity[2],
+        _ => app.theme.activity[3],
+    };
+    fg_style(color, app.use_color)
+}
+
+fn draw_coins_overview(f: &mut Frame, app: &mut App, area: Rect) {
+    let header_cells = [
+        "Coin",
+        "Trades",
+        "Net Volume",
+        "Buy Volume",
+        "Sell Volume",
+        "Last Price",
+        "Last Trade",
+        "Since Viewed",
+    ]
+        .iter()
+        .map(|h| Cell::from(*h).style(fg_style(app.theme.accent, app.use_color).add_modifier(Modifier::BOLD)));
+    let header = Row::new(header_cells).height(1).bottom_margin(1);
+
+    app.selected_coin = app.selected_coin.min(app.coins_for_view().len().saturating_sub(1));
+    let app = &*app;
+    let now = Utc::now().timestamp();
+    let coins = app.coins_for_view();
+    // Scroll just enough to keep the selected coin in view.
+    let visible_row_count = area.height.saturating_sub(4) as usize;
+    let start = (app.selected_coin + 1).saturating_sub(visible_row_count);
+
+    let rows: Vec<Row> = coins
+        .iter()
+        .enumerate()
+        .skip(start)
+        .take(visible_row_count)
+        .map(|(index, (_, stats))| {
+            let net_color = signed_color(app, stats.net_volume());
+            let row = Row::new(vec![
+                Cell::from(stats.display_symbol.as_str()).style(Style::default().add_modifier(Modifier::BOLD)),
+                Cell::from(stats.trade_count.to_string()),
+                Cell::from(format!("{:+.2}", stats.net_volume())).style(fg_style(net_color, app.use_color)),
+                Cell::from(format_value(stats.buy_volume)),
+                Cell::from(format_value(stats.sell_volume)),
+                Cell::from(format_price(stats.last_price)),
+                Cell::from(match &stats.last_trade_time {
+                    Some(time) => app.display_time(stats.last_trade_at, time),
+                    None => "-".to_string(),
+                }),
+                Cell::from(stats.since_viewed()),
+            ]);
+            let row_style = activity_style(app, stats.last_trade_at, now);
+            if index == app.selected_coin {
+                row.style(row_style.add_modifier(Modifier::REVERSED))
+            } else {
+                row.style(row_style)
+            }
+        })
+        .collect();
+
+    let widths = [
+        Constraint::Length(10),
+        Constraint::Length(8),
+        Constraint::Length(14),
+        Constraint::Length(14),
+        Constraint::Length(14),
+        Constraint::Length(14),
+        Constraint::Length(10),
+        Constraint::Min(12),
+    ];
+    let title = format!(
+        "Coins ({}){} - {}/{} to select, {} to open, {} for the tape",
+        coins.len(),
+        if app.directional_only { " [directional only]" } else { "" },
+        app.key_hint(Action::SelectDown),
+        app.key_hint(Action::SelectUp),
+        app.key_hint(Action::Submit),
+        app.key_hint(Action::ToggleView)
+    );
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(Block::default().title(title).borders(Borders::ALL));
+    f.render_widget(table, area);
+}
+
+/// Both compared coins side by side, stacked on narrow terminals, or a
+/// notice when there's room for neither.
+fn draw_compare(f: &mut Frame, app: &App, area: Rect) {
+    let Some(compare) = &app.compare else {
+        return;
+    };
+    let direction = if area.width >= COMPARE_SIDE_BY_SIDE_MIN_WIDTH {
+        Direction::Horizontal
+    } else if area.height >= 2 * COMPARE_MIN_PANE_HEIGHT {
+        Direction::Vertical
+    } else {
+        let notice = Paragraph::new(format!(
+            "Too small to compare: need {} columns, or {} rows to stack. Esc to go back.",
+            COMPARE_SIDE_BY_SIDE_MIN_WIDTH,
+            2 * COMPARE_MIN_PANE_HEIGHT
+        ))
+        .wrap(Wrap { trim: true })
+        .block(Block::default().title("Compare").borders(Borders::ALL));
+        f.render_widget(notice, area);
+        return;
+    };
+    let panes = Layout::default()
+        .direction(direction)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(area);
+    for (compared, pane) in compare.iter().zip(panes.iter()) {
+        draw_compare_pane(f, app, compared, *pane);
+    }
+}
+
+/// One coin in the compare view: its stats, price chart and newest trades.
+fn draw_compare_pane(f: &mut Frame, app: &App, compared: &ComparedCoin, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Length(6), Constraint::Min(0)].as_ref())
+        .split(area);
+
+    let symbol = compared.symbol.as_str();
+    let stats_line = match app.coin_stats.get(symbol) {
+        Some(stats) => {
+            let change = match stats.session_change_pct() {
+                Some(pct) => Span::styled(format!(" ({:+.2}%)", pct), fg_style(signed_color(app, pct), app.use_color)),
+                None => Span::raw(""),
+            };
+            Line::from(vec![
+                Span::raw(format!("Last {}", format_price(stats.last_price))),
+                change,
+                Span::raw(format!("  5m ${}  Net ", format_compact(stats.recent_volume()))),
+                Span::styled(
+                    format!("{:+.2}", stats.net_volume()),
+                    fg_style(signed_color(app, stats.net_volume()), app.use_color),
+                ),
+                Span::raw(format!("  {} trades", stats.trade_count)),
+            ])
+        }
+        None => Line::from("No trades yet"),
+    };
+    f.render_widget(
+        Paragraph::new(stats_line).block(Block::default().title(symbol.to_string()).borders(Borders::ALL)),
+        chunks[0],
+    );
+
+    draw_price_sparkline(f, app, symbol, &compared.price_history, chunks[1]);
+
+    let trades: Vec<&Trade> = app
+        .all_trades
+        .iter()
+        .filter(|t| symbols::same(&t.coin_symbol, symbol) && !app.blocked_users.contains(&t.username.to_lowercase()))
+        .collect();
+    let header_cells = app
+        .columns
+        .iter()
+        .map(|&c| Cell::from(app.column_header(c)).style(fg_style(app.theme.accent, app.use_color).add_modifier(Modifier::BOLD)));
+    let header = Row::new(header_cells).height(1).bottom_margin(1);
+    let visible_row_count = chunks[2].height.saturating_sub(4) as usize;
+    let cell_widths = app.rendered_column_widths(&app.columns, chunks[2]);
+    let rows = trades.iter().take(visible_row_count).map(|trade| {
+        Row::new(app.columns.iter().zip(&cell_widths).map(|(c, &width)| c.cell(trade, app, width))).style(tier_row_style(app, trade, app.color_mode))
+    });
+    let widths: Vec<Constraint> = app.columns.iter().map(|&c| app.column_width(c)).collect();
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(Block::default().title(format!("Trades for {} - {}", symbol, trades.len())).borders(Borders::ALL));
+    f.render_widget(table, chunks[2]);
+}
+
+/// Stands in for the whole layout until the terminal is resized to at
+/// least `min_terminal_size`.
+fn draw_too_small(f: &mut Frame, app: &App, area: Rect) {
+    let min = app.min_terminal_size;
+    let lines = vec![
+        Line::from(Span::styled(
+            "Terminal too small",
+            fg_style(app.theme.negative, app.use_color).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(format!("need \u{2265} {}x{}, have {}x{}", min.width, min.height, area.width, area.height)),
+    ];
+    let height = (lines.len() as u16).min(area.height);
+    let message = Rect {
+        y: area.y + (area.height - height) / 2,
+        height,
+        ..area
+    };
+    f.render_widget(Paragraph::new(lines).alignment(Alignment::Center).wrap(Wrap { trim: true }), message);
+}
+
+/// Rows `lines` take word-wrapped in a bordered popup `percent_x` wide,
+/// borders included.
+fn wrapped_popup_height(lines: &[Line], percent_x: u16, area: Rect) -> u16 {
+    let inner_width = (area.width * percent_x / 100).saturating_sub(2).max(1);
+    let rows = Paragraph::new(lines.to_vec()).wrap(Wrap { trim: false }).line_count(inner_width);
+    rows as u16 + 2
+}
+
+/// A `percent_x` by `height` rect centred in `area`.
+fn centered_rect(percent_x: u16, height: u16, area: Rect) -> Rect {
+    let width = area.width * percent_x / 100;
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+/// `3725s` as `1h 02m 05s`.
+fn format_uptime(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{}h {:02}m {:02}s", hours, minutes, seconds)
+    } else {
+        format!("{}m {:02}s", minutes, seconds)
+    }
+}
+
+/// Totals over the trades in the display buffer. Redrawn with
+/// each frame, so it keeps counting while open.
+/// Everything here covers only trades received since startup.
+fn draw_session_stats(f: &mut Frame, app: &App) {
+    let session = &app.session;
+    let busiest = match session.busiest_coin() {
+        Some((symbol, count)) => format!("{} ({} trades)", symbol, count),
+        None => "-".to_string(),
+    };
+    let largest = match &app.largest_trade {
+        Some(trade) => format!(
+            "${} {} {} by {}",
+            format_value(trade.total_value),
+            trade.action,
+            trade.coin_symbol,
+            trade.username
+        ),
+        None => "-".to_string(),
+    };
+    let (buys, sells) = (session.buys, session.sells);
+    let ratio = if sells > 0 {
+        format!("{:.2} ({} buys / {} sells)", buys as f64 / sells as f64, buys, sells)
+    } else {
+        format!("- ({} buys / {} sells)", buys, sells)
+    };
+
+    let label_style = fg_style(app.theme.accent, app.use_color).add_modifier(Modifier::BOLD);
+    let fields = [
+        ("Trades", session.trades.to_string()),
+        ("Volume", format!("${}", format_compact(session.volume))),
+        ("Coins", session.coins.len().to_string()),
+        ("Users", session.users.len().to_string()),
+        ("Busiest", busiest),
+        ("Largest", largest),
+        ("Buy/sell", ratio),
+        ("Uptime", format_uptime(app.started_at.elapsed())),
+    ];
+    let lines: Vec<Line> = fields
+        .into_iter()
+        .map(|(label, value)| {
+            Line::from(vec![
+                Span::styled(format!("{:<10} ", label), label_style),
+                Span::raw(value),
+            ])
+        })
+        .collect();
+
+    let area = centered_rect(60, lines.len() as u16 + 2, f.size());
+    let block = Block::default()
+        .title("Session stats (any key closes)")
+        .borders(Borders::ALL);
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(block).wrap(Wrap { trim: false }), area);
+}
+
+fn draw_trade_details(f: &mut Frame, app: &App) {
+    let Some(trade) = app.selected_trade() else {
+        return;
+    };
+    let label_style = fg_style(app.theme.accent, app.use_color).add_modifier(Modifier::BOLD);
+    let fields = [
+        ("Time", match (trade.recorded_at, timestamp_millis(&trade.timestamp)) {
+            (Some(secs), Some(millis)) => {
+                app.format_unix_millis(secs.saturating_mul(1000) + i64::from(millis), "%Y-%m-%d %H:%M:%S%.3f %Z")
+            }
+            (Some(secs), None) => app.format_unix_time(secs, "%Y-%m-%d %H:%M:%S %Z"),
+            (None, _) => trade.timestamp.clone(),
+        }),
+        ("Type", trade.trade_type_val.clone()),
+        ("Action", trade.action.clone()),
+        ("User", trade.username.clone()),
+        ("Coin", trade.coin_symbol.clone()),
+        ("Amount", format_adaptive(trade.amount, 8)),
+        ("Total USD", format_value(trade.total_value)),
+        ("Price USD", format_price(trade.price)),
+        ("Trade ID", trade.id.clone().unwrap_or_else(|| "-".to_string())),
+    ];
+    let note = trade.row_id.and_then(|row_id| app.notes.get(&row_id));
+    let lines: Vec<Line> = fields
+        .into_iter()
+        .chain(note.map(|note| ("Note", note.clone())))
+        .map(|(label, value)| {
+            Line::from(vec![
+                Span::styled(format!("{:<10} ", label), label_style),
+                Span::raw(value),
+            ])
+        })
+        .collect();
+
+    let area = centered_rect(60, wrapped_popup_height(&lines, 60, f.size()), f.size());
+    let block = Block::default()
+        .title("Trade details (Esc to close)")
+        .borders(Borders::ALL);
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(block).wrap(Wrap { trim: false }), area);
+}
+
+fn draw_watchlist(f: &mut Frame, app: &mut App, area: Rect) {
+    let now = Instant::now();
+    for stats in app.coin_stats.values_mut() {
+        stats.expire(now);
+    }
+
+    let header_cells = ["Coin", "Last Price", "Session %", "Vol 5m", "Last Trade", "Since Viewed"]
+        .iter()
+        .map(|h| Cell::from(*h).style(fg_style(app.theme.accent, app.use_color).add_modifier(Modifier::BOLD)));
+    let header = Row::new(header_cells).height(1);
+
+    // Borders plus header; a short terminal can fit fewer than the usual
+    // rows, and whatever doesn't fit is counted on the bottom border.
+    let shown = (area.height.saturating_sub(3) as usize).min(WATCHLIST_MAX_ROWS);
+    let hidden = app.pinned.len().saturating_sub(shown);
+    let rows = app.pinned.iter().take(shown).map(|symbol| {
+        let stats = app.coin_stats.get(symbol);
+        let last_price = stats.map_or("-".to_string(), |s| format_price(s.last_price));
+        let recent_volume = stats.map_or("-".to_string(), |s| format!("${}", format_compact(s.recent_volume())));
+        let last_trade = match stats.and_then(|s| s.last_trade_time.as_ref().map(|time| (s.last_trade_at, time))) {
+            Some((recorded_at, time)) => app.display_time(recorded_at, time),
+            None => "-".to_string(),
+        };
+        let change_cell = match stats.and_then(CoinStats::session_change_pct) {
+            Some(change) => {
+                let color = signed_color(app, change);
+                Cell::from(format!("{:+.2}%", change)).style(fg_style(color, app.use_color))
+            }
+            None => Cell::from("-"),
+        };
+        Row::new(vec![
+            Cell::from(symbol.as_str()).style(Style::default().add_modifier(Modifier::BOLD)),
+            Cell::from(last_price),
+            change_cell,
+            Cell::from(recent_volume),
+            Cell::from(last_trade),
+            Cell::from(stats.map_or("-".to_string(), CoinStats::since_viewed)),
+        ])
+    });
+
+    let widths = [
+        Constraint::Length(10),
+        Constraint::Length(16),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Min(12),
+    ];
+    let block = Block::default().title("Watchlist").borders(Borders::ALL);
+    let block = if hidden > 0 {
+        block.title_bottom(format!(" +{} more pinned ", hidden))
+    } else {
+        block
+    };
+    let table = Table::new(rows, widths).header(header).block(block);
+    f.render_widget(table, area);
+}
+
+/// Aggregate stats and per-coin balances for the followed user.
+fn draw_user_panel(f: &mut Frame, app: &App, username: &str, area: Rect) {
+    let summaries = app.user_coin_summaries(username);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(5), Constraint::Min(0)].as_ref())
+        .split(area);
+
+    let label_style = fg_style(app.theme.accent, app.use_color).add_modifier(Modifier::BOLD);
+    let trade_count: u64 = summaries.iter().map(|s| s.trade_count).sum();
+    let volume: f64 = summaries.iter().map(|s| s.volume).sum();
+    let pnl: f64 = summaries.iter().map(|s| s.pnl).sum();
+    let most_traded = match summaries.first() {
+        Some(top) => format!("{} (${})", top.coin, format_compact(top.volume)),
+        None => "-".to_string(),
+    };
+    let lines = vec![
+        Line::from(vec![
+            Span::styled("Volume: ", label_style),
+            Span::raw(format!("${} over {} trades", format_compact(volume), trade_count)),
+        ]),
+        Line::from(vec![Span::styled("Most traded: ", label_style), Span::raw(most_traded)]),
+        Line::from(vec![
+            Span::styled("Rough P&L: ", label_style),
+            Span::styled(format!("{:+.2}", pnl), fg_style(signed_color(app, pnl), app.use_color)),
+        ]),
+    ];
+    let stats = Paragraph::new(lines).block(Block::default().title(format!("Wallet: {}", username)).borders(Borders::ALL));
+    f.render_widget(stats, chunks[0]);
+
+    let header_cells = ["Coin", "Balance", "P&L"]
+        .iter()
+        .map(|h| Cell::from(*h).style(fg_style(app.theme.accent, app.use_color).add_modifier(Modifier::BOLD)));
+    let header = Row::new(header_cells).height(1).bottom_margin(1);
+    let rows = summaries.iter().map(|summary| {
+        Row::new(vec![
+            Cell::from(summary.coin.as_str()),
+            Cell::from(format_value(summary.balance)),
+            Cell::from(format!("{:+.2}", summary.pnl)).style(fg_style(signed_color(app, summary.pnl), app.use_color)),
+        ])
+    });
+    let table = Table::new(rows, [Constraint::Percentage(30), Constraint::Percentage(35), Constraint::Percentage(35)])
+        .header(header)
+        .block(Block::default().title("Per coin (P&L at last price)").borders(Borders::ALL));
+    f.render_widget(table, chunks[1]);
+}
+
+/// Positive, negative or neutral color by the sign of `value`.
+fn signed_color(app: &App, value: f64) -> Color {
+    if value > 0.0 {
+        app.theme.positive
+    } else if value < 0.0 {
+        app.theme.negative
+    } else {
+        app.theme.neutral
+    }
+}
 
+const TRADE_SIZE_BUCKETS: [(&str, f64); 5] = [
+    ("<10", 0.0),
+    ("10+", 10.0),
+    ("100+", 100.0),
+    ("1k+", 1000.0),
+    ("10k+", 10000.0),
+];
 
-    if let Some(symbol) = &app.active_search_symbol {
-        let side_by_side_chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(65), Constraint::Percentage(35)].as_ref())
-            .split(content_area);
+fn draw_trade_size_histogram(f: &mut Frame, app: &App, trades: &[Trade], area: Rect) {
+    let mut counts = [0u64; TRADE_SIZE_BUCKETS.len()];
+    for trade in trades {
+        let bucket = TRADE_SIZE_BUCKETS
+            .iter()
+            .rposition(|(_, floor)| trade.total_value >= *floor)
+            .unwrap_or(0);
+        counts[bucket] += 1;
+    }
+    let data: Vec<(&str, u64)> = TRADE_SIZE_BUCKETS
+        .iter()
+        .zip(counts)
+        .map(|((label, _), count)| (*label, count))
+        .collect();
 
-        draw_trades_table(f, &mut app.scroll_offset, &visible_trades, side_by_side_chunks[0], &trades_display_block_title);
+    let bucket_count = TRADE_SIZE_BUCKETS.len() as u16;
+    let bar_width = (area.width.saturating_sub(2) / bucket_count).saturating_sub(1).max(1);
+    let chart = BarChart::default()
+        .block(Block::default().title("Trade sizes (USD)").borders(Borders::ALL))
+        .data(&data)
+        .bar_width(bar_width)
+        .bar_gap(1)
+        .bar_style(fg_style(app.theme.highlight, app.use_color))
+        .value_style(Style::default().add_modifier(Modifier::REVERSED));
+    f.render_widget(chart, area);
+}
 
-        let balances_block = Block::default()
-            .title(format!("Balances for {}", symbol))
-            .borders(Borders::ALL);
-        
-        let mut user_coin_balances: Vec<(String, f64)> = app
-            .user_balances
-            .iter()
-            .filter_map(|(username, coin_map)| {
-                coin_map.get(symbol).map(|balance| (username.clone(), *balance))
-            })
-            .filter(|(_, balance)| *balance != 0.0) 
-            .collect();
-        
-        user_coin_balances.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+fn draw_price_sparkline(f: &mut Frame, app: &App, symbol: &str, history: &[(i64, f64)], area: Rect) {
+    let prices: Vec<f64> = history.iter().map(|(_, price)| *price).collect();
+    let last_price = prices.last().map_or("-".to_string(), |p| format_price(*p));
+    let levels = app.price_alerts.get(symbol).map_or(&[][..], Vec::as_slice);
+    let mut title = format!("{} price ({})", symbol, last_price);
+    if !levels.is_empty() {
+        title += &format!(" {} alert{}", levels.len(), if levels.len() == 1 { "" } else { "s" });
+    }
+    let period = app.moving_average_period;
+    if period > 0 {
+        title += &format!(" MA{}", period);
+    }
+    let block = Block::default().title(title).borders(Borders::ALL);
 
-        let header_cells = ["User", "Balance"]
-            .iter()
-            .map(|h| Cell::from(*h).style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
-        let header = Row::new(header_cells).height(1).bottom_margin(1);
+    // Sparkline only takes u64, so rescale the prices to 0..=100 within their
+    // range, stretched to take in the alert levels so their lines fit.
+    let min = prices.iter().chain(levels).copied().fold(f64::INFINITY, f64::min);
+    let max = prices.iter().chain(levels).copied().fold(f64::NEG_INFINITY, f64::max);
+    let range = max - min;
+    let data: Vec<u64> = prices
+        .iter()
+        .map(|p| if range > 0.0 { ((p - min) / range * 100.0) as u64 } else { 50 })
+        .collect();
 
-        let rows = user_coin_balances.iter().map(|(username, balance)| {
-            Row::new(vec![
-                Cell::from(username.as_str()),
-                Cell::from(format!("{:.2}", balance)),
-            ])
-        });
+    // Keep only the most recent points that fit inside the block.
+    let width = area.width.saturating_sub(2) as usize;
+    let start = data.len().saturating_sub(width);
+    let sparkline = Sparkline::default()
+        .block(block)
+        .data(&data[start..])
+        .max(100)
+        .style(fg_style(app.theme.info, app.use_color));
+    f.render_widget(sparkline, area);
 
-        let balance_table = Table::new(
-                rows,
-                [Constraint::Percentage(60), Constraint::Percentage(40)]
-            )
-            .header(header)
-            .block(balances_block)
-            .widths([Constraint::Percentage(70), Constraint::Percentage(30)]);
+    // Alert lines go over the empty cells only, so bars stay readable.
+    let inner = area.inner(&ratatui::layout::Margin { vertical: 1, horizontal: 1 });
+    if inner.height == 0 || prices.is_empty() {
+        return;
+    }
+    let row_for = |value: f64| {
+        let fraction = if range > 0.0 { (value - min) / range } else { 0.5 };
+        inner.bottom() - 1 - (fraction * (inner.height - 1) as f64).round() as u16
+    };
+    let line_style = fg_style(app.theme.warning, app.use_color);
+    for level in levels {
+        let y = row_for(*level);
+        for x in inner.left()..inner.right() {
+            let cell = f.buffer_mut().get_mut(x, y);
+            if cell.symbol() == " " {
+                cell.set_symbol("\u{2504}").set_style(line_style);
+            }
+        }
+    }
+
+    // The moving average is a dot per column: on the bar itself when the
+    // price is above it, recolored so it still shows.
+    if period == 0 {
+        return;
+    }
+    let average_style = fg_style(app.theme.highlight, app.use_color).add_modifier(Modifier::BOLD);
+    let mut sums = vec![0.0];
+    for price in &prices {
+        sums.push(sums.last().copied().unwrap_or_default() + price);
+    }
+    for (x, index) in (inner.left()..inner.right()).zip(start..prices.len()) {
+        if index + 1 < period {
+            continue;
+        }
+        let average = (sums[index + 1] - sums[index + 1 - period]) / period as f64;
+        let cell = f.buffer_mut().get_mut(x, row_for(average));
+        if cell.symbol() == " " {
+            cell.set_symbol("\u{2022}");
+        }
+        cell.set_style(average_style);
+    }
+}
 
-        f.render_widget(balance_table, side_by_side_chunks[1]);
+/// A color per trade type, the same every run, so types tell apart at a
+/// glance without a fixed list of them.
+fn trade_type_color(trade_type: &str, theme: &Theme) -> Color {
+    let colors = [theme.info, theme.highlight, theme.accent, theme.neutral, theme.warning];
+    // FNV-1a, since std's hasher is seeded per process.
+    let hash = trade_type
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3));
+    colors[(hash % colors.len() as u64) as usize]
+}
 
+/// A foreground-colored style, or a plain one when color is disabled.
+fn fg_style(color: Color, use_color: bool) -> Style {
+    if use_color {
+        Style::default().fg(color)
     } else {
-        draw_trades_table(f, &mut app.scroll_offset, &visible_trades, content_area, &trades_display_block_title);
+        Style::default()
     }
 }
 
-fn draw_trades_table(f: &mut Frame, scroll_offset: &mut usize, trades_to_display: &[Trade], area: Rect, title: &str) {
-    let trades_block = Block::default().title(title.to_string()).borders(Borders::ALL);
+fn filter_summary_line(app: &App) -> Line<'static> {
+    let filters = [
+        ("sym", app.active_search_symbol.as_ref().map(|symbol| match app.search_mode {
+            SearchMode::Exact => symbol.clone(),
+            SearchMode::Substring => format!("*{}*", symbol),
+            SearchMode::Fuzzy => format!("~{}", symbol),
+        })),
+        ("type", app.trade_type_filter.clone()),
+        ("act", app.action_filter.clone()),
+        ("min", app.min_value_filter.map(|v| format!("${}", v))),
+        ("user", app.user_filter.clone()),
+        ("expr", app.filter_expr.as_ref().map(|(source, _)| source.clone())),
+    ];
 
-    let header_cells = [
-        "Time", "Type", "Action", "User", "Amount", "Coin", "Total USD", "Price USD",
-    ]
-    .iter()
-    .map(|h| Cell::from(*h).style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
-    let header = Row::new(header_cells).height(1).bottom_margin(1);
+    let mut spans = Vec::new();
+    for (label, value) in filters {
+        if !spans.is_empty() {
+            spans.push(Span::styled(" | ", fg_style(app.theme.muted, app.use_color)));
+        }
+        match value {
+            Some(value) => spans.push(Span::styled(
+                format!("{}:{}", label, value),
+                fg_style(app.theme.info, app.use_color).add_modifier(Modifier::BOLD),
+            )),
+            None => spans.push(Span::styled(
+                format!("{}:-", label),
+                fg_style(app.theme.muted, app.use_color).add_modifier(Modifier::DIM),
+            )),
+        }
+    }
 
-    let rows: Vec<Row> = trades_to_display.iter().map(|trade| {
-        let action_color = if trade.action == "BUY" {
-            Color::Green
+    let (mut buys, mut buy_volume, mut sells, mut sell_volume) = (0, 0.0, 0, 0.0);
+    for trade in app.all_trades.iter().filter(|t| app.trade_matches_filters(t)) {
+        if trade.action == "BUY" {
+            buys += 1;
+            buy_volume += trade.total_value;
         } else if trade.action == "SELL" {
-            Color::Red
+            sells += 1;
+            sell_volume += trade.total_value;
+        }
+    }
+    spans.push(Span::styled("  buys ", fg_style(app.theme.muted, app.use_color)));
+    spans.push(Span::styled(
+        format!("{} ${}", buys, format_compact(buy_volume)),
+        fg_style(app.theme.positive, app.use_color),
+    ));
+    spans.push(Span::styled(" / sells ", fg_style(app.theme.muted, app.use_color)));
+    spans.push(Span::styled(
+        format!("{} ${}", sells, format_compact(sell_volume)),
+        fg_style(app.theme.negative, app.use_color),
+    ));
+
+    if let Some(every) = app.sampler.as_ref().and_then(|sampler| sampler.active_rate(Instant::now())) {
+        spans.push(Span::styled(
+            format!("  sampling 1/{}", every),
+            fg_style(app.theme.highlight, app.use_color).add_modifier(Modifier::BOLD),
+        ));
+    }
+    if app.db_in_memory {
+        spans.push(Span::styled(
+            "  IN-MEMORY DB: nothing is saved",
+            fg_style(app.theme.negative, app.use_color).add_modifier(Modifier::BOLD | Modifier::REVERSED),
+        ));
+    }
+    if app.incomplete_dropped > 0 {
+        spans.push(Span::styled(
+            format!("  {} incomplete dropped", app.incomplete_dropped),
+            fg_style(app.theme.muted, app.use_color),
+        ));
+    }
+    if let Some(record) = &app.largest_trade {
+        let new_record = app.record_set_at.is_some_and(|at| at.elapsed() < RECORD_FLASH_DURATION);
+        let style = if new_record {
+            fg_style(app.theme.highlight, app.use_color).add_modifier(Modifier::BOLD | Modifier::REVERSED)
         } else {
-            Color::Gray
+            fg_style(app.theme.muted, app.use_color)
         };
+        let label = if new_record { "NEW RECORD" } else { "record" };
+        spans.push(Span::styled(
+            format!("  {} ${} {}", label, format_compact(record.total_value), record.coin_symbol),
+            style,
+        ));
+    }
+    let schema_mismatches = app.feed_metrics.schema_mismatches();
+    if schema_mismatches > 0 {
+        spans.push(Span::styled(
+            format!("  {} malformed trades (see {})", schema_mismatches, feed::FEED_LOG_PATH),
+            fg_style(app.theme.warning, app.use_color).add_modifier(Modifier::BOLD),
+        ));
+    }
+    if app.queue_dropped > 0 {
+        spans.push(Span::styled(
+            format!("  {} dropped (UI behind)", app.queue_dropped),
+            fg_style(app.theme.warning, app.use_color).add_modifier(Modifier::BOLD),
+        ));
+    }
+    if app.feed_metrics.connection_state() == ConnectionState::Failed {
+        spans.push(Span::styled(
+            "  FEED FAILED: not reconnecting",
+            fg_style(app.theme.negative, app.use_color).add_modifier(Modifier::BOLD | Modifier::REVERSED),
+        ));
+    }
+    if let Some(message) = &app.status_message {
+        spans.push(Span::styled(
+            format!("  {}", message),
+            fg_style(app.theme.accent, app.use_color).add_modifier(Modifier::BOLD),
+        ));
+    }
+    Line::from(spans)
+}
+
+/// Row style for `trade` by the tier it reaches under `mode`. Tagged
+/// incomplete trades are dimmed instead, since their numbers can't be trusted.
+fn tier_row_style(app: &App, trade: &Trade, mode: ColorMode) -> Style {
+    if app.incomplete_trades == IncompleteTrades::Tag && trade.is_incomplete() {
+        return Style::default().add_modifier(Modifier::DIM | Modifier::ITALIC);
+    }
+    tier_style(app.tier(trade, mode), app)
+}
+
+fn tier_style(tier: Option<usize>, app: &App) -> Style {
+    let Some(tier) = tier else {
+        return Style::default();
+    };
+    let tiers = &app.theme.tiers;
+    let top_tier = tier == tiers.len() - 1;
+    if app.use_color {
+        let style = Style::default().fg(tiers[tier].0);
+        if top_tier { style.add_modifier(Modifier::BOLD) } else { style }
+    } else if top_tier {
+        Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED)
+    } else if tier + 2 == tiers.len() {
+        Style::default().add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    }
+}
+
+/// 0 for fresh trades, 1 once dimmed, 2 once greyed out. Trades saved
+/// before dates were kept count as oldest.
+fn decay_level(recorded_at: Option<i64>, now_secs: i64, decay: TradeDecay) -> u64 {
+    let Some(recorded_at) = recorded_at else {
+        return 2;
+    };
+    (now_secs.saturating_sub(recorded_at).max(0) as u64 / decay.step_secs).min(2)
+}
+
+/// Fades `style` to `level`. Dimming is a modifier, so the value tier and
+/// the Action cell's color show through; only rows without a tier color
+/// are greyed out.
+fn decayed_style(style: Style, level: u64, app: &App) -> Style {
+    match level {
+        0 => style,
+        1 => style.add_modifier(Modifier::DIM),
+        _ if style.fg.is_none() => style.patch(fg_style(app.theme.muted, app.use_color)).add_modifier(Modifier::DIM),
+        _ => style.add_modifier(Modifier::DIM),
+    }
+}
 
-        let row_style = match trade.total_value {
-            v if v >= 10000.0 => Style::default().fg(Color::LightRed).add_modifier(Modifier::BOLD),
-            v if v >= 1000.0 => Style::default().fg(Color::Magenta),
-            v if v >= 100.0 => Style::default().fg(Color::Yellow),
-            v if v >= 10.0 => Style::default().fg(Color::Cyan),
-            _ => Style::default(),
+/// One swatch per tier of the active color mode, rendered in that tier's
+/// own row style.
+fn tier_legend_line(app: &App) -> Line<'static> {
+    let (title, unit) = match app.color_mode {
+        ColorMode::Value => ("Value tiers: ", "$"),
+        ColorMode::Amount => ("Amount tiers: ", ""),
+    };
+    let mut spans = vec![Span::raw(title)];
+    for (tier, floor) in app.tiers(app.color_mode).iter().enumerate() {
+        let amount = format_compact(*floor).replace(".00", "");
+        let label = if app.use_color {
+            format!("{} \u{2265} {}{}", app.theme.tiers[tier].1, unit, amount)
+        } else {
+            format!("\u{2265} {}{}", unit, amount)
         };
+        spans.push(Span::styled(label, tier_style(Some(tier), app)));
+        spans.push(Span::raw("  "));
+    }
+    let hint = format!("({} to hide, {} to switch)", app.key_hint(Action::ToggleLegend), app.key_hint(Action::ToggleColorMode));
+    spans.push(Span::styled(hint, fg_style(app.theme.muted, app.use_color)));
+    Line::from(spans)
+}
 
-        Row::new(vec![
-            Cell::from(trade.timestamp.as_str()),
-            Cell::from(trade.trade_type_val.as_str()),
-            Cell::from(Span::styled(trade.action.as_str(), Style::default().fg(action_color))),
-            Cell::from(trade.username.as_str()),
-            Cell::from(format!("{:.2}", trade.amount)),
-            Cell::from(trade.coin_symbol.as_str()),
-            Cell::from(format!("{:.2}", trade.total_value)),
-            Cell::from(format!("{:.8}", trade.price)),
-        ])
-        .style(row_style)
-    }).collect();
+/// Thumb height and top position, in cells, for a scrollbar over
+/// `track_height` cells showing `view_height` of `content_height` rows from
+/// `offset` down. The thumb is at least one cell and always fits the track.
+fn scrollbar_geometry(content_height: usize, view_height: usize, offset: usize, track_height: u16) -> (u16, u16) {
+    if content_height <= view_height {
+        return (track_height, 0);
+    }
+    let thumb_height = ((view_height as f32 / content_height as f32) * track_height as f32).max(1.0) as u16;
+    let thumb_height = thumb_height.min(track_height);
+
+    let scrollable_content_range = content_height - view_height;
+    let movement_range = track_height - thumb_height;
+    let offset = offset.min(scrollable_content_range);
+    let pos = ((offset as f32 / scrollable_content_range as f32) * movement_range as f32) as u16;
+    (thumb_height, pos.min(movement_range))
+}
+
+fn draw_trades_table(f: &mut Frame, app: &mut App, trades_to_display: &[Trade], area: Rect, title: &str) {
+    let use_color = app.use_color;
+    let columns = &app.columns;
+    let trades_block = Block::default().title(title.to_string()).borders(Borders::ALL);
+
+    let header_cells = columns.iter().enumerate().map(|(index, &c)| {
+        let style = fg_style(app.theme.accent, use_color).add_modifier(Modifier::BOLD);
+        let style = if index == app.selected_column { style.add_modifier(Modifier::UNDERLINED) } else { style };
+        Cell::from(app.column_header(c)).style(style)
+    });
+    let header = Row::new(header_cells).height(1).bottom_margin(1);
+    let cell_widths = app.rendered_column_widths(columns, area);
 
-    let visible_row_count = if area.height > 3 { area.height as usize - 3 } else { 0 };
+    let fitting_row_count = if area.height > 3 { area.height as usize - 3 } else { 0 };
+    let visible_row_count = fitting_row_count.min(app.max_rendered_rows);
 
+    if let Some(selected) = app.selected_trade
+        && selected >= trades_to_display.len()
+    {
+        app.selected_trade = trades_to_display.len().checked_sub(1);
+    }
     if trades_to_display.is_empty() {
-        *scroll_offset = 0;
-    } else if *scroll_offset >= trades_to_display.len() {
-        *scroll_offset = trades_to_display.len().saturating_sub(1);
+        app.scroll_offset = 0;
+    } else if app.scroll_offset >= trades_to_display.len() {
+        app.scroll_offset = trades_to_display.len().saturating_sub(1);
     }
-    if trades_to_display.len() > visible_row_count && *scroll_offset > trades_to_display.len() - visible_row_count {
-        *scroll_offset = trades_to_display.len() - visible_row_count;
+    if trades_to_display.len() > visible_row_count && app.scroll_offset > trades_to_display.len() - visible_row_count {
+        app.scroll_offset = trades_to_display.len() - visible_row_count;
     }
     
-    let start_index = *scroll_offset;
+    let start_index = app.scroll_offset;
     
-    let visible_rows_slice = if !rows.is_empty() && start_index < rows.len() {
-        let end_idx = (start_index + visible_row_count).min(rows.len());
-        &rows[start_index..end_idx]
+    let now = Instant::now();
+    let now_secs = Local::now().timestamp();
+    // Only the rows on screen are built, so the cost doesn't grow with the tape.
+    let rows: Vec<Row> = trades_to_display.iter().enumerate().skip(start_index).take(visible_row_count).map(|(index, trade)| {
+        let row_style = tier_row_style(app, trade, app.color_mode);
+        let row_style = match app.dim_old_trades {
+            Some(decay) => decayed_style(row_style, decay_level(trade.recorded_at, now_secs, decay), app),
+            None => row_style,
+        };
+
+        let flash_age = trade.received_at.map(|received_at| now.duration_since(received_at));
+        let row_style = match flash_age {
+            // Sorted by size, a new trade can land anywhere, so any row may flash.
+            Some(age)
+                if app.flash_new_trades
+                    && (index < FLASH_MAX_ROWS || app.tape_sort != TapeSort::Newest)
+                    && age < FLASH_DURATION =>
+            {
+                // Strong highlight first, then ease off to just bold.
+                if age < FLASH_DURATION / 2 {
+                    if use_color {
+                        row_style.bg(app.theme.flash_bg).add_modifier(Modifier::BOLD)
+                    } else {
+                        row_style.add_modifier(Modifier::REVERSED)
+                    }
+                } else {
+                    row_style.add_modifier(Modifier::BOLD)
+                }
+            }
+            _ => row_style,
+        };
+
+        let row_style = if app.selected_trade == Some(index) {
+            row_style.add_modifier(Modifier::REVERSED)
+        } else {
+            row_style
+        };
+
+        Row::new(columns.iter().zip(&cell_widths).map(|(c, &width)| c.cell(trade, app, width)))
+            .style(row_style)
+    }).collect();
+
+    let column_widths: Vec<Constraint> = columns.iter().map(|&c| app.column_width(c)).collect();
+
+    let trades_block = if visible_row_count < fitting_row_count && trades_to_display.len() > visible_row_count {
+        trades_block.title_bottom(format!(
+            " showing {} rows at a time (max_rendered_rows) ",
+            visible_row_count
+        ))
     } else {
-        &[]
+        trades_block
     };
-    
-    let column_widths = [
-        Constraint::Length(8),
-        Constraint::Length(10),
-        Constraint::Length(6),
-        Constraint::Length(15),
-        Constraint::Length(10),
-        Constraint::Length(8),
-        Constraint::Length(12),
-        Constraint::Length(14),
-    ];
-
-    let table = Table::new(visible_rows_slice.to_vec(), column_widths.clone())
+    let trades_block = match &app.paused {
+        Some(pause) => {
+            let burst = pause.burst_rate.map_or(String::new(), |rate| format!(" for a burst of {:.0} trades/s", rate));
+            let resume_hint = match app.keymap.keys_for(Action::TogglePause).first() {
+                Some(key) => format!(", {} to resume", key),
+                None => String::new(),
+            };
+            let banner = format!(" PAUSED{}: {} new trades held{} ", burst, pause.held, resume_hint);
+            let style = if use_color {
+                Style::default().fg(Color::Black).bg(app.theme.warning)
+            } else {
+                Style::default().add_modifier(Modifier::REVERSED)
+            };
+            trades_block.title_bottom(Line::styled(banner, style.add_modifier(Modifier::BOLD)))
+        }
+        None => trades_block,
+    };
+    let table = Table::new(rows, column_widths)
         .header(header)
-        .block(trades_block)
-        .widths(&column_widths);
+        .block(trades_block);
 
     f.render_widget(table, area);
 
+    // Rows start below the top border, the header and its margin.
+    let noted_rows = trades_to_display.iter().skip(start_index).take(visible_row_count).enumerate().filter(|(_, trade)| {
+        trade.row_id.is_some_and(|row_id| app.notes.contains_key(&row_id))
+    });
+    for (row, _) in noted_rows {
+        let y = area.y + 3 + row as u16;
+        if y < area.bottom().saturating_sub(1) {
+            f.buffer_mut().set_string(area.x, y, NOTE_MARKER.to_string(), fg_style(app.theme.highlight, use_color));
+        }
+    }
+
+    if trades_to_display.is_empty() {
+        let placeholder = if app.watchlist_only && app.pinned.is_empty() {
+            format!("Watchlist is empty: search a coin and press '{}' to pin it", app.key_hint(Action::TogglePin))
+        } else if app.all_trades.is_empty() {
+            "Waiting for trades\u{2026}".to_string()
+        } else {
+            "No trades match your filters".to_string()
+        };
+        // Vertically centred inside the block.
+        let inner = area.inner(&ratatui::layout::Margin { vertical: 1, horizontal: 1 });
+        let placeholder_area = Rect {
+            y: inner.y + inner.height / 2,
+            height: inner.height.min(1),
+            ..inner
+        };
+        f.render_widget(
+            Paragraph::new(placeholder)
+                .alignment(Alignment::Center)
+                .style(fg_style(app.theme.muted, app.use_color)),
+            placeholder_area,
+        );
+    }
+
     let total_rows_to_display = trades_to_display.len();
     if total_rows_to_display > visible_row_count {
         let scrollbar_area = area.inner(&ratatui::layout::Margin { vertical: 1, horizontal: 0 });
         if scrollbar_area.width > 0 && scrollbar_area.height > 0 {
-            let content_height = total_rows_to_display;
-            let view_height = visible_row_count;
-
-            let scrollbar_thumb_height = ((view_height as f32 / content_height as f32) * scrollbar_area.height as f32).max(1.0) as u16;
             let scrollbar_track_height = scrollbar_area.height;
-            
-            let scrollable_content_range = content_height.saturating_sub(view_height);
-            let scrollbar_movement_range = scrollbar_track_height.saturating_sub(scrollbar_thumb_height);
-            
-            let scrollbar_pos = if scrollable_content_range > 0 {
-                ((*scroll_offset as f32 / scrollable_content_range as f32) * scrollbar_movement_range as f32) as u16
-            } else {
-                0
-            };
-            let scrollbar_pos = scrollbar_pos.min(scrollbar_track_height.saturating_sub(scrollbar_thumb_height));
+            let (scrollbar_thumb_height, scrollbar_pos) = scrollbar_geometry(
+                total_rows_to_display,
+                visible_row_count,
+                app.scroll_offset,
+                scrollbar_track_height,
+            );
 
+            let style = app.theme.scrollbar;
             for y_offset in 0..scrollbar_track_height {
-                let char_to_draw = if y_offset >= scrollbar_pos && y_offset < scrollbar_pos + scrollbar_thumb_height { '█' } else { '░' };
+                let (char_to_draw, color) = if y_offset >= scrollbar_pos && y_offset < scrollbar_pos + scrollbar_thumb_height {
+                    (style.thumb, style.thumb_color)
+                } else {
+                    (style.track, style.track_color)
+                };
                 if area.right() > area.left() {
                     f.buffer_mut().set_string(
                         area.right() - 1, 
                         scrollbar_area.top() + y_offset,
                         char_to_draw.to_string(),
-                        Style::default().fg(Color::DarkGray)
+                        fg_style(color, use_color)
                     );
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrollbar_fills_track_when_everything_fits() {
+        assert_eq!(scrollbar_geometry(5, 10, 0, 20), (20, 0));
+        assert_eq!(scrollbar_geometry(10, 10, 0, 20), (20, 0));
+        assert_eq!(scrollbar_geometry(0, 10, 0, 20), (20, 0));
+    }
+
+    #[test]
+    fn scrollbar_thumb_at_top_and_bottom() {
+        assert_eq!(scrollbar_geometry(100, 10, 0, 20), (2, 0));
+        assert_eq!(scrollbar_geometry(100, 10, 90, 20), (2, 18));
+        assert_eq!(scrollbar_geometry(100, 10, 45, 20), (2, 9));
+    }
+
+    #[test]
+    fn scrollbar_offset_past_end_is_clamped() {
+        assert_eq!(scrollbar_geometry(100, 10, 500, 20), (2, 18));
+    }
+
+    #[test]
+    fn scrollbar_with_single_row_view() {
+        assert_eq!(scrollbar_geometry(2, 1, 0, 10), (5, 0));
+        assert_eq!(scrollbar_geometry(2, 1, 1, 10), (5, 5));
+    }
+
+    #[test]
+    fn scrollbar_thumb_is_at_least_one_cell() {
+        let (thumb, pos) = scrollbar_geometry(100_000, 10, 99_990, 20);
+        assert_eq!(thumb, 1);
+        assert_eq!(pos, 19);
+        assert_eq!(scrollbar_geometry(100_000, 10, 0, 20), (1, 0));
+    }
+
+    #[test]
+    fn scrollbar_on_a_very_tall_track() {
+        assert_eq!(scrollbar_geometry(200, 100, 0, 1000), (500, 0));
+        assert_eq!(scrollbar_geometry(200, 100, 100, 1000), (500, 500));
+        let (thumb, pos) = scrollbar_geometry(1_000, 999, 1, 5000);
+        assert_eq!(thumb + pos, 5000);
+    }
+
+    #[test]
+    fn scrollbar_never_overflows_the_track() {
+        for content in 1..60 {
+            for view in 1..20 {
+                for offset in 0..content {
+                    for track in 1..15 {
+                        let (thumb, pos) = scrollbar_geometry(content, view, offset, track);
+                        assert!(thumb >= 1 && thumb + pos <= track, "{content} {view} {offset} {track}");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Keeps trades in memory instead of SQLite.
+    #[derive(Default)]
+    struct MemoryStore {
+        trades: std::cell::RefCell<Vec<Trade>>,
+    }
+
+    impl TradeStore for MemoryStore {
+        fn insert(&self, trade: &Trade) -> Result<i64, String> {
+            let mut trades = self.trades.borrow_mut();
+            trades.push(trade.clone());
+            Ok(trades.len() as i64)
+        }
+
+        fn insert_price_point(&self, _coin_symbol: &str, _timestamp: i64, _price: f64) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn load_recent(&self, limit: Option<usize>) -> Result<Vec<Trade>, String> {
+            let trades = self.trades.borrow();
+            Ok(trades.iter().rev().take(limit.unwrap_or(usize::MAX)).cloned().collect())
+        }
+
+        fn load_page(&self, before: i64, limit: usize) -> Result<Vec<Trade>, String> {
+            let trades = self.trades.borrow();
+            let end = (before.max(1) as usize - 1).min(trades.len());
+            Ok(trades[..end].iter().rev().take(limit).cloned().collect())
+        }
+
+        fn trades_since(&self, since: i64, limit: usize) -> Result<Vec<Trade>, String> {
+            let trades = self.trades.borrow();
+            Ok(trades.iter().rev().filter(|t| t.recorded_at.is_some_and(|at| at > since)).take(limit).cloned().collect())
+        }
+
+        fn query(&self, _query: &db_search::DbQuery) -> Result<Vec<Trade>, String> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn trade(username: &str, coin: &str, action: &str, amount: f64) -> Trade {
+        Trade {
+            timestamp: "12:00:00".to_string(),
+            trade_type_val: "live-trade".to_string(),
+            action: action.to_string(),
+            username: username.to_string(),
+            amount,
+            coin_symbol: coin.to_string(),
+            total_value: amount * 2.0,
+            price: 2.0,
+            id: None,
+            received_at: None,
+            recorded_at: None,
+            row_id: None,
+        }
+    }
+
+    fn balance(app: &App, username: &str, coin: &str) -> Option<f64> {
+        app.user_balances.get(username)?.get(coin).copied()
+    }
+
+    #[test]
+    fn buys_add_to_and_sells_take_from_the_balance() {
+        let store = MemoryStore::default();
+        let mut app = App::new(Vec::new());
+        app.add_trade(trade("alice", "DOGE", "BUY", 100.0), &store);
+        app.add_trade(trade("alice", "DOGE", "BUY", 50.0), &store);
+        app.add_trade(trade("alice", "DOGE", "SELL", 30.0), &store);
+        assert_eq!(balance(&app, "alice", "DOGE"), Some(120.0));
+    }
+
+    #[test]
+    fn offsetting_trades_leave_a_zero_balance() {
+        let store = MemoryStore::default();
+        let mut app = App::new(Vec::new());
+        app.add_trade(trade("bob", "PEPE", "BUY", 75.5), &store);
+        app.add_trade(trade("bob", "PEPE", "SELL", 75.5), &store);
+        assert_eq!(balance(&app, "bob", "PEPE"), Some(0.0));
+    }
+
+    #[test]
+    fn capped_balances_keep_holdings_through_pruning() {
+        let store = MemoryStore::default();
+        let mut app = App::new(Vec::new());
+        app.max_balance_entries = Some(10);
+        app.add_trade(trade("alice", "DOGE", "BUY", 50.0), &store);
+        for i in 0..12 {
+            let user = format!("user{}", i);
+            app.add_trade(trade(&user, "PEPE", "BUY", 1.0), &store);
+            app.add_trade(trade(&user, "PEPE", "SELL", 1.0), &store);
+        }
+        assert!(app.balance_entries <= 10);
+        assert_eq!(balance(&app, "user0", "PEPE"), None);
+        // A pruned pair trades again from zero; a kept one adds to its holding.
+        app.add_trade(trade("user0", "PEPE", "BUY", 3.0), &store);
+        app.add_trade(trade("alice", "DOGE", "SELL", 20.0), &store);
+        assert_eq!(balance(&app, "user0", "PEPE"), Some(3.0));
+        assert_eq!(app.coin_balances("DOGE"), [("alice".to_string(), 30.0)]);
+    }
+
+    #[test]
+    fn pruning_drops_float_residue() {
+        let store = MemoryStore::default();
+        let mut app = App::new(Vec::new());
+        app.max_balance_entries = Some(10);
+        for i in 0..11 {
+            let user = format!("user{}", i);
+            app.add_trade(trade(&user, "PEPE", "BUY", 0.1), &store);
+            app.add_trade(trade(&user, "PEPE", "BUY", 0.2), &store);
+            app.add_trade(trade(&user, "PEPE", "SELL", 0.3), &store);
+        }
+        // 0.1 + 0.2 - 0.3 isn't quite zero in floating point.
+        assert_ne!(0.1 + 0.2 - 0.3, 0.0);
+        assert!(app.balance_entries <= 10, "{} entries kept", app.balance_entries);
+        assert_eq!(app.coin_balances("PEPE"), []);
+    }
+
+    #[test]
+    fn pruning_backs_off_when_every_balance_is_held() {
+        let store = MemoryStore::default();
+        let mut app = App::new(Vec::new());
+        app.max_balance_entries = Some(10);
+        for i in 0..11 {
+            app.add_trade(trade(&format!("user{}", i), "DOGE", "BUY", 1.0), &store);
+        }
+        assert_eq!(app.balance_entries, 11);
+        assert_eq!(app.balance_prune_at, 12);
+        app.add_trade(trade("user11", "DOGE", "BUY", 1.0), &store);
+        assert_eq!(app.balance_prune_at, 12);
+        assert_eq!(app.coin_balances("DOGE").len(), 12);
+    }
+
+    #[test]
+    fn selling_more_than_bought_goes_negative() {
+        // Holdings from before the session aren't known, so this is expected.
+        let store = MemoryStore::default();
+        let mut app = App::new(Vec::new());
+        app.add_trade(trade("carol", "DOGE", "SELL", 40.0), &store);
+        assert_eq!(balance(&app, "carol", "DOGE"), Some(-40.0));
+    }
+
+    #[test]
+    fn balances_are_kept_per_user_and_coin() {
+        let store = MemoryStore::default();
+        let mut app = App::new(Vec::new());
+        app.add_trade(trade("alice", "DOGE", "BUY", 10.0), &store);
+        app.add_trade(trade("alice", "PEPE", "BUY", 20.0), &store);
+        app.add_trade(trade("bob", "DOGE", "BUY", 30.0), &store);
+        app.add_trade(trade("bob", "DOGE", "SELL", 5.0), &store);
+        app.add_trade(trade("alice", "PEPE", "SELL", 20.0), &store);
+        assert_eq!(balance(&app, "alice", "DOGE"), Some(10.0));
+        assert_eq!(balance(&app, "alice", "PEPE"), Some(0.0));
+        assert_eq!(balance(&app, "bob", "DOGE"), Some(25.0));
+        assert_eq!(balance(&app, "bob", "PEPE"), None);
+    }
+
+    #[test]
+    fn unknown_actions_do_not_move_the_balance() {
+        let store = MemoryStore::default();
+        let mut app = App::new(Vec::new());
+        app.add_trade(trade("dave", "DOGE", "BUY", 10.0), &store);
+        app.add_trade(trade("dave", "DOGE", "TRANSFER", 10.0), &store);
+        assert_eq!(balance(&app, "dave", "DOGE"), Some(10.0));
+    }
+
+    #[test]
+    fn incomplete_trades_are_kept_out_of_balances() {
+        let store = MemoryStore::default();
+        let mut app = App::new(Vec::new());
+        app.add_trade(trade("erin", "DOGE", "BUY", 10.0), &store);
+        app.add_trade(trade("erin", "DOGE", "BUY", 0.0), &store);
+        let mut zero_price = trade("erin", "DOGE", "SELL", 4.0);
+        zero_price.price = 0.0;
+        app.add_trade(zero_price, &store);
+        assert_eq!(balance(&app, "erin", "DOGE"), Some(10.0));
+        // Tagged, not dropped: they're still saved and on the tape.
+        assert_eq!(store.trades.borrow().len(), 3);
+        assert_eq!(app.all_trades.len(), 3);
+    }
+
+    #[test]
+    fn incomplete_trades_never_set_the_session_record() {
+        let store = MemoryStore::default();
+        let mut app = App::new(Vec::new());
+        app.add_trade(trade("erin", "DOGE", "BUY", 10.0), &store);
+        let mut garbage = trade("erin", "DOGE", "BUY", 1.0);
+        garbage.price = 0.0;
+        garbage.total_value = 1e12;
+        app.add_trade(garbage, &store);
+        assert_eq!(app.largest_trade.as_ref().map(|t| t.total_value), Some(20.0));
+    }
+
+    #[test]
+    fn live_and_replayed_balances_agree() {
+        let trades = [
+            trade("alice", "DOGE", "BUY", 100.0),
+            trade("bob", "DOGE", "BUY", 40.0),
+            trade("alice", "DOGE", "SELL", 60.0),
+            trade("bob", "PEPE", "BUY", 5.0),
+            trade("bob", "DOGE", "SELL", 40.0),
+        ];
+        let store = MemoryStore::default();
+        let mut live = App::new(Vec::new());
+        for trade in trades.iter().cloned() {
+            live.add_trade(trade, &store);
+        }
+        let mut replayed = App::new(store.load_recent(None).unwrap());
+        replayed.recalculate_balances_from_trades();
+        assert_eq!(live.user_balances, replayed.user_balances);
+    }
+
+    #[test]
+    fn saved_trades_get_row_ids() {
+        let store = MemoryStore::default();
+        let mut app = App::new(Vec::new());
+        app.add_trade(trade("alice", "DOGE", "BUY", 1.0), &store);
+        app.add_trade(trade("alice", "DOGE", "BUY", 2.0), &store);
+        let row_ids: Vec<Option<i64>> = app.all_trades.iter().map(|t| t.row_id).collect();
+        assert_eq!(row_ids, [Some(2), Some(1)]);
+    }
+
+    #[test]
+    fn mixed_case_symbols_share_stats_and_balances() {
+        let store = MemoryStore::default();
+        let mut app = App::new(Vec::new());
+        app.add_trade(trade("alice", "doge", "BUY", 10.0), &store);
+        app.add_trade(trade("alice", "DOGE", "BUY", 5.0), &store);
+        app.add_trade(trade("alice", "DoGe", "SELL", 3.0), &store);
+        assert_eq!(app.coin_stats.len(), 1);
+        assert_eq!(app.coin_stats["DOGE"].trade_count, 3);
+        assert_eq!(balance(&app, "alice", "DOGE"), Some(12.0));
+    }
+
+    #[test]
+    fn a_broken_filter_expression_is_not_the_last_search() {
+        let mut app = App::new(Vec::new());
+        app.search_input = "?value>100".to_string();
+        app.submit_search();
+        app.search_input = "?value>lots".to_string();
+        app.submit_search();
+        assert_eq!(app.last_submitted_search, "?value>100");
+        assert_eq!(app.filter_expr.as_ref().map(|(source, _)| source.as_str()), Some("value>100"));
+    }
+
+    #[test]
+    fn escape_after_a_database_search_drops_the_live_filter() {
+        let conn = seeded_db(&[]);
+        let mut app = App::new(Vec::new());
+        app.live_search = true;
+        app.search_input = "!coin:DOGE".to_string();
+        app.submit(&conn);
+        assert_eq!(app.last_submitted_search, "!coin:DOGE");
+        app.input_mode = InputMode::Editing;
+        app.search_input = "PEP".to_string();
+        app.apply_live_search(&conn);
+        assert!(app.active_search_symbol.is_some());
+        app.cancel_editing();
+        assert_eq!(app.search_input, "");
+        assert_eq!(app.active_search_symbol, None);
+    }
+
+    #[test]
+    fn live_fuzzy_search_filters_while_typing() {
+        let conn = seeded_db(&[]);
+        let store = MemoryStore::default();
+        let mut app = App::new(Vec::new());
+        app.add_trade(trade("alice", "DOGE", "BUY", 1.0), &store);
+        app.add_trade(trade("bob", "PEPE", "BUY", 2.0), &store);
+        app.live_search = true;
+        app.search_mode = SearchMode::Fuzzy;
+        app.search_input = "dge".to_string();
+        app.apply_live_search(&conn);
+        let shown: Vec<String> = app.get_visible_trades().iter().map(|t| t.coin_symbol.clone()).collect();
+        assert_eq!(shown, ["DOGE"]);
+    }
+
+    #[test]
+    fn one_tick_takes_every_queued_trade() {
+        let conn = seeded_db(&[]);
+        let mut app = App::new(Vec::new());
+        let (tx, rx) = trade_channel::bounded(100, config::OverflowPolicy::Block);
+        for i in 0..50 {
+            tx.send(trade("alice", "DOGE", "BUY", i as f64 + 1.0)).ok().unwrap();
+        }
+        assert_eq!(drain_trades(&mut app, &rx, &conn), Some(false));
+        assert_eq!(app.all_trades.len(), 50);
+        drop(tx);
+        assert_eq!(drain_trades(&mut app, &rx, &conn), None);
+    }
+
+    #[test]
+    fn a_flood_is_taken_a_tick_at_a_time() {
+        let conn = seeded_db(&[]);
+        let mut app = App::new(Vec::new());
+        let (tx, rx) = trade_channel::bounded(TRADES_PER_TICK + 10, config::OverflowPolicy::Block);
+        for _ in 0..TRADES_PER_TICK + 10 {
+            tx.send(trade("alice", "DOGE", "BUY", 1.0)).ok().unwrap();
+        }
+        assert_eq!(drain_trades(&mut app, &rx, &conn), Some(true));
+        assert!(app.all_trades.len() > 1);
+        // The time budget can cut a slow tick short, so keep ticking.
+        while drain_trades(&mut app, &rx, &conn) == Some(true) {}
+        assert_eq!(app.all_trades.len(), TRADES_PER_TICK + 10);
+    }
+
+    #[test]
+    fn session_stats_leave_out_loaded_history() {
+        let store = MemoryStore::default();
+        let mut app = App::new(vec![trade("old", "PEPE", "SELL", 100.0), trade("old", "PEPE", "SELL", 200.0)]);
+        app.seed_coin_stats();
+        app.add_trade(trade("alice", "DOGE", "BUY", 1.0), &store);
+        app.add_trade(trade("bob", "doge", "SELL", 2.0), &store);
+        app.add_trade(trade("alice", "WIF", "BUY", 3.0), &store);
+        let session = &app.session;
+        assert_eq!((session.trades, session.volume, session.buys, session.sells), (3, 12.0, 2, 1));
+        assert_eq!((session.coins.len(), session.users.len()), (2, 2));
+        assert_eq!(session.busiest_coin(), Some((&"DOGE".to_string(), 2)));
+    }
+
+    #[test]
+    fn search_matches_any_casing_and_keeps_the_feed_casing() {
+        let store = MemoryStore::default();
+        let mut app = App::new(Vec::new());
+        app.add_trade(trade("alice", "doge", "BUY", 1.0), &store);
+        app.add_trade(trade("bob", "DOGE", "BUY", 2.0), &store);
+        app.add_trade(trade("carol", "PEPE", "BUY", 3.0), &store);
+        app.search_mode = SearchMode::Exact;
+        app.active_search_symbol = Some(symbols::normalize("DoGe"));
+        let shown: Vec<String> = app.get_visible_trades().iter().map(|t| t.coin_symbol.clone()).collect();
+        assert_eq!(shown, ["DOGE", "doge"]);
+    }
+
+    #[test]
+    fn a_burst_pauses_the_tape_until_resumed() {
+        let mut app = App::new(Vec::new());
+        app.auto_pause = Some(AutoPause { trades_per_sec: 10.0, resume_when_calm: false });
+        let start = Instant::now();
+        app.check_burst(start);
+        for _ in 0..100 {
+            app.feed_metrics.record_trade("DOGE");
+        }
+        app.check_burst(start + Duration::from_secs(2));
+        assert!(app.paused.as_ref().is_some_and(|pause| pause.burst_rate == Some(50.0)));
+        // Still bursting, but resumed by hand: stay resumed.
+        app.toggle_pause();
+        app.check_burst(start + Duration::from_secs(3));
+        assert!(app.paused.is_none());
+    }
+
+    #[test]
+    fn auto_pause_can_resume_once_calm() {
+        let mut app = App::new(Vec::new());
+        app.auto_pause = Some(AutoPause { trades_per_sec: 10.0, resume_when_calm: true });
+        let start = Instant::now();
+        app.check_burst(start);
+        for _ in 0..100 {
+            app.feed_metrics.record_trade("DOGE");
+        }
+        app.check_burst(start + Duration::from_secs(2));
+        assert!(app.paused.is_some());
+        app.check_burst(start + Duration::from_secs(10));
+        assert!(app.paused.is_none());
+        // A pause by hand isn't lifted by the calm.
+        app.toggle_pause();
+        app.check_burst(start + Duration::from_secs(12));
+        assert!(app.paused.is_some());
+    }
+
+    #[test]
+    fn type_filter_cycles_through_seen_types() {
+        let mut loaded = trade("alice", "DOGE", "BUY", 1.0);
+        loaded.trade_type_val = "all-trades".to_string();
+        let store = MemoryStore::default();
+        let mut app = App::new(vec![loaded]);
+        let mut whale = trade("bob", "DOGE", "BUY", 2.0);
+        whale.trade_type_val = "whale-alert".to_string();
+        app.add_trade(whale, &store);
+        app.add_trade(trade("carol", "DOGE", "BUY", 3.0), &store);
+        let mut seen = Vec::new();
+        for _ in 0..4 {
+            app.toggle_trade_type_filter();
+            seen.push(app.trade_type_filter.clone());
+        }
+        assert_eq!(
+            seen,
+            [Some("all-trades".to_string()), Some("live-trade".to_string()), Some("whale-alert".to_string()), None]
+        );
+    }
+
+    #[test]
+    fn trades_fade_a_step_at_a_time() {
+        let decay = TradeDecay { step_secs: 60 };
+        assert_eq!(decay_level(Some(1_000), 1_059, decay), 0);
+        assert_eq!(decay_level(Some(1_000), 1_060, decay), 1);
+        assert_eq!(decay_level(Some(1_000), 1_130, decay), 2);
+        assert_eq!(decay_level(Some(1_000), 9_999, decay), 2);
+        // Clock skew can date a trade slightly in the future.
+        assert_eq!(decay_level(Some(1_005), 1_000, decay), 0);
+        assert_eq!(decay_level(None, 1_000, decay), 2);
+    }
+
+    #[test]
+    fn amount_and_total_are_formatted_independently() {
+        let mut app = App::new(Vec::new());
+        let configured = BTreeMap::from([("amount".to_string(), 4), ("coin".to_string(), 3), ("price".to_string(), 99)]);
+        let warnings;
+        (app.column_decimals, warnings) = resolve_column_decimals(&configured);
+        assert_eq!(app.column_decimals, HashMap::from([(Column::Amount, 4)]));
+        assert_eq!(warnings.len(), 2);
+        app.column_formats.insert(Column::Total, NumberFormat::Compact);
+        assert_eq!(Column::Amount.format_number(12345.678912, &app), "12345.6789");
+        assert_eq!(Column::Total.format_number(12345.678912, &app), "12.35K");
+        assert_eq!(Column::Total.format_number(12.345, &app), "12.35");
+        assert_eq!(Column::Price.format_number(0.5, &app), "0.50000000");
+    }
+
+    #[test]
+    fn auto_fit_names_are_cut_at_the_rendered_width() {
+        let mut app = App::new(Vec::new());
+        app.columns = vec![Column::User, Column::Amount, Column::Coin];
+        let area = Rect::new(0, 0, 80, 10);
+        assert_eq!(app.rendered_column_widths(&app.columns, area), [15, 10, 8]);
+        app.auto_fit_columns = true;
+        let widths = app.rendered_column_widths(&app.columns, area);
+        // Coin's 15 is well past the 8 it's cut to without auto-fit.
+        assert_eq!(widths, [46, 15, 15]);
+        assert_eq!(widths.iter().sum::<u16>() + 2, 78);
+    }
+
+    #[test]
+    fn the_legend_hint_follows_the_keymap() {
+        let mut app = App::new(Vec::new());
+        let configured = BTreeMap::from([("toggle_legend".to_string(), keymap::KeyBinding::One("ctrl-l".to_string()))]);
+        app.keymap = Keymap::from_config(&configured).0;
+        let legend: String = tier_legend_line(&app).spans.iter().map(|span| span.content.as_ref()).collect();
+        assert!(legend.ends_with("(ctrl-l to hide, C to switch)"), "{}", legend);
+        assert_eq!(app.key_hint(Action::RemoveEntry), "d");
+    }
+
+    #[test]
+    fn extra_decimals_widen_their_column() {
+        let mut app = App::new(Vec::new());
+        app.column_decimals = HashMap::from([(Column::Amount, 8), (Column::Total, 1)]);
+        assert_eq!(app.column_width(Column::Amount), Constraint::Length(16));
+        assert_eq!(app.column_width(Column::Total), Constraint::Length(12));
+        app.auto_fit_columns = true;
+        assert_eq!(app.column_width(Column::Amount), Constraint::Min(16));
+    }
+
+    fn seeded_db(recorded_at: &[Option<i64>]) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn, false, Synchronous::Off).unwrap();
+        for (i, at) in recorded_at.iter().enumerate() {
+            let mut trade = trade("alice", "DOGE", "BUY", i as f64 + 1.0);
+            trade.recorded_at = *at;
+            insert_trade_db(&conn, &trade).unwrap();
+            if at.is_none() {
+                // Saving fills in the current time, as for live trades.
+                conn.execute("UPDATE trades SET recorded_at = NULL WHERE id = ?1", params![conn.last_insert_rowid()])
+                    .unwrap();
+            }
+        }
+        conn
+    }
+
+    #[test]
+    fn notes_are_saved_replaced_and_removed() {
+        let conn = seeded_db(&[Some(100), Some(200)]);
+        save_note_db(&conn, 1, "wash trade?").unwrap();
+        save_note_db(&conn, 2, "first").unwrap();
+        save_note_db(&conn, 2, "second").unwrap();
+        let notes = load_notes_db(&conn).unwrap();
+        assert_eq!(notes.get(&1).map(String::as_str), Some("wash trade?"));
+        assert_eq!(notes.get(&2).map(String::as_str), Some("second"));
+        save_note_db(&conn, 1, "").unwrap();
+        assert_eq!(load_notes_db(&conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn retention_prunes_the_notes_of_pruned_trades() {
+        let now = Local::now().timestamp();
+        let conn = seeded_db(&[Some(now - 10 * 86_400), Some(now)]);
+        save_note_db(&conn, 1, "old").unwrap();
+        save_note_db(&conn, 2, "new").unwrap();
+        assert_eq!(retention::prune_trades_db(&conn, 86_400).unwrap(), 1);
+        assert_eq!(load_notes_db(&conn).unwrap().into_keys().collect::<Vec<_>>(), [2]);
+    }
+
+    #[test]
+    fn bookmarks_are_the_noted_trades_newest_first() {
+        let conn = seeded_db(&[Some(100), Some(200), Some(300)]);
+        save_note_db(&conn, 1, "early").unwrap();
+        save_note_db(&conn, 3, "late").unwrap();
+        let row_ids = |conn: &Connection| -> Vec<Option<i64>> {
+            load_bookmarked_trades_db(conn).unwrap().iter().map(|t| t.row_id).collect()
+        };
+        assert_eq!(row_ids(&conn), [Some(3), Some(1)]);
+        save_note_db(&conn, 3, "").unwrap();
+        assert_eq!(row_ids(&conn), [Some(1)]);
+    }
+
+    #[test]
+    fn trades_since_returns_newer_trades_newest_first() {
+        let conn = seeded_db(&[Some(100), Some(200), Some(300), Some(400)]);
+        let amounts: Vec<f64> = conn.trades_since(200, 10).unwrap().iter().map(|t| t.amount).collect();
+        assert_eq!(amounts, [4.0, 3.0]);
+        assert!(conn.trades_since(400, 10).unwrap().is_empty());
+        assert_eq!(conn.trades_since(0, 10).unwrap().len(), 4);
+        let amounts: Vec<f64> = conn.trades_since(0, 2).unwrap().iter().map(|t| t.amount).collect();
+        assert_eq!(amounts, [4.0, 3.0]);
+    }
+
+    #[test]
+    fn trades_since_skips_undated_trades() {
+        let conn = seeded_db(&[None, Some(500)]);
+        let trades = conn.trades_since(0, 10).unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].recorded_at, Some(500));
+    }
+}
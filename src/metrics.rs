@@ -0,0 +1,202 @@
+use crate::symbols;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, AtomicU8, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// How far back `RateMeter` averages.
+const RATE_WINDOW: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConnectionState {
+    Connecting = 0,
+    Connected = 1,
+    Disconnected = 2,
+    /// The reconnect limit was reached; the feed won't try again.
+    Failed = 3,
+}
+
+impl ConnectionState {
+    fn from_u8(value: u8) -> ConnectionState {
+        match value {
+            1 => ConnectionState::Connected,
+            2 => ConnectionState::Disconnected,
+            3 => ConnectionState::Failed,
+            _ => ConnectionState::Connecting,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ConnectionState::Connecting => "connecting",
+            ConnectionState::Connected => "connected",
+            ConnectionState::Disconnected => "disconnected",
+            ConnectionState::Failed => "failed",
+        }
+    }
+}
+
+/// Feed health counters, shared between the socket thread and the
+/// `/metrics` endpoint.
+pub struct Metrics {
+    trades_received: AtomicU64,
+    messages_dropped: AtomicU64,
+    incomplete_trades: AtomicU64,
+    trades_filtered: AtomicU64,
+    schema_mismatches: AtomicU64,
+    reconnects: AtomicU64,
+    connection_state: AtomicU8,
+    trades_per_coin: Mutex<HashMap<String, u64>>,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics {
+            trades_received: AtomicU64::new(0),
+            messages_dropped: AtomicU64::new(0),
+            incomplete_trades: AtomicU64::new(0),
+            trades_filtered: AtomicU64::new(0),
+            schema_mismatches: AtomicU64::new(0),
+            reconnects: AtomicU64::new(0),
+            connection_state: AtomicU8::new(ConnectionState::Connecting as u8),
+            trades_per_coin: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn record_trade(&self, coin_symbol: &str) {
+        self.trades_received.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut per_coin) = self.trades_per_coin.lock() {
+            *per_coin.entry(symbols::normalize(coin_symbol)).or_insert(0) += 1;
+        }
+    }
+
+    pub fn record_dropped(&self) {
+        self.messages_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_incomplete(&self) {
+        self.incomplete_trades.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_filtered(&self) {
+        self.trades_filtered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_schema_mismatch(&self) {
+        self.schema_mismatches.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn trades_received(&self) -> u64 {
+        self.trades_received.load(Ordering::Relaxed)
+    }
+
+    pub fn schema_mismatches(&self) -> u64 {
+        self.schema_mismatches.load(Ordering::Relaxed)
+    }
+
+    pub fn record_reconnect(&self) {
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_connection_state(&self, state: ConnectionState) {
+        self.connection_state.store(state as u8, Ordering::Relaxed);
+    }
+
+    pub fn connection_state(&self) -> ConnectionState {
+        ConnectionState::from_u8(self.connection_state.load(Ordering::Relaxed))
+    }
+
+    /// Renders everything in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let counters = [
+            ("trademaxxing_trades_received_total", "Trades parsed from the feed.", &self.trades_received),
+            ("trademaxxing_messages_dropped_total", "Feed messages that couldn't be decoded.", &self.messages_dropped),
+            (
+                "trademaxxing_incomplete_trades_total",
+                "Trades with a zero amount or price, whether dropped or kept.",
+                &self.incomplete_trades,
+            ),
+            (
+                "trademaxxing_trades_filtered_total",
+                "Trades discarded by the ingestion filter.",
+                &self.trades_filtered,
+            ),
+            (
+                "trademaxxing_schema_mismatches_total",
+                "Trades missing required fields or with fields of the wrong type.",
+                &self.schema_mismatches,
+            ),
+            ("trademaxxing_reconnects_total", "Websocket reconnect attempts.", &self.reconnects),
+        ];
+        for (name, help, counter) in counters {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, counter.load(Ordering::Relaxed));
+        }
+
+        let current = self.connection_state();
+        let _ = writeln!(out, "# HELP trademaxxing_connection_state Current websocket state (1 for the active state).");
+        let _ = writeln!(out, "# TYPE trademaxxing_connection_state gauge");
+        for state in [
+            ConnectionState::Connecting,
+            ConnectionState::Connected,
+            ConnectionState::Disconnected,
+            ConnectionState::Failed,
+        ] {
+            let _ = writeln!(
+                out,
+                "trademaxxing_connection_state{{state=\"{}\"}} {}",
+                state.label(),
+                u8::from(state == current)
+            );
+        }
+
+        let _ = writeln!(out, "# HELP trademaxxing_coin_trades Trades received this session per coin.");
+        let _ = writeln!(out, "# TYPE trademaxxing_coin_trades gauge");
+        if let Ok(per_coin) = self.trades_per_coin.lock() {
+            let mut coins: Vec<_> = per_coin.iter().collect();
+            coins.sort();
+            for (coin, count) in coins {
+                let _ = writeln!(out, "trademaxxing_coin_trades{{coin=\"{}\"}} {}", escape_label(coin), count);
+            }
+        }
+        out
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Trades per second arriving from the feed, from samples of the
+/// `trades_received` counter. Counting at the feed sees bursts the UI is
+/// still working through.
+#[derive(Default)]
+pub struct RateMeter {
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl RateMeter {
+    /// Records the counter at `now` and returns the rate over the last
+    /// `RATE_WINDOW`, or 0 until a second of samples has built up.
+    pub fn sample(&mut self, now: Instant, total: u64) -> f64 {
+        self.samples.push_back((now, total));
+        while self.samples.front().is_some_and(|&(at, _)| now.duration_since(at) > RATE_WINDOW) {
+            self.samples.pop_front();
+        }
+        let Some(&(first_at, first_total)) = self.samples.front() else {
+            return 0.0;
+        };
+        let elapsed = now.duration_since(first_at).as_secs_f64();
+        if elapsed < 1.0 {
+            return 0.0;
+        }
+        total.saturating_sub(first_total) as f64 / elapsed
+    }
+}
@@ -0,0 +1,189 @@
+use crate::{fuzzy::subsequence_score, keymap::Action};
+
+/// What a palette entry does once chosen.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Command {
+    /// Search for the argument, as if typed into the search box.
+    Search,
+    /// Show only `buy`, `sell` or `all` trades.
+    Filter,
+    /// Hide trades below the argument's USD value.
+    Min,
+    /// Sort the balances pane.
+    Sort,
+    /// Apply a named filter preset, or clear it with `off`.
+    Preset,
+    /// Show two coins side by side, or go back with `off`.
+    Compare,
+    Run(Action),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Entry {
+    pub name: &'static str,
+    /// Hint for the argument, empty when it takes none.
+    pub args: &'static str,
+    pub description: &'static str,
+    pub command: Command,
+}
+
+const ARGUMENT_COMMANDS: [Entry; 6] = [
+    Entry {
+        name: "search",
+        args: "<symbol|@user|?expr|!query>",
+        description: "Search, like the search box",
+        command: Command::Search,
+    },
+    Entry {
+        name: "filter",
+        args: "<buy|sell|all>",
+        description: "Show only buys or sells",
+        command: Command::Filter,
+    },
+    Entry {
+        name: "min",
+        args: "<usd>",
+        description: "Hide trades below a value",
+        command: Command::Min,
+    },
+    Entry {
+        name: "sort",
+        args: "<balance|asc|user>",
+        description: "Sort the balances pane",
+        command: Command::Sort,
+    },
+    Entry {
+        name: "preset",
+        args: "<name|off>",
+        description: "Apply a filter preset",
+        command: Command::Preset,
+    },
+    Entry {
+        name: "compare",
+        args: "<symbol> <symbol>|off",
+        description: "Show two coins side by side",
+        command: Command::Compare,
+    },
+];
+
+/// Every command the palette knows: the ones taking an argument, then each
+/// keymap action under its config name, less those that only work in a
+/// popup.
+pub fn registry() -> Vec<Entry> {
+    let actions = Action::ALL
+        .into_iter()
+        .filter(|action| !matches!(action, Action::CommandPalette | Action::RemoveEntry))
+        .map(|action| Entry {
+            name: action.name(),
+            args: "",
+            description: action.description(),
+            command: Command::Run(action),
+        });
+    ARGUMENT_COMMANDS.into_iter().chain(actions).collect()
+}
+
+/// State of the open `:` palette.
+#[derive(Default)]
+pub struct Palette {
+    pub input: String,
+    /// Index into `matches()`.
+    pub selected: usize,
+}
+
+impl Palette {
+    /// The command word and the (trimmed) rest of the input.
+    fn split(&self) -> (&str, &str) {
+        let input = self.input.trim_start();
+        match input.split_once(char::is_whitespace) {
+            Some((name, rest)) => (name, rest.trim()),
+            None => (input, ""),
+        }
+    }
+
+    /// Registry entries whose name fuzzily matches the command word, best
+    /// first.
+    pub fn matches(&self) -> Vec<Entry> {
+        let (name, _) = self.split();
+        let mut scored: Vec<(i64, Entry)> = registry()
+            .into_iter()
+            .filter_map(|entry| subsequence_score(name, entry.name).map(|score| (score, entry)))
+            .collect();
+        // Stable, so equal scores keep registry order.
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    /// The highlighted entry and the argument typed after the command word.
+    pub fn chosen(&self) -> Option<(Entry, String)> {
+        let entry = *self.matches().get(self.selected)?;
+        Some((entry, self.split().1.to_string()))
+    }
+
+    pub fn move_selection(&mut self, delta: isize) {
+        let last = self.matches().len().saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + delta).clamp(0, last) as usize;
+    }
+
+    /// Replaces the command word with the highlighted entry's full name.
+    pub fn complete(&mut self) {
+        if let Some((entry, argument)) = self.chosen() {
+            self.input = format!("{} {}", entry.name, argument);
+            self.selected = 0;
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.input.push(c);
+        self.selected = 0;
+    }
+
+    pub fn pop(&mut self) {
+        self.input.pop();
+        self.selected = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn palette(input: &str) -> Palette {
+        Palette {
+            input: input.to_string(),
+            selected: 0,
+        }
+    }
+
+    #[test]
+    fn best_matches_come_first() {
+        let first = |input| palette(input).matches().first().map(|entry| entry.name);
+        assert_eq!(first("quit"), Some("quit"));
+        assert_eq!(first("paus"), Some("pause"));
+        assert_eq!(first("tpin"), Some("toggle_pin"));
+        assert!(palette("zzz").matches().is_empty());
+        assert!(!palette("").matches().iter().any(|entry| entry.command == Command::Run(Action::CommandPalette)));
+    }
+
+    #[test]
+    fn the_argument_follows_the_command_word() {
+        let (entry, argument) = palette("  min   250 ").chosen().unwrap();
+        assert_eq!(entry.command, Command::Min);
+        assert_eq!(argument, "250");
+        let mut typed = palette("comp DOGE PEPE");
+        typed.complete();
+        assert_eq!(typed.input, "compare DOGE PEPE");
+    }
+
+    #[test]
+    fn selection_stays_within_the_matches() {
+        let mut typed = palette("sea");
+        let count = typed.matches().len();
+        typed.move_selection(100);
+        assert_eq!(typed.selected, count - 1);
+        typed.move_selection(-100);
+        assert_eq!(typed.selected, 0);
+        typed.move_selection(1);
+        typed.push('r');
+        assert_eq!(typed.selected, 0);
+    }
+}
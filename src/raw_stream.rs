@@ -0,0 +1,103 @@
+//! Records the websocket's raw frames to a file and plays them back through
+//! the normal parsing path, so feed bugs can be reproduced exactly.
+//!
+//! Each line is a JSON object: `{"received_at_ms": .., "text": ".."}` for
+//! text frames or `{"received_at_ms": .., "binary_hex": ".."}` for binary.
+
+use crate::{
+    feed::{handle_frame, IngestFilter},
+    metrics::{ConnectionState, Metrics},
+    trade_channel::BoundedSender,
+    Trade,
+};
+use chrono::Utc;
+use serde_json::{json, Value};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+use tungstenite::Message;
+
+/// Longer recorded silences are shortened to this on replay.
+const MAX_REPLAY_GAP: Duration = Duration::from_secs(2);
+
+pub struct RawRecorder {
+    file: File,
+}
+
+impl RawRecorder {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(RawRecorder { file })
+    }
+
+    /// Appends one data frame; control frames aren't recorded.
+    pub fn record(&mut self, msg: &Message) {
+        let received_at_ms = Utc::now().timestamp_millis();
+        let line = match msg {
+            Message::Text(text) => json!({ "received_at_ms": received_at_ms, "text": text.as_str() }),
+            Message::Binary(bytes) => json!({ "received_at_ms": received_at_ms, "binary_hex": to_hex(bytes) }),
+            _ => return,
+        };
+        let _ = writeln!(self.file, "{}", line);
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Turns a recording line back into its receive time and frame.
+fn parse_line(line: &str) -> Option<(i64, Message)> {
+    let v: Value = serde_json::from_str(line).ok()?;
+    let received_at_ms = v["received_at_ms"].as_i64()?;
+    let msg = if let Some(text) = v["text"].as_str() {
+        Message::Text(text.into())
+    } else {
+        Message::Binary(from_hex(v["binary_hex"].as_str()?)?.into())
+    };
+    Some((received_at_ms, msg))
+}
+
+/// Reads a recording up front so a bad path fails before the TUI starts.
+pub fn load_recording(path: &str) -> io::Result<Vec<String>> {
+    Ok(fs::read_to_string(path)?.lines().map(str::to_string).collect())
+}
+
+/// Feeds recorded frames into `tx` with their original spacing. Unreadable
+/// lines are skipped. The sender is held open afterwards so the UI keeps
+/// running with what was replayed.
+pub fn replay(lines: Vec<String>, tx: BoundedSender<Trade>, metrics: Arc<Metrics>, ingest: IngestFilter) {
+    metrics.set_connection_state(ConnectionState::Connected);
+    let mut previous_ms: Option<i64> = None;
+    for line in lines.iter().filter(|line| !line.trim().is_empty()) {
+        let Some((received_at_ms, msg)) = parse_line(line) else {
+            continue;
+        };
+        if let Some(previous_ms) = previous_ms {
+            let gap = Duration::from_millis((received_at_ms - previous_ms).max(0) as u64);
+            thread::sleep(gap.min(MAX_REPLAY_GAP));
+        }
+        previous_ms = Some(received_at_ms);
+        if handle_frame(&msg, &tx, &metrics, &ingest).is_err() {
+            return;
+        }
+    }
+    metrics.set_connection_state(ConnectionState::Disconnected);
+    loop {
+        thread::park();
+    }
+}
@@ -0,0 +1,59 @@
+use chrono::Local;
+use rusqlite::{params, Connection, Result as RusqliteResult};
+use std::{
+    sync::mpsc::Sender,
+    thread,
+    time::Duration,
+};
+
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Rows deleted per statement, so the UI's inserts never wait long for the lock.
+const PRUNE_BATCH_SIZE: i64 = 1000;
+
+/// Deletes trades recorded more than `max_age_secs` ago, and their notes.
+/// Trades saved before `recorded_at` existed have no date and are kept.
+pub fn prune_trades_db(conn: &Connection, max_age_secs: i64) -> RusqliteResult<usize> {
+    let cutoff = Local::now().timestamp() - max_age_secs;
+    let mut total = 0;
+    loop {
+        let tx = conn.unchecked_transaction()?;
+        let deleted = tx.execute(
+            "DELETE FROM trades WHERE id IN (SELECT id FROM trades WHERE recorded_at < ?1 LIMIT ?2)",
+            params![cutoff, PRUNE_BATCH_SIZE],
+        )?;
+        tx.execute("DELETE FROM trade_notes WHERE trade_row_id NOT IN (SELECT id FROM trades)", [])?;
+        tx.commit()?;
+        total += deleted;
+        if deleted < PRUNE_BATCH_SIZE as usize {
+            return Ok(total);
+        }
+    }
+}
+
+/// Prunes on its own connection every `PRUNE_INTERVAL`, reporting anything
+/// removed (or any failure) on `status_tx`. Stops once the UI has exited.
+pub fn spawn_pruner(db_path: String, max_age_secs: i64, status_tx: Sender<String>) {
+    thread::spawn(move || {
+        let conn = match Connection::open(&db_path).and_then(|conn| {
+            conn.busy_timeout(Duration::from_secs(5))?;
+            Ok(conn)
+        }) {
+            Ok(conn) => conn,
+            Err(e) => {
+                let _ = status_tx.send(format!("Retention pruning disabled: {}", e));
+                return;
+            }
+        };
+        loop {
+            thread::sleep(PRUNE_INTERVAL);
+            let message = match prune_trades_db(&conn, max_age_secs) {
+                Ok(0) => continue,
+                Ok(deleted) => format!("Pruned {} trades past retention", deleted),
+                Err(e) => format!("Failed to prune old trades: {}", e),
+            };
+            if status_tx.send(message).is_err() {
+                return;
+            }
+        }
+    });
+}
@@ -0,0 +1,66 @@
+use crate::config::ViewSampling;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// How far back arrivals count towards the feed rate.
+const RATE_WINDOW: Duration = Duration::from_secs(5);
+
+/// Decides which trades reach the tape while the feed is hot. Only the view
+/// is thinned; every trade is still saved and counted.
+pub struct Sampler {
+    settings: ViewSampling,
+    arrivals: VecDeque<Instant>,
+    since_last_shown: u32,
+}
+
+impl Sampler {
+    pub fn new(settings: ViewSampling) -> Self {
+        Sampler {
+            settings,
+            arrivals: VecDeque::new(),
+            since_last_shown: 0,
+        }
+    }
+
+    /// Records an arrival and returns whether the trade should be shown.
+    pub fn admit(&mut self, now: Instant) -> bool {
+        self.arrivals.push_back(now);
+        self.expire(now);
+        if !self.is_hot(now) {
+            self.since_last_shown = 0;
+            return true;
+        }
+        self.since_last_shown += 1;
+        if self.since_last_shown >= self.settings.every {
+            self.since_last_shown = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some(&oldest) = self.arrivals.front() {
+            if now.duration_since(oldest) <= RATE_WINDOW {
+                break;
+            }
+            self.arrivals.pop_front();
+        }
+    }
+
+    fn is_hot(&self, now: Instant) -> bool {
+        let recent = self
+            .arrivals
+            .iter()
+            .filter(|&&arrival| now.duration_since(arrival) <= RATE_WINDOW)
+            .count();
+        self.settings.every > 1 && recent as f64 / RATE_WINDOW.as_secs_f64() > self.settings.hot_trades_per_sec
+    }
+
+    /// The N in "1/N" while sampling is in effect.
+    pub fn active_rate(&self, now: Instant) -> Option<u32> {
+        self.is_hot(now).then_some(self.settings.every)
+    }
+}
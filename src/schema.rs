@@ -0,0 +1,273 @@
+//! Versioned database schema. `PRAGMA user_version` counts how many of
+//! `MIGRATIONS` a database has had applied; opening one runs the rest in
+//! order, after backing the file up.
+
+use crate::{backup_db, describe_db_error};
+use chrono::Local;
+use rusqlite::{Connection, Result as RusqliteResult};
+use std::{fmt, path::Path};
+
+/// One step from the previous version to the next. Runs in a transaction
+/// together with the version bump.
+struct Migration {
+    description: &'static str,
+    apply: fn(&Connection) -> RusqliteResult<()>,
+}
+
+/// In order; a database at version `n` has had the first `n` applied.
+/// Only ever append here: released databases already count these.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        description: "trades, price history, pins, recent coins, alerts and blocklist",
+        apply: baseline,
+    },
+    Migration {
+        description: "trade notes",
+        apply: trade_notes,
+    },
+];
+
+/// The version this build writes.
+pub const LATEST_VERSION: u32 = MIGRATIONS.len() as u32;
+
+#[derive(Debug)]
+pub enum SchemaError {
+    Db(rusqlite::Error),
+    /// The database was written by a newer build. Nothing was changed.
+    TooNew { found: u32, supported: u32 },
+    /// The backup before migrating failed, so nothing was migrated.
+    Backup { path: String, error: rusqlite::Error },
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::Db(error) => f.write_str(&describe_db_error(error)),
+            SchemaError::TooNew { found, supported } => write!(
+                f,
+                "its schema is version {}, but this build only knows up to version {} (upgrade trademaxxing, or point --db at another file)",
+                found, supported
+            ),
+            SchemaError::Backup { path, error } => write!(
+                f,
+                "couldn't back it up to {} before upgrading its schema, so nothing was changed: {}",
+                path,
+                describe_db_error(error)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl From<rusqlite::Error> for SchemaError {
+    fn from(error: rusqlite::Error) -> Self {
+        SchemaError::Db(error)
+    }
+}
+
+/// Brings `conn` up to `LATEST_VERSION`. A file that already holds tables
+/// is backed up next to itself first; a newer schema is left untouched.
+/// Returns the backup's path if one was made.
+pub fn migrate(conn: &Connection) -> Result<Option<String>, SchemaError> {
+    let version = version(conn)?;
+    if version > LATEST_VERSION {
+        return Err(SchemaError::TooNew {
+            found: version,
+            supported: LATEST_VERSION,
+        });
+    }
+    if version == LATEST_VERSION {
+        return Ok(None);
+    }
+    let backup = match backup_path(conn, version)? {
+        Some(path) => {
+            backup_db(conn, &path).map_err(|error| SchemaError::Backup {
+                path: path.clone(),
+                error,
+            })?;
+            Some(path)
+        }
+        None => None,
+    };
+    for (step, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let tx = conn.unchecked_transaction()?;
+        (migration.apply)(&tx).inspect_err(|e| {
+            eprintln!("Schema migration to version {} ({}) failed: {}", step + 1, migration.description, e)
+        })?;
+        tx.pragma_update(None, "user_version", step as u32 + 1)?;
+        tx.commit()?;
+    }
+    Ok(backup)
+}
+
+pub fn version(conn: &Connection) -> RusqliteResult<u32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+/// Where to back up an on-disk database with tables in it. Fresh files and
+/// in-memory databases have nothing worth keeping.
+fn backup_path(conn: &Connection, version: u32) -> RusqliteResult<Option<String>> {
+    let Some(path) = conn.path().filter(|path| !path.is_empty()) else {
+        return Ok(None);
+    };
+    let tables: i64 = conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table'", [], |row| row.get(0))?;
+    if tables == 0 {
+        return Ok(None);
+    }
+    let path = Path::new(path);
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("trades");
+    let name = format!("{}-v{}-backup-{}.db", stem, version, Local::now().format("%Y%m%d-%H%M%S"));
+    Ok(Some(path.with_file_name(name).to_string_lossy().into_owned()))
+}
+
+/// Everything from before the schema was versioned. Those databases are at
+/// version 0 in whatever shape they were left, so this only fills gaps.
+fn baseline(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS trades (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp TEXT NOT NULL,
+            trade_type_val TEXT NOT NULL,
+            action TEXT NOT NULL,
+            username TEXT NOT NULL,
+            amount REAL NOT NULL,
+            coin_symbol TEXT NOT NULL,
+            total_value REAL NOT NULL,
+            price REAL NOT NULL,
+            trade_id TEXT,
+            recorded_at INTEGER
+        )",
+        [],
+    )?;
+    // Databases created before trade ids were tracked lack the column.
+    if !column_exists(conn, "trades", "trade_id")? {
+        conn.execute("ALTER TABLE trades ADD COLUMN trade_id TEXT", [])?;
+    }
+    // `timestamp` has no date, so retention needs its own unix-seconds column.
+    if !column_exists(conn, "trades", "recorded_at")? {
+        conn.execute("ALTER TABLE trades ADD COLUMN recorded_at INTEGER", [])?;
+    }
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_trades_recorded_at ON trades (recorded_at)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS price_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            coin_symbol TEXT NOT NULL,
+            timestamp INTEGER NOT NULL,
+            price REAL NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_price_history_coin_time ON price_history (coin_symbol, timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS pinned_coins (
+            coin_symbol TEXT PRIMARY KEY,
+            position INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS recent_coins (
+            coin_symbol TEXT PRIMARY KEY,
+            position INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS price_alerts (
+            coin_symbol TEXT NOT NULL,
+            price REAL NOT NULL,
+            PRIMARY KEY (coin_symbol, price)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS blocklist (
+            kind TEXT NOT NULL,
+            value TEXT NOT NULL,
+            PRIMARY KEY (kind, value)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Unversioned databases may already have this from before versioning.
+fn trade_notes(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS trade_notes (
+            trade_row_id INTEGER PRIMARY KEY,
+            note TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn column_exists(conn: &Connection, table: &str, column: &str) -> RusqliteResult<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let names = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<RusqliteResult<Vec<String>>>()?;
+    Ok(names.iter().any(|name| name == column))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fresh_databases_get_every_migration() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(migrate(&conn).unwrap(), None);
+        assert_eq!(version(&conn).unwrap(), LATEST_VERSION);
+        assert!(column_exists(&conn, "trade_notes", "note").unwrap());
+        // Running again is a no-op.
+        migrate(&conn).unwrap();
+        assert_eq!(version(&conn).unwrap(), LATEST_VERSION);
+    }
+
+    #[test]
+    fn unversioned_databases_keep_their_trades() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE trades (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp TEXT NOT NULL,
+                trade_type_val TEXT NOT NULL,
+                action TEXT NOT NULL,
+                username TEXT NOT NULL,
+                amount REAL NOT NULL,
+                coin_symbol TEXT NOT NULL,
+                total_value REAL NOT NULL,
+                price REAL NOT NULL
+            );
+            INSERT INTO trades (timestamp, trade_type_val, action, username, amount, coin_symbol, total_value, price)
+                VALUES ('12:00:00', 'live-trade', 'BUY', 'alice', 1.0, 'DOGE', 10.0, 10.0);",
+        )
+        .unwrap();
+        migrate(&conn).unwrap();
+        assert_eq!(version(&conn).unwrap(), LATEST_VERSION);
+        assert!(column_exists(&conn, "trades", "recorded_at").unwrap());
+        let username: String = conn.query_row("SELECT username FROM trades", [], |row| row.get(0)).unwrap();
+        assert_eq!(username, "alice");
+    }
+
+    #[test]
+    fn newer_schemas_are_refused_untouched() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", LATEST_VERSION + 1).unwrap();
+        assert!(matches!(
+            migrate(&conn),
+            Err(SchemaError::TooNew { found, supported }) if found == LATEST_VERSION + 1 && supported == LATEST_VERSION
+        ));
+        let tables: i64 = conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get(0)).unwrap();
+        assert_eq!(tables, 0);
+    }
+}
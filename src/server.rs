@@ -0,0 +1,87 @@
+use crate::{export::trade_json, metrics::Metrics, store::TradeStore};
+use rusqlite::{Connection, OpenFlags};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+/// Requests are served one at a time, so a client that stalls mid-request
+/// or stops reading is dropped after this long.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Trades `/trades` returns when no `limit` is given.
+const DEFAULT_TRADES_LIMIT: usize = 1_000;
+const MAX_TRADES_LIMIT: usize = 10_000;
+
+/// Serves the HTTP endpoints on `addr` until the process exits. `db_path`
+/// is the trades database `/trades` reads, `None` when it's in memory.
+pub fn serve(addr: &str, metrics: Arc<Metrics>, db_path: Option<String>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let _ = handle_connection(stream, &metrics, db_path.as_deref());
+        }
+    });
+    Ok(())
+}
+
+fn handle_connection(mut stream: TcpStream, metrics: &Metrics, db_path: Option<&str>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/metrics") => ("200 OK", "text/plain; version=0.0.4", metrics.render()),
+        ("GET", path) if path == "/trades" || path.starts_with("/trades?") => trades_since(path, db_path),
+        _ => ("404 Not Found", "text/plain", "not found\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// `GET /trades?since=<unix seconds>&limit=<n>`: a JSON array of the
+/// newest `limit` trades recorded after `since`, newest first. Without
+/// `since`, any dated trade. `limit` defaults to `DEFAULT_TRADES_LIMIT` and
+/// is capped at `MAX_TRADES_LIMIT`.
+fn trades_since(path: &str, db_path: Option<&str>) -> (&'static str, &'static str, String) {
+    let param = |name: &str| {
+        path.split_once('?')
+            .into_iter()
+            .flat_map(|(_, query)| query.split('&'))
+            .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+    };
+    let since = match param("since").map(str::parse::<i64>) {
+        None => 0,
+        Some(Ok(since)) => since,
+        Some(Err(_)) => return ("400 Bad Request", "text/plain", "since must be Unix seconds\n".to_string()),
+    };
+    let limit = match param("limit").map(str::parse::<usize>) {
+        None => DEFAULT_TRADES_LIMIT,
+        Some(Ok(limit)) if limit > 0 => limit.min(MAX_TRADES_LIMIT),
+        Some(_) => return ("400 Bad Request", "text/plain", "limit must be a positive number\n".to_string()),
+    };
+    let Some(db_path) = db_path else {
+        return ("503 Service Unavailable", "text/plain", "trades are only kept in memory\n".to_string());
+    };
+    let trades = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| e.to_string())
+        .and_then(|conn| conn.trades_since(since, limit));
+    match trades {
+        Ok(trades) => {
+            let trades: Vec<serde_json::Value> = trades.iter().map(trade_json).collect();
+            ("200 OK", "application/json", serde_json::Value::from(trades).to_string() + "\n")
+        }
+        Err(e) => ("500 Internal Server Error", "text/plain", format!("{}\n", e)),
+    }
+}
@@ -0,0 +1,102 @@
+//! Saves what's on screen to a text file, for bug reports and sharing.
+
+use crate::config::SnapshotFormat;
+use chrono::Local;
+use ratatui::{
+    buffer::Buffer,
+    style::{Color, Modifier, Style},
+};
+use std::{fs, io};
+use unicode_width::UnicodeWidthStr;
+
+/// Writes `buffer` to `snapshot-<time>.txt` and returns the path.
+pub fn save(buffer: &Buffer, format: SnapshotFormat) -> io::Result<String> {
+    let path = format!("snapshot-{}.txt", Local::now().format("%Y%m%d-%H%M%S"));
+    fs::write(&path, render(buffer, format))?;
+    Ok(path)
+}
+
+/// One line per buffer row. Plain text drops trailing spaces; ANSI keeps
+/// every cell so backgrounds survive, and resets at the end of each line.
+pub fn render(buffer: &Buffer, format: SnapshotFormat) -> String {
+    let area = buffer.area;
+    let mut out = String::new();
+    for y in area.top()..area.bottom() {
+        let mut line = String::new();
+        let mut style = Style::default();
+        // Cells covered by the previous wide character hold filler.
+        let mut skip = 0;
+        for x in area.left()..area.right() {
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
+            let cell = buffer.get(x, y);
+            if format == SnapshotFormat::Ansi && cell.style() != style {
+                style = cell.style();
+                line += &sgr(style);
+            }
+            line += cell.symbol();
+            skip = cell.symbol().width().saturating_sub(1);
+        }
+        match format {
+            SnapshotFormat::Plain => out += line.trim_end(),
+            SnapshotFormat::Ansi => {
+                out += &line;
+                out += "\x1b[0m";
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// The escape sequence that switches to `style` from anything else.
+fn sgr(style: Style) -> String {
+    let mut codes = vec!["0".to_string()];
+    for (modifier, code) in [
+        (Modifier::BOLD, "1"),
+        (Modifier::DIM, "2"),
+        (Modifier::ITALIC, "3"),
+        (Modifier::UNDERLINED, "4"),
+        (Modifier::REVERSED, "7"),
+        (Modifier::CROSSED_OUT, "9"),
+    ] {
+        if style.add_modifier.contains(modifier) {
+            codes.push(code.to_string());
+        }
+    }
+    if let Some(code) = style.fg.and_then(|color| color_code(color, false)) {
+        codes.push(code);
+    }
+    if let Some(code) = style.bg.and_then(|color| color_code(color, true)) {
+        codes.push(code);
+    }
+    format!("\x1b[{}m", codes.join(";"))
+}
+
+fn color_code(color: Color, background: bool) -> Option<String> {
+    let base = if background { 40 } else { 30 };
+    let code = match color {
+        Color::Reset => return None,
+        Color::Black => base,
+        Color::Red => base + 1,
+        Color::Green => base + 2,
+        Color::Yellow => base + 3,
+        Color::Blue => base + 4,
+        Color::Magenta => base + 5,
+        Color::Cyan => base + 6,
+        Color::Gray => base + 7,
+        Color::DarkGray => base + 60,
+        Color::LightRed => base + 61,
+        Color::LightGreen => base + 62,
+        Color::LightYellow => base + 63,
+        Color::LightBlue => base + 64,
+        Color::LightMagenta => base + 65,
+        Color::LightCyan => base + 66,
+        Color::White => base + 67,
+        Color::Rgb(r, g, b) => return Some(format!("{};2;{};{};{}", base + 8, r, g, b)),
+        Color::Indexed(index) => return Some(format!("{};5;{}", base + 8, index)),
+    };
+    Some(code.to_string())
+}
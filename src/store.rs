@@ -0,0 +1,70 @@
+//! Where trades are saved. The app only talks to a `TradeStore`; SQLite
+//! (on `Connection`) is the one backend shipped. Pins, alerts and other
+//! settings tables still go straight to SQLite.
+
+use crate::{
+    db_search::{self, DbQuery},
+    describe_db_error, insert_trade_db, symbols, trade_from_row, Trade, TRADE_SELECT_SQL,
+};
+use rusqlite::{params, Connection, Params, Result as RusqliteResult};
+
+/// Errors are already worded for the status line.
+pub trait TradeStore {
+    /// Saves a trade and returns its row id.
+    fn insert(&self, trade: &Trade) -> Result<i64, String>;
+    /// Records a coin's price at `timestamp` (Unix seconds) for the chart.
+    fn insert_price_point(&self, coin_symbol: &str, timestamp: i64, price: f64) -> Result<(), String>;
+    /// The newest `limit` trades, or all of them, newest first.
+    fn load_recent(&self, limit: Option<usize>) -> Result<Vec<Trade>, String>;
+    /// Up to `limit` trades saved before row `before`, newest first.
+    fn load_page(&self, before: i64, limit: usize) -> Result<Vec<Trade>, String>;
+    /// The newest `limit` trades recorded after `since` (Unix seconds),
+    /// newest first. Trades saved before dates were kept never match.
+    fn trades_since(&self, since: i64, limit: usize) -> Result<Vec<Trade>, String>;
+    /// Trades matching a `!` search, newest first, at most
+    /// `db_search::RESULT_LIMIT`.
+    fn query(&self, query: &DbQuery) -> Result<Vec<Trade>, String>;
+}
+
+impl TradeStore for Connection {
+    fn insert(&self, trade: &Trade) -> Result<i64, String> {
+        insert_trade_db(self, trade).map_err(|e| describe_db_error(&e))?;
+        Ok(self.last_insert_rowid())
+    }
+
+    fn insert_price_point(&self, coin_symbol: &str, timestamp: i64, price: f64) -> Result<(), String> {
+        self.execute(
+            "INSERT INTO price_history (coin_symbol, timestamp, price) VALUES (?1, ?2, ?3)",
+            params![symbols::normalize(coin_symbol), timestamp, price],
+        )
+        .map(|_| ())
+        .map_err(|e| describe_db_error(&e))
+    }
+
+    fn load_recent(&self, limit: Option<usize>) -> Result<Vec<Trade>, String> {
+        // SQLite reads a negative LIMIT as no limit.
+        let limit = limit.map_or(-1, |limit| limit as i64);
+        select_trades(self, "ORDER BY id DESC LIMIT ?1", params![limit]).map_err(|e| describe_db_error(&e))
+    }
+
+    fn load_page(&self, before: i64, limit: usize) -> Result<Vec<Trade>, String> {
+        select_trades(self, "WHERE id < ?1 ORDER BY id DESC LIMIT ?2", params![before, limit as i64])
+            .map_err(|e| describe_db_error(&e))
+    }
+
+    fn trades_since(&self, since: i64, limit: usize) -> Result<Vec<Trade>, String> {
+        select_trades(self, "WHERE recorded_at > ?1 ORDER BY id DESC LIMIT ?2", params![since, limit as i64])
+            .map_err(|e| describe_db_error(&e))
+    }
+
+    fn query(&self, query: &DbQuery) -> Result<Vec<Trade>, String> {
+        db_search::search_trades_db(self, query).map_err(|e| describe_db_error(&e))
+    }
+}
+
+/// Trades for `TRADE_SELECT_SQL` followed by `clauses`.
+fn select_trades(conn: &Connection, clauses: &str, params: impl Params) -> RusqliteResult<Vec<Trade>> {
+    let mut stmt = conn.prepare(&format!("{} {}", TRADE_SELECT_SQL, clauses))?;
+    let trades = stmt.query_map(params, trade_from_row)?;
+    trades.collect()
+}
@@ -0,0 +1,32 @@
+//! Coin symbols are matched case-insensitively everywhere: sets, maps and
+//! comparisons all go through `normalize`. Trades keep the casing the feed
+//! sent, which is what gets displayed.
+
+/// The form symbols are stored and compared in: trimmed and upper case.
+pub fn normalize(symbol: &str) -> String {
+    symbol.trim().to_uppercase()
+}
+
+/// Whether two symbols name the same coin.
+pub fn same(a: &str, b: &str) -> bool {
+    normalize(a) == normalize(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_mixed_case_and_whitespace() {
+        assert_eq!(normalize("DoGe"), "DOGE");
+        assert_eq!(normalize("  pepe "), "PEPE");
+        assert_eq!(normalize("BTC2"), "BTC2");
+    }
+
+    #[test]
+    fn mixed_case_symbols_are_the_same_coin() {
+        assert!(same("doge", "DOGE"));
+        assert!(same("DoGe", " dOgE"));
+        assert!(!same("DOGE", "DOGEX"));
+    }
+}
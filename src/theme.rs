@@ -0,0 +1,161 @@
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    #[default]
+    Default,
+    HighContrast,
+    Monochrome,
+    Solarized,
+}
+
+impl ThemeName {
+    pub fn next(self) -> ThemeName {
+        match self {
+            ThemeName::Default => ThemeName::HighContrast,
+            ThemeName::HighContrast => ThemeName::Monochrome,
+            ThemeName::Monochrome => ThemeName::Solarized,
+            ThemeName::Solarized => ThemeName::Default,
+        }
+    }
+
+    /// The name as written in the config file.
+    pub fn label(self) -> &'static str {
+        match self {
+            ThemeName::Default => "default",
+            ThemeName::HighContrast => "high-contrast",
+            ThemeName::Monochrome => "monochrome",
+            ThemeName::Solarized => "solarized",
+        }
+    }
+
+    pub fn theme(self) -> Theme {
+        match self {
+            ThemeName::Default => DEFAULT,
+            ThemeName::HighContrast => HIGH_CONTRAST,
+            ThemeName::Monochrome => Theme { colored: false, ..DEFAULT },
+            ThemeName::Solarized => SOLARIZED,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ScrollbarStyle {
+    pub thumb: char,
+    pub track: char,
+    pub thumb_color: Color,
+    pub track_color: Color,
+}
+
+/// The colors the UI draws with, by role rather than by hue.
+#[derive(Clone, Copy, Debug)]
+pub struct Theme {
+    /// Headers, labels and key names.
+    pub accent: Color,
+    /// Buys and other good news.
+    pub positive: Color,
+    /// Sells, errors and warnings that need attention.
+    pub negative: Color,
+    pub neutral: Color,
+    /// Hints and secondary text.
+    pub muted: Color,
+    pub info: Color,
+    pub highlight: Color,
+    /// Cells whose data looks off.
+    pub warning: Color,
+    /// Row color and legend name for each value/amount tier, lowest first.
+    pub tiers: [(Color, &'static str); 4],
+    /// Coins-view shading from just traded to long quiet.
+    pub activity: [Color; 4],
+    /// Background of freshly arrived rows.
+    pub flash_bg: Color,
+    pub scrollbar: ScrollbarStyle,
+    /// `false` draws with modifiers only, like `--no-color`.
+    pub colored: bool,
+}
+
+const DEFAULT: Theme = Theme {
+    accent: Color::Yellow,
+    positive: Color::Green,
+    negative: Color::Red,
+    neutral: Color::Gray,
+    muted: Color::DarkGray,
+    info: Color::Cyan,
+    highlight: Color::Magenta,
+    warning: Color::LightYellow,
+    tiers: [
+        (Color::Cyan, "cyan"),
+        (Color::Yellow, "yellow"),
+        (Color::Magenta, "magenta"),
+        (Color::LightRed, "light red"),
+    ],
+    activity: [Color::LightGreen, Color::Green, Color::Gray, Color::DarkGray],
+    flash_bg: Color::DarkGray,
+    scrollbar: ScrollbarStyle {
+        thumb: '\u{2588}',
+        track: '\u{2591}',
+        thumb_color: Color::DarkGray,
+        track_color: Color::DarkGray,
+    },
+    colored: true,
+};
+
+const HIGH_CONTRAST: Theme = Theme {
+    accent: Color::LightYellow,
+    positive: Color::LightGreen,
+    negative: Color::LightRed,
+    neutral: Color::White,
+    muted: Color::Gray,
+    info: Color::LightCyan,
+    highlight: Color::LightMagenta,
+    warning: Color::LightYellow,
+    tiers: [
+        (Color::LightCyan, "cyan"),
+        (Color::LightYellow, "yellow"),
+        (Color::LightMagenta, "magenta"),
+        (Color::LightRed, "red"),
+    ],
+    activity: [Color::White, Color::LightGreen, Color::Gray, Color::DarkGray],
+    flash_bg: Color::Gray,
+    scrollbar: ScrollbarStyle {
+        thumb: '\u{2588}',
+        track: '\u{2591}',
+        thumb_color: Color::White,
+        track_color: Color::Gray,
+    },
+    colored: true,
+};
+
+// https://ethanschoonover.com/solarized/
+const SOLARIZED: Theme = Theme {
+    accent: Color::Rgb(0xb5, 0x89, 0x00),
+    positive: Color::Rgb(0x85, 0x99, 0x00),
+    negative: Color::Rgb(0xdc, 0x32, 0x2f),
+    neutral: Color::Rgb(0x83, 0x94, 0x96),
+    muted: Color::Rgb(0x58, 0x6e, 0x75),
+    info: Color::Rgb(0x2a, 0xa1, 0x98),
+    highlight: Color::Rgb(0xd3, 0x36, 0x82),
+    warning: Color::Rgb(0xcb, 0x4b, 0x16),
+    tiers: [
+        (Color::Rgb(0x26, 0x8b, 0xd2), "blue"),
+        (Color::Rgb(0xb5, 0x89, 0x00), "yellow"),
+        (Color::Rgb(0x6c, 0x71, 0xc4), "violet"),
+        (Color::Rgb(0xcb, 0x4b, 0x16), "orange"),
+    ],
+    activity: [
+        Color::Rgb(0x93, 0xa1, 0xa1),
+        Color::Rgb(0x85, 0x99, 0x00),
+        Color::Rgb(0x65, 0x7b, 0x83),
+        Color::Rgb(0x58, 0x6e, 0x75),
+    ],
+    flash_bg: Color::Rgb(0x07, 0x36, 0x42),
+    scrollbar: ScrollbarStyle {
+        thumb: '\u{2588}',
+        track: '\u{2591}',
+        thumb_color: Color::Rgb(0x93, 0xa1, 0xa1),
+        track_color: Color::Rgb(0x58, 0x6e, 0x75),
+    },
+    colored: true,
+};
@@ -0,0 +1,196 @@
+//! Bounded queue between the feed thread and the UI, so a stalled UI
+//! can't pile up trades (and latency) without limit.
+
+use crate::config::OverflowPolicy;
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::TryRecvError,
+        Arc, Condvar, Mutex,
+    },
+};
+
+struct State<T> {
+    queue: VecDeque<T>,
+    sender_alive: bool,
+    receiver_alive: bool,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    not_full: Condvar,
+    capacity: usize,
+    policy: OverflowPolicy,
+    dropped: AtomicU64,
+}
+
+pub struct BoundedSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+pub struct BoundedReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// A queue holding at most `capacity` (at least 1) items. When it's full
+/// the sender either waits for room or throws away the oldest queued item,
+/// depending on `policy`.
+pub fn bounded<T>(capacity: usize, policy: OverflowPolicy) -> (BoundedSender<T>, BoundedReceiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::new(),
+            sender_alive: true,
+            receiver_alive: true,
+        }),
+        not_full: Condvar::new(),
+        capacity: capacity.max(1),
+        policy,
+        dropped: AtomicU64::new(0),
+    });
+    (
+        BoundedSender {
+            shared: Arc::clone(&shared),
+        },
+        BoundedReceiver { shared },
+    )
+}
+
+impl<T> BoundedSender<T> {
+    /// Queues `item`. `Err` gives it back once the receiver is gone.
+    pub fn send(&self, item: T) -> Result<(), T> {
+        let Ok(mut state) = self.shared.state.lock() else {
+            return Err(item);
+        };
+        loop {
+            if !state.receiver_alive {
+                return Err(item);
+            }
+            if state.queue.len() < self.shared.capacity {
+                break;
+            }
+            match self.shared.policy {
+                OverflowPolicy::DropOldest => {
+                    state.queue.pop_front();
+                    self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+                    break;
+                }
+                OverflowPolicy::Block => match self.shared.not_full.wait(state) {
+                    Ok(guard) => state = guard,
+                    Err(_) => return Err(item),
+                },
+            }
+        }
+        state.queue.push_back(item);
+        Ok(())
+    }
+}
+
+impl<T> Drop for BoundedSender<T> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.shared.state.lock() {
+            state.sender_alive = false;
+        }
+    }
+}
+
+impl<T> BoundedReceiver<T> {
+    /// Like `mpsc::Receiver::try_recv`: `Disconnected` only once the sender
+    /// is gone and everything it sent has been taken.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.shared.state.lock().map_err(|_| TryRecvError::Disconnected)?;
+        match state.queue.pop_front() {
+            Some(item) => {
+                self.shared.not_full.notify_one();
+                Ok(item)
+            }
+            None if state.sender_alive => Err(TryRecvError::Empty),
+            None => Err(TryRecvError::Disconnected),
+        }
+    }
+
+    /// Items thrown away so far because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<T> Drop for BoundedReceiver<T> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.shared.state.lock() {
+            state.receiver_alive = false;
+        }
+        // Wake a sender blocked on a full queue so it sees we're gone.
+        self.shared.not_full.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{thread, time::Duration};
+
+    fn drain(rx: &BoundedReceiver<u32>) -> Vec<u32> {
+        std::iter::from_fn(|| rx.try_recv().ok()).collect()
+    }
+
+    #[test]
+    fn flooding_drops_the_oldest_items() {
+        let (tx, rx) = bounded(3, OverflowPolicy::DropOldest);
+        for i in 0..10 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.dropped(), 7);
+        assert_eq!(drain(&rx), [7, 8, 9]);
+    }
+
+    #[test]
+    fn nothing_is_dropped_below_capacity() {
+        let (tx, rx) = bounded(5, OverflowPolicy::DropOldest);
+        for i in 0..5 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.dropped(), 0);
+        assert_eq!(drain(&rx), [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn blocking_sender_waits_for_room() {
+        let (tx, rx) = bounded(2, OverflowPolicy::Block);
+        let sender = thread::spawn(move || {
+            for i in 0..50 {
+                tx.send(i).unwrap();
+            }
+        });
+        let mut received = Vec::new();
+        while received.len() < 50 {
+            match rx.try_recv() {
+                Ok(item) => received.push(item),
+                Err(TryRecvError::Empty) => thread::sleep(Duration::from_millis(1)),
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+        sender.join().unwrap();
+        assert_eq!(received, (0..50).collect::<Vec<_>>());
+        assert_eq!(rx.dropped(), 0);
+    }
+
+    #[test]
+    fn receiver_sees_disconnect_after_draining() {
+        let (tx, rx) = bounded(4, OverflowPolicy::DropOldest);
+        tx.send(1).unwrap();
+        drop(tx);
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn blocked_sender_gives_up_when_the_receiver_goes() {
+        let (tx, rx) = bounded(1, OverflowPolicy::Block);
+        tx.send(1).unwrap();
+        let sender = thread::spawn(move || tx.send(2));
+        thread::sleep(Duration::from_millis(20));
+        drop(rx);
+        assert_eq!(sender.join().unwrap(), Err(2));
+    }
+}
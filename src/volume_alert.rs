@@ -0,0 +1,80 @@
+use crate::{config::VolumeAlert, symbols};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+/// Rolling USD volume for one coin over the alert window.
+#[derive(Default)]
+struct CoinWindow {
+    trades: VecDeque<(Instant, f64)>,
+    volume: f64,
+    /// Set while the volume is over the threshold, so a spike fires once.
+    spiking: bool,
+    last_fired: Option<Instant>,
+}
+
+/// Watches per-coin volume over a rolling window and reports coins whose
+/// volume crosses `min_volume`. A coin fires once per spike: it has to drop
+/// back under the threshold and sit out `cooldown_secs` before it can fire
+/// again.
+pub struct VolumeWatcher {
+    settings: VolumeAlert,
+    coins: HashMap<String, CoinWindow>,
+}
+
+impl VolumeWatcher {
+    pub fn new(settings: VolumeAlert) -> Self {
+        VolumeWatcher {
+            settings,
+            coins: HashMap::new(),
+        }
+    }
+
+    pub fn settings(&self) -> VolumeAlert {
+        self.settings
+    }
+
+    pub fn record(&mut self, coin: &str, value: f64, now: Instant) {
+        let window = self.coins.entry(symbols::normalize(coin)).or_default();
+        window.trades.push_back((now, value));
+        window.volume += value;
+    }
+
+    /// Expires old trades and returns the coins that just started spiking,
+    /// with their volume over the window, biggest first.
+    pub fn check(&mut self, now: Instant) -> Vec<(String, f64)> {
+        let window_len = Duration::from_secs(self.settings.window_secs);
+        let cooldown = Duration::from_secs(self.settings.cooldown_secs);
+        let mut fired = Vec::new();
+        for (coin, window) in &mut self.coins {
+            while let Some(&(at, value)) = window.trades.front() {
+                if now.duration_since(at) <= window_len {
+                    break;
+                }
+                window.trades.pop_front();
+                window.volume -= value;
+            }
+            if window.trades.is_empty() {
+                // Don't let float drift accumulate across quiet spells.
+                window.volume = 0.0;
+            }
+
+            if window.volume < self.settings.min_volume {
+                window.spiking = false;
+                continue;
+            }
+            let cooled_down = window.last_fired.is_none_or(|last| now.duration_since(last) >= cooldown);
+            if !window.spiking && cooled_down {
+                window.last_fired = Some(now);
+                fired.push((coin.clone(), window.volume));
+            }
+            window.spiking = true;
+        }
+        self.coins.retain(|_, window| {
+            !window.trades.is_empty() || window.last_fired.is_some_and(|last| now.duration_since(last) < cooldown)
+        });
+        fired.sort_by(|a, b| b.1.total_cmp(&a.1));
+        fired
+    }
+}